@@ -0,0 +1,56 @@
+//! Benchmarks for grammar analyses and parsing on a large generated grammar.
+//! Run with `cargo bench`; each benchmark prints the average time per iteration.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bnf_parser::context_free_grammar::build_grammar;
+use bnf_parser::earley;
+use bnf_parser::sets::GrammarSets;
+
+/// Number of precedence levels in the generated grammar, three rules each.
+const LEVELS: usize = 1200;
+
+/// Builds an LL(1)-style expression grammar with one precedence level per operator:
+/// `<E_i> ::= <E_i+1> <R_i>`, `<R_i> ::= op_i <E_i+1> <R_i> | ε`, ending in identifiers and parentheses.
+fn expression_grammar(levels: usize) -> String {
+    let mut bnf = String::new();
+    for level in 0..levels {
+        bnf.push_str(&format!("<E{}> ::= <E{}> <R{}>\n", level, level + 1, level));
+        bnf.push_str(&format!("<R{}> ::= op{} <E{}> <R{}> | ε\n", level, level, level + 1, level));
+    }
+    bnf.push_str(&format!("<E{}> ::= id | ( <E0> )\n", levels));
+    bnf
+}
+
+/// Runs `f` repeatedly for at least a second and prints the average time per run.
+fn bench<T>(name: &str, mut f: impl FnMut() -> T) {
+    let mut runs = 0;
+    let start = Instant::now();
+    while runs < 3 || start.elapsed() < Duration::from_secs(1) {
+        black_box(f());
+        runs += 1;
+    }
+    let average = start.elapsed() / runs;
+    println!("{:<28} {:>12.3} ms/iter ({} runs)", name, average.as_secs_f64() * 1000.0, runs);
+}
+
+fn main() {
+    let bnf = expression_grammar(LEVELS);
+    let grammar = build_grammar(&bnf).unwrap();
+    println!("grammar: {} rules, {} variables\n", grammar.production_rules().len(), grammar.variables().len());
+
+    let sentence: Vec<String> = "id op0 ( id op7 id ) op1199 id op500 id"
+        .split_whitespace()
+        .map(|s| s.to_string())
+        .collect();
+    assert!(earley::recognizes(&grammar, &sentence));
+
+    bench("build_grammar", || build_grammar(&bnf).unwrap());
+    bench("rules_for every variable", || {
+        grammar.variables().iter().map(|variable| grammar.rules_for(variable).count()).sum::<usize>()
+    });
+    bench("FIRST and FOLLOW sets", || GrammarSets::new(&grammar).follow.len());
+    bench("earley recognize", || earley::recognizes(&grammar, &sentence));
+    bench("earley parse tree", || earley::parse(&grammar, &sentence, 1).len());
+}
@@ -0,0 +1,117 @@
+use crate::context_free_grammar::ContextFreeGrammar;
+use crate::earley::EarleyRecognizer;
+use crate::parse_tree::ParseTree;
+
+/// A sentence with two distinct parse trees, and so two distinct leftmost derivations.
+#[derive(Debug, PartialEq)]
+pub struct Witness {
+    pub sentence: Vec<String>,
+    pub trees: (ParseTree, ParseTree),
+}
+
+/// Outcome of a bounded search for ambiguity.
+#[derive(Debug, PartialEq)]
+pub enum Ambiguity {
+    Ambiguous(Witness),
+    /// No ambiguous sentence exists up to the searched length. Holds the number of sentences checked.
+    NotFound { checked: usize },
+}
+
+/// Searches every sentence of at most `max_len` terminals for one with two distinct parse trees.
+/// Sentences are tried by increasing length, so a reported witness is a shortest one.
+pub fn find_ambiguity(grammar: &ContextFreeGrammar, max_len: usize) -> Ambiguity {
+    let mut search = Search {
+        recognizer: EarleyRecognizer::new(grammar),
+        alphabet: grammar.terminals().iter().map(|s| s.as_str()).collect(),
+        prefix: Vec::new(),
+        checked: 0,
+    };
+
+    for len in 0..=max_len {
+        if let Some(witness) = search.find(len) {
+            return Ambiguity::Ambiguous(witness);
+        }
+    }
+
+    Ambiguity::NotFound { checked: search.checked }
+}
+
+/// Depth first search over the sentences of a grammar, pruned to viable prefixes.
+struct Search<'g> {
+    recognizer: EarleyRecognizer<'g>,
+    alphabet: Vec<&'g str>,
+    prefix: Vec<String>,
+    checked: usize,
+}
+impl Search<'_> {
+    /// Looks for an ambiguous sentence of exactly `len` terminals extending the current prefix.
+    fn find(&mut self, len: usize) -> Option<Witness> {
+        if self.prefix.len() == len {
+            if !self.recognizer.accepts() {
+                return None;
+            }
+            self.checked += 1;
+
+            let mut trees = self.recognizer.parse_trees(&self.prefix, 2);
+            if trees.len() < 2 {
+                return None;
+            }
+            let second = trees.pop()?;
+            let first = trees.pop()?;
+            return Some(Witness { sentence: self.prefix.clone(), trees: (first, second) });
+        }
+
+        for index in 0..self.alphabet.len() {
+            let terminal = self.alphabet[index];
+            let viable = self.recognizer.push(terminal);
+            self.prefix.push(terminal.to_string());
+
+            let found = if viable { self.find(len) } else { None };
+
+            self.prefix.pop();
+            self.recognizer.pop();
+
+            if found.is_some() {
+                return found;
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+
+    #[test]
+    fn test_dangling_else() {
+        let grammar = build_grammar("<S> ::= if c then <S> | if c then <S> else <S> | s").unwrap();
+
+        let Ambiguity::Ambiguous(witness) = find_ambiguity(&grammar, 9) else {
+            panic!("expected the dangling else to be ambiguous");
+        };
+
+        assert_eq!(witness.sentence.join(" "), "if c then if c then s else s");
+        assert_ne!(witness.trees.0, witness.trees.1);
+    }
+
+    #[test]
+    fn test_unambiguous() {
+        let grammar = build_grammar("<E> ::= <E> + <T> | <T>\n<T> ::= <T> * id | id").unwrap();
+
+        assert!(matches!(find_ambiguity(&grammar, 5), Ambiguity::NotFound { checked: 7 }));
+    }
+
+    #[test]
+    fn test_cycle_is_ambiguous() {
+        let grammar = build_grammar("<S> ::= <A> | a\n<A> ::= <S>").unwrap();
+
+        let Ambiguity::Ambiguous(witness) = find_ambiguity(&grammar, 2) else {
+            panic!("expected a unit cycle to be ambiguous");
+        };
+
+        assert_eq!(witness.sentence, vec!["a".to_string()]);
+    }
+}
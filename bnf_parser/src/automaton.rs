@@ -0,0 +1,897 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+
+/// Most characters a class may stand for when a regex is converted to an automaton.
+const MAX_CLASS_SIZE: usize = 256;
+
+use crate::commands::format_table;
+use crate::context_free_grammar::{is_plain_symbol, ContextFreeGrammar, ProductionRule, EPSILON};
+use crate::parse_tree::escape_dot;
+use crate::regex::Regex;
+
+/// Which side of its rules a regular grammar keeps its variables on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Linearity {
+    /// Every rule is `<A> ::= a b <B>` or `<A> ::= a b`.
+    Right,
+    /// Every rule is `<A> ::= <B> a b` or `<A> ::= a b`.
+    Left,
+}
+
+/// Returns whether a grammar is right- or left-linear, preferring right-linear if it is both.
+/// Otherwise returns a rule that keeps it from being right-linear, preferring one that is neither.
+pub fn linearity(grammar: &ContextFreeGrammar) -> Result<Linearity, &ProductionRule> {
+    let variable_positions = |rule: &ProductionRule| -> Vec<usize> {
+        (0..rule.rhs.len()).filter(|&i| grammar.is_variable(&rule.rhs[i])).collect()
+    };
+    let is_right_linear = |rule: &ProductionRule| match variable_positions(rule).as_slice() {
+        [] => true,
+        [position] => *position == rule.rhs.len() - 1,
+        _ => false,
+    };
+    let is_left_linear = |rule: &ProductionRule| match variable_positions(rule).as_slice() {
+        [] => true,
+        [position] => *position == 0,
+        _ => false,
+    };
+
+    let rules = grammar.production_rules();
+    if rules.iter().all(is_right_linear) {
+        Ok(Linearity::Right)
+    } else if rules.iter().all(is_left_linear) {
+        Ok(Linearity::Left)
+    } else {
+        let rule = rules
+            .iter()
+            .find(|rule| !is_right_linear(rule) && !is_left_linear(rule))
+            .or_else(|| rules.iter().find(|rule| !is_right_linear(rule)))
+            .expect("some rule is not right-linear");
+        Err(rule)
+    }
+}
+
+/// A nondeterministic finite automaton with ε-transitions over a grammar's terminals.
+#[derive(Debug, Clone)]
+pub struct Nfa {
+    pub alphabet: Vec<String>,
+    /// Name of each state, the variable it stands for where there is one.
+    pub states: Vec<String>,
+    pub start: usize,
+    pub accepting: Vec<bool>,
+    /// Transitions from each state as (symbol, target), None for ε.
+    pub transitions: Vec<Vec<(Option<usize>, usize)>>,
+}
+impl Nfa {
+    /// Builds an NFA accepting the language of a right- or left-linear grammar.
+    /// Each variable becomes a state and rules with several terminals get states in between.
+    pub fn from_grammar(grammar: &ContextFreeGrammar) -> Result<Nfa, &ProductionRule> {
+        let linearity = linearity(grammar)?;
+
+        let mut nfa = Nfa {
+            alphabet: grammar.terminals().iter().cloned().collect(),
+            states: grammar.variables().iter().cloned().collect(),
+            start: 0,
+            accepting: vec![false; grammar.variables().len()],
+            transitions: vec![Vec::new(); grammar.variables().len()],
+        };
+        let state_of = |variable: &str| grammar.variables().get_index_of(variable).expect("rules only use variables");
+        let start_state = state_of(grammar.start_symbol());
+
+        // right-linear: <A> derives the strings read from state <A> to the final state
+        // left-linear: <A> derives the strings read from the initial state to state <A>
+        let extra = nfa.add_state(match linearity {
+            Linearity::Right => "final",
+            Linearity::Left => "initial",
+        });
+        match linearity {
+            Linearity::Right => {
+                nfa.start = start_state;
+                nfa.accepting[extra] = true;
+            }
+            Linearity::Left => {
+                nfa.start = extra;
+                nfa.accepting[start_state] = true;
+            }
+        }
+
+        for rule in grammar.production_rules() {
+            let lhs = state_of(&rule.lhs);
+            let (variable, terminals) = match (linearity, rule.rhs.split_last(), rule.rhs.split_first()) {
+                (Linearity::Right, Some((last, rest)), _) if grammar.is_variable(last) => (Some(last), rest),
+                (Linearity::Left, _, Some((first, rest))) if grammar.is_variable(first) => (Some(first), rest),
+                _ => (None, rule.rhs.as_slice()),
+            };
+            let variable = variable.map(|variable| state_of(variable)).unwrap_or(extra);
+            let (from, to) = match linearity {
+                Linearity::Right => (lhs, variable),
+                Linearity::Left => (variable, lhs),
+            };
+            nfa.add_path(from, terminals, to);
+        }
+
+        Ok(nfa)
+    }
+
+    /// Builds an NFA with Thompson's construction accepting the strings a regex matches,
+    /// with every character of the regex as a symbol of the alphabet.
+    /// Fails for `.` and negated classes, which don't stand for a finite set of characters.
+    pub fn from_regex(regex: &Regex) -> Result<Nfa, String> {
+        Nfa::build_from_regex(regex, None)
+    }
+
+    /// Builds an NFA accepting the strings of some characters that a regex matches, with those
+    /// characters as the alphabet. `.`, classes and literals only stand for the characters given.
+    pub fn from_regex_over(regex: &Regex, characters: &[char]) -> Nfa {
+        Nfa::build_from_regex(regex, Some(characters)).expect("single characters are only matched against the ones given")
+    }
+
+    fn build_from_regex(regex: &Regex, characters: Option<&[char]>) -> Result<Nfa, String> {
+        let mut nfa = Nfa {
+            alphabet: characters.map(|characters| characters.iter().map(|c| c.to_string()).collect()).unwrap_or_default(),
+            states: Vec::new(),
+            start: 0,
+            accepting: Vec::new(),
+            transitions: Vec::new(),
+        };
+        let start = nfa.add_state("s0");
+        let end = nfa.add_regex(regex, start, characters)?;
+        nfa.accepting[end] = true;
+        Ok(nfa)
+    }
+
+    /// Adds the states of a regex starting at a state and returns the state they end at.
+    /// Single characters stand for those of `characters` they match, if given.
+    fn add_regex(&mut self, regex: &Regex, from: usize, characters: Option<&[char]>) -> Result<usize, String> {
+        Ok(match regex {
+            Regex::Empty => from,
+            Regex::Literal(_) | Regex::Any | Regex::Class { .. } if characters.is_some() => {
+                let to = self.fresh_state();
+                for (symbol, c) in characters.into_iter().flatten().enumerate() {
+                    if regex.matches_char(*c) {
+                        self.transitions[from].push((Some(symbol), to));
+                    }
+                }
+                to
+            }
+            Regex::Literal(c) => {
+                let to = self.fresh_state();
+                let symbol = self.symbol(*c)?;
+                self.transitions[from].push((Some(symbol), to));
+                to
+            }
+            Regex::Any | Regex::Class { negated: true, .. } => {
+                return Err(format!("{} doesn't stand for a finite set of characters", regex));
+            }
+            Regex::Class { negated: false, ranges } => {
+                let size: usize = ranges.iter().map(|&(low, high)| high as usize - low as usize + 1).sum();
+                if size > MAX_CLASS_SIZE {
+                    return Err(format!("{} stands for more than {} characters", regex, MAX_CLASS_SIZE));
+                }
+                let to = self.fresh_state();
+                for &(low, high) in ranges {
+                    for c in low..=high {
+                        let symbol = self.symbol(c)?;
+                        self.transitions[from].push((Some(symbol), to));
+                    }
+                }
+                to
+            }
+            Regex::Concat(parts) => {
+                let mut current = from;
+                for part in parts {
+                    current = self.add_regex(part, current, characters)?;
+                }
+                current
+            }
+            Regex::Alternation(alternatives) => {
+                let to = self.fresh_state();
+                for alternative in alternatives {
+                    let start = self.fresh_state();
+                    self.transitions[from].push((None, start));
+                    let end = self.add_regex(alternative, start, characters)?;
+                    self.transitions[end].push((None, to));
+                }
+                to
+            }
+            Regex::Repeat { inner, min, max } => {
+                let mut current = from;
+                for _ in 0..*min {
+                    current = self.add_regex(inner, current, characters)?;
+                }
+                match max {
+                    None => {
+                        let start = self.fresh_state();
+                        self.transitions[current].push((None, start));
+                        let end = self.add_regex(inner, start, characters)?;
+                        self.transitions[end].push((None, start));
+                        let to = self.fresh_state();
+                        self.transitions[start].push((None, to));
+                        to
+                    }
+                    Some(max) => {
+                        for _ in *min..*max {
+                            let next = self.fresh_state();
+                            self.transitions[current].push((None, next));
+                            let end = self.add_regex(inner, current, characters)?;
+                            self.transitions[end].push((None, next));
+                            current = next;
+                        }
+                        current
+                    }
+                }
+            }
+        })
+    }
+
+    /// Returns the alphabet index of a character, adding it if it is new.
+    fn symbol(&mut self, c: char) -> Result<usize, String> {
+        let symbol = c.to_string();
+        if !is_plain_symbol(&symbol) {
+            return Err(format!("'{}' can't be written as a terminal of a grammar", c.escape_default()));
+        }
+        Ok(match self.alphabet.iter().position(|existing| *existing == symbol) {
+            Some(index) => index,
+            None => {
+                self.alphabet.push(symbol);
+                self.alphabet.len() - 1
+            }
+        })
+    }
+
+    fn fresh_state(&mut self) -> usize {
+        let name = format!("s{}", self.states.len());
+        self.add_state(&name)
+    }
+
+    fn add_state(&mut self, name: &str) -> usize {
+        self.states.push(name.to_string());
+        self.accepting.push(false);
+        self.transitions.push(Vec::new());
+        self.states.len() - 1
+    }
+
+    /// Adds transitions reading `terminals` from one state to another, through fresh states.
+    fn add_path(&mut self, from: usize, terminals: &[String], to: usize) {
+        let symbols: Vec<usize> = terminals
+            .iter()
+            .map(|terminal| self.alphabet.iter().position(|symbol| symbol == terminal).expect("terminal of the grammar"))
+            .collect();
+        let Some((&last, rest)) = symbols.split_last() else {
+            self.transitions[from].push((None, to));
+            return;
+        };
+
+        let mut current = from;
+        for &symbol in rest {
+            let next = self.add_state(&format!("{}.{}", self.states[from], self.states.len()));
+            self.transitions[current].push((Some(symbol), next));
+            current = next;
+        }
+        self.transitions[current].push((Some(last), to));
+    }
+
+    /// Returns the states reachable from some states through ε-transitions alone.
+    fn epsilon_closure(&self, states: impl IntoIterator<Item = usize>) -> BTreeSet<usize> {
+        let mut closure: BTreeSet<usize> = BTreeSet::new();
+        let mut worklist: Vec<usize> = states.into_iter().collect();
+        while let Some(state) = worklist.pop() {
+            if closure.insert(state) {
+                worklist.extend(self.transitions[state].iter().filter(|(symbol, _)| symbol.is_none()).map(|(_, to)| *to));
+            }
+        }
+        closure
+    }
+
+    /// Converts the NFA to a DFA with the subset construction. Each DFA state is named after
+    /// the set of NFA states it stands for, and only subsets reachable from the start are built.
+    pub fn to_dfa(&self) -> Dfa {
+        let start = self.epsilon_closure([self.start]);
+        let mut subsets: Vec<BTreeSet<usize>> = vec![start.clone()];
+        let mut ids: HashMap<BTreeSet<usize>, usize> = HashMap::from([(start, 0)]);
+        let mut transitions: Vec<Vec<Option<usize>>> = Vec::new();
+
+        let mut index = 0;
+        while index < subsets.len() {
+            let mut row = Vec::new();
+            for symbol in 0..self.alphabet.len() {
+                let targets = subsets[index].iter().flat_map(|&state| {
+                    self.transitions[state].iter().filter(|(s, _)| *s == Some(symbol)).map(|(_, to)| *to)
+                });
+                let target = self.epsilon_closure(targets);
+                if target.is_empty() {
+                    row.push(None);
+                    continue;
+                }
+                let id = *ids.entry(target.clone()).or_insert_with(|| {
+                    subsets.push(target);
+                    subsets.len() - 1
+                });
+                row.push(Some(id));
+            }
+            transitions.push(row);
+            index += 1;
+        }
+
+        Dfa {
+            alphabet: self.alphabet.clone(),
+            states: subsets
+                .iter()
+                .map(|subset| {
+                    let names: Vec<&str> = subset.iter().map(|&state| self.states[state].as_str()).collect();
+                    format!("{{{}}}", names.join(", "))
+                })
+                .collect(),
+            start: 0,
+            accepting: subsets.iter().map(|subset| subset.iter().any(|&state| self.accepting[state])).collect(),
+            transitions,
+        }
+    }
+
+    /// Formats the transition table, with a column for ε-transitions. Each cell is the set of target states.
+    pub fn to_table(&self) -> String {
+        let mut header = vec!["".to_string(), "State".to_string(), EPSILON.to_string()];
+        header.extend(self.alphabet.iter().cloned());
+
+        let rows: Vec<Vec<String>> = (0..self.states.len())
+            .map(|state| {
+                let mut row = vec![marker(state == self.start, self.accepting[state]).to_string(), self.states[state].clone()];
+                for symbol in std::iter::once(None).chain((0..self.alphabet.len()).map(Some)) {
+                    let targets: Vec<&str> = self.transitions[state]
+                        .iter()
+                        .filter(|(s, _)| *s == symbol)
+                        .map(|(_, to)| self.states[*to].as_str())
+                        .collect();
+                    row.push(if targets.is_empty() { "-".to_string() } else { format!("{{{}}}", targets.join(", ")) });
+                }
+                row
+            })
+            .collect();
+
+        format_table(&header, &rows)
+    }
+
+    /// Renders the NFA as a Graphviz dot graph.
+    pub fn to_dot(&self) -> String {
+        let mut edges = Vec::new();
+        for (from, transitions) in self.transitions.iter().enumerate() {
+            for (symbol, to) in transitions {
+                let label = symbol.map_or(EPSILON, |symbol| self.alphabet[symbol].as_str());
+                edges.push((from, label, *to));
+            }
+        }
+        render_dot("nfa", &self.states, self.start, &self.accepting, &edges)
+    }
+}
+
+/// A deterministic finite automaton over a grammar's terminals. Missing transitions reject.
+#[derive(Debug, Clone)]
+pub struct Dfa {
+    pub alphabet: Vec<String>,
+    pub states: Vec<String>,
+    pub start: usize,
+    pub accepting: Vec<bool>,
+    /// The target of each state on each symbol of the alphabet, None if the input is rejected.
+    pub transitions: Vec<Vec<Option<usize>>>,
+}
+impl Dfa {
+    /// Reads a DFA from a file with a `start <state>` line, an `accept <states>` line and one
+    /// transition like `q0 a -> q1` per line. Symbols are whitespace separated words, like terminals.
+    pub fn parse(text: &str) -> Result<Dfa, DfaError> {
+        let mut dfa = Dfa {
+            alphabet: Vec::new(),
+            states: Vec::new(),
+            start: 0,
+            accepting: Vec::new(),
+            transitions: Vec::new(),
+        };
+        let mut start = None;
+        let mut accepting = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| DfaError { message, line: index + 1 };
+            let line = line.split('#').next().unwrap_or_default().trim();
+            match line.split_whitespace().collect::<Vec<&str>>().as_slice() {
+                [] => {}
+                ["start", state] => start = Some(dfa.state(state)),
+                ["start", ..] => return Err(error("expected a single state after 'start'".to_string())),
+                ["accept", states @ ..] => accepting.extend(states.iter().map(|state| dfa.state(state))),
+                [from, symbol, "->", to] => {
+                    let (from, to) = (dfa.state(from), dfa.state(to));
+                    let symbol = match dfa.alphabet.iter().position(|existing| existing == symbol) {
+                        Some(symbol) => symbol,
+                        None => {
+                            dfa.alphabet.push(symbol.to_string());
+                            dfa.transitions.iter_mut().for_each(|transitions| transitions.push(None));
+                            dfa.alphabet.len() - 1
+                        }
+                    };
+                    if dfa.transitions[from][symbol].is_some_and(|existing| existing != to) {
+                        return Err(error(format!(
+                            "{} already has a transition on {}",
+                            dfa.states[from], dfa.alphabet[symbol]
+                        )));
+                    }
+                    dfa.transitions[from][symbol] = Some(to);
+                }
+                _ => return Err(error("expected a transition like 'q0 a -> q1'".to_string())),
+            }
+        }
+
+        let line = text.lines().count().max(1);
+        dfa.start = start.ok_or_else(|| DfaError { message: "missing 'start <state>' line".to_string(), line })?;
+        for state in accepting {
+            dfa.accepting[state] = true;
+        }
+        Ok(dfa)
+    }
+
+    /// Returns the index of a state, adding it if it is new.
+    fn state(&mut self, name: &str) -> usize {
+        if let Some(state) = self.states.iter().position(|state| state == name) {
+            return state;
+        }
+        self.states.push(name.to_string());
+        self.accepting.push(false);
+        self.transitions.push(vec![None; self.alphabet.len()]);
+        self.states.len() - 1
+    }
+
+    /// Returns the state reached from a state on a symbol, or None if the symbol isn't in the alphabet
+    /// or has no transition.
+    pub fn step(&self, state: usize, symbol: &str) -> Option<usize> {
+        let symbol = self.alphabet.iter().position(|existing| existing == symbol)?;
+        self.transitions[state][symbol]
+    }
+
+    /// Returns the minimal DFA for the same language, using Hopcroft's partition refinement.
+    /// States are named q0, q1, ... in breadth first order from the start, and the dead state is left out.
+    pub fn minimize(&self) -> Dfa {
+        // complete the DFA with a sink state so every state has every transition
+        let sink = self.states.len();
+        let count = sink + 1;
+        let target = |state: usize, symbol: usize| {
+            if state == sink {
+                sink
+            } else {
+                self.transitions[state][symbol].unwrap_or(sink)
+            }
+        };
+        let accepting = |state: usize| state != sink && self.accepting[state];
+
+        let mut inverse: Vec<Vec<Vec<usize>>> = vec![vec![Vec::new(); count]; self.alphabet.len()];
+        for state in 0..count {
+            for (symbol, inverse) in inverse.iter_mut().enumerate() {
+                inverse[target(state, symbol)].push(state);
+            }
+        }
+
+        let (accepted, rejected): (Vec<usize>, Vec<usize>) = (0..count).partition(|&state| accepting(state));
+        let mut classes: Vec<Vec<usize>> = [accepted, rejected].into_iter().filter(|class| !class.is_empty()).collect();
+        let mut class_of = vec![0; count];
+        for (class, states) in classes.iter().enumerate() {
+            for &state in states {
+                class_of[state] = class;
+            }
+        }
+
+        // only the smaller of the two starting classes needs to be a splitter
+        let smallest = (0..classes.len()).min_by_key(|&class| classes[class].len()).expect("the sink is a state");
+        let mut worklist = vec![smallest];
+        let mut in_worklist = vec![false; classes.len()];
+        in_worklist[smallest] = true;
+
+        while let Some(splitter) = worklist.pop() {
+            in_worklist[splitter] = false;
+            let splitter_states = classes[splitter].clone();
+
+            for inverse in &inverse {
+                // the states that move into the splitter on this symbol, grouped by class
+                let mut hits: HashMap<usize, Vec<usize>> = HashMap::new();
+                for &state in &splitter_states {
+                    for &source in &inverse[state] {
+                        hits.entry(class_of[source]).or_default().push(source);
+                    }
+                }
+
+                for (class, mut inside) in hits {
+                    inside.sort_unstable();
+                    inside.dedup();
+                    if inside.len() == classes[class].len() {
+                        continue;
+                    }
+
+                    let outside: Vec<usize> =
+                        classes[class].iter().copied().filter(|state| inside.binary_search(state).is_err()).collect();
+                    let new_class = classes.len();
+                    let (kept, moved) = if inside.len() <= outside.len() { (outside, inside) } else { (inside, outside) };
+                    for &state in &moved {
+                        class_of[state] = new_class;
+                    }
+                    classes[class] = kept;
+                    classes.push(moved);
+                    in_worklist.push(false);
+
+                    // if the split class is still waiting both halves are, otherwise the smaller half is enough,
+                    // and either way that is the half that was moved out
+                    in_worklist[new_class] = true;
+                    worklist.push(new_class);
+                }
+            }
+        }
+
+        // classes that can't reach an accepting state are dead and become missing transitions
+        let mut live = vec![false; classes.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (class, states) in classes.iter().enumerate() {
+                let state = states[0];
+                let reaches_live = accepting(state)
+                    || (0..self.alphabet.len()).any(|symbol| live[class_of[target(state, symbol)]]);
+                if !live[class] && reaches_live {
+                    live[class] = true;
+                    changed = true;
+                }
+            }
+        }
+
+        // number the live classes in breadth first order from the start
+        let mut numbers: HashMap<usize, usize> = HashMap::new();
+        let mut order = Vec::new();
+        let mut queue = VecDeque::new();
+        let start_class = class_of[self.start];
+        if live[start_class] {
+            numbers.insert(start_class, 0);
+            order.push(start_class);
+            queue.push_back(start_class);
+        }
+        while let Some(class) = queue.pop_front() {
+            for symbol in 0..self.alphabet.len() {
+                let next = class_of[target(classes[class][0], symbol)];
+                if live[next] && !numbers.contains_key(&next) {
+                    numbers.insert(next, order.len());
+                    order.push(next);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        if order.is_empty() {
+            // the language is empty: a single rejecting state
+            return Dfa {
+                alphabet: self.alphabet.clone(),
+                states: vec!["q0".to_string()],
+                start: 0,
+                accepting: vec![false],
+                transitions: vec![vec![None; self.alphabet.len()]],
+            };
+        }
+
+        Dfa {
+            alphabet: self.alphabet.clone(),
+            states: (0..order.len()).map(|number| format!("q{}", number)).collect(),
+            start: 0,
+            accepting: order.iter().map(|&class| accepting(classes[class][0])).collect(),
+            transitions: order
+                .iter()
+                .map(|&class| {
+                    (0..self.alphabet.len())
+                        .map(|symbol| numbers.get(&class_of[target(classes[class][0], symbol)]).copied())
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the right-linear grammar of the DFA, with a variable `<Qn>` for each state `qn`:
+    /// `<Qp> ::= a <Qq>` for each transition and `<Qp> ::= ε` for each accepting state.
+    pub fn to_grammar(&self) -> ContextFreeGrammar {
+        let variable = |state: usize| format!("<Q{}>", state);
+
+        let mut rules = Vec::new();
+        for (state, transitions) in self.transitions.iter().enumerate() {
+            for (symbol, target) in transitions.iter().enumerate() {
+                if let Some(target) = target {
+                    rules.push(ProductionRule::new(&variable(state), &[&self.alphabet[symbol], &variable(*target)]));
+                }
+            }
+            if self.accepting[state] {
+                rules.push(ProductionRule::new(&variable(state), &[]));
+            }
+        }
+
+        ContextFreeGrammar::from_rules(&variable(self.start), rules)
+    }
+
+    /// Returns a regex for the language of the DFA by state elimination, or None if the language is empty.
+    /// `symbol` gives the regex each symbol of the alphabet stands for.
+    pub fn to_regex(&self, symbol: impl Fn(&str) -> Regex) -> Option<Regex> {
+        // a generalized NFA with a new start and final state, whose edges are labelled with regexes
+        let start = self.states.len();
+        let end = start + 1;
+        let mut edges: Vec<Vec<Option<Regex>>> = vec![vec![None; end + 1]; end + 1];
+        let add = |edges: &mut Vec<Vec<Option<Regex>>>, from: usize, to: usize, regex: Regex| {
+            let label = match edges[from][to].take() {
+                Some(existing) => Regex::alternatives(vec![existing, regex]),
+                None => regex,
+            };
+            edges[from][to] = Some(label);
+        };
+
+        add(&mut edges, start, self.start, Regex::Empty);
+        for (state, transitions) in self.transitions.iter().enumerate() {
+            for (index, target) in transitions.iter().enumerate() {
+                if let Some(target) = target {
+                    add(&mut edges, state, *target, symbol(&self.alphabet[index]));
+                }
+            }
+            if self.accepting[state] {
+                add(&mut edges, state, end, Regex::Empty);
+            }
+        }
+
+        // eliminate the state with the fewest paths through it first, which keeps the regex small
+        let mut remaining: Vec<usize> = (0..self.states.len()).collect();
+        while !remaining.is_empty() {
+            let paths = |state: usize| {
+                let incoming = (0..=end).filter(|&from| from != state && edges[from][state].is_some()).count();
+                let outgoing = (0..=end).filter(|&to| to != state && edges[state][to].is_some()).count();
+                incoming * outgoing
+            };
+            let position = (0..remaining.len()).min_by_key(|&i| paths(remaining[i])).expect("remaining is not empty");
+            let state = remaining.remove(position);
+
+            let others: Vec<usize> = remaining.iter().copied().chain([start, end]).collect();
+            let repeat = edges[state][state].take().map(Regex::star).unwrap_or(Regex::Empty);
+            for &from in &others {
+                let Some(into) = edges[from][state].take() else {
+                    continue;
+                };
+                for &to in &others {
+                    if let Some(out) = edges[state][to].clone() {
+                        add(&mut edges, from, to, Regex::concatenation(vec![into.clone(), repeat.clone(), out]));
+                    }
+                }
+            }
+            edges[state].fill(None);
+        }
+
+        edges[start][end].take()
+    }
+
+    /// Formats the transition table, marking the start state with `->` and accepting states with `*`.
+    /// Missing transitions are shown as `-`.
+    pub fn to_table(&self) -> String {
+        let mut header = vec!["".to_string(), "State".to_string()];
+        header.extend(self.alphabet.iter().cloned());
+
+        let rows: Vec<Vec<String>> = (0..self.states.len())
+            .map(|state| {
+                let mut row = vec![marker(state == self.start, self.accepting[state]).to_string(), self.states[state].clone()];
+                row.extend(
+                    self.transitions[state]
+                        .iter()
+                        .map(|target| target.map_or("-".to_string(), |target| self.states[target].clone())),
+                );
+                row
+            })
+            .collect();
+
+        format_table(&header, &rows)
+    }
+
+    /// Renders the DFA as a Graphviz dot graph.
+    pub fn to_dot(&self) -> String {
+        let mut edges = Vec::new();
+        for (from, transitions) in self.transitions.iter().enumerate() {
+            for (symbol, to) in transitions.iter().enumerate() {
+                if let Some(to) = to {
+                    edges.push((from, self.alphabet[symbol].as_str(), *to));
+                }
+            }
+        }
+        render_dot("dfa", &self.states, self.start, &self.accepting, &edges)
+    }
+}
+
+/// Error in a DFA file, with the line it was found on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DfaError {
+    pub message: String,
+    pub line: usize,
+}
+impl fmt::Display for DfaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+impl Error for DfaError {}
+
+/// Returns the marker of a state in a transition table: `->` for the start state and `*` for accepting states.
+fn marker(start: bool, accepting: bool) -> &'static str {
+    match (start, accepting) {
+        (true, true) => "->*",
+        (true, false) => "->",
+        (false, true) => "*",
+        (false, false) => "",
+    }
+}
+
+/// Renders an automaton as a Graphviz dot graph. Accepting states are drawn as double circles,
+/// and the labels of parallel edges are joined into one edge.
+fn render_dot(name: &str, states: &[String], start: usize, accepting: &[bool], edges: &[(usize, &str, usize)]) -> String {
+    let mut output = format!("digraph {} {{\n    rankdir=LR;\n    start [shape=point];\n", name);
+    for (id, state) in states.iter().enumerate() {
+        let shape = if accepting[id] { "doublecircle" } else { "circle" };
+        output.push_str(&format!("    s{} [label=\"{}\", shape={}];\n", id, escape_dot(state), shape));
+    }
+    output.push_str(&format!("    start -> s{};\n", start));
+
+    let mut labels: Vec<((usize, usize), Vec<&str>)> = Vec::new();
+    for &(from, label, to) in edges {
+        match labels.iter_mut().find(|(edge, _)| *edge == (from, to)) {
+            Some((_, edge_labels)) => edge_labels.push(label),
+            None => labels.push(((from, to), vec![label])),
+        }
+    }
+    for ((from, to), edge_labels) in labels {
+        output.push_str(&format!("    s{} -> s{} [label=\"{}\"];\n", from, to, escape_dot(&edge_labels.join(", "))));
+    }
+
+    output.push_str("}\n");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+    use crate::earley;
+
+    /// Returns every sentence over the grammar's terminals up to a length.
+    fn sentences(grammar: &ContextFreeGrammar, max_len: usize) -> Vec<Vec<String>> {
+        let mut sentences = vec![Vec::new()];
+        let mut index = 0;
+        while index < sentences.len() {
+            if sentences[index].len() < max_len {
+                for terminal in grammar.terminals() {
+                    let mut sentence = sentences[index].clone();
+                    sentence.push(terminal.clone());
+                    sentences.push(sentence);
+                }
+            }
+            index += 1;
+        }
+        sentences
+    }
+
+    /// Returns true if the DFA accepts a sentence of terminals.
+    fn accepts(dfa: &Dfa, sentence: &[String]) -> bool {
+        let mut state = dfa.start;
+        for terminal in sentence {
+            let Some(symbol) = dfa.alphabet.iter().position(|symbol| symbol == terminal) else {
+                return false;
+            };
+            match dfa.transitions[state][symbol] {
+                Some(next) => state = next,
+                None => return false,
+            }
+        }
+        dfa.accepting[state]
+    }
+
+    fn assert_same_language(grammar: &ContextFreeGrammar, dfa: &Dfa) {
+        for sentence in sentences(grammar, 6) {
+            assert_eq!(accepts(dfa, &sentence), earley::recognizes(grammar, &sentence), "{:?}", sentence);
+        }
+    }
+
+    #[test]
+    fn test_linearity() {
+        let right = build_grammar("<S> ::= a <S> | b <A>\n<A> ::= b c <A> | ε").unwrap();
+        assert_eq!(linearity(&right), Ok(Linearity::Right));
+
+        let left = build_grammar("<S> ::= <S> a | <A> b\n<A> ::= c").unwrap();
+        assert_eq!(linearity(&left), Ok(Linearity::Left));
+
+        let both = build_grammar("<S> ::= a <S> | <S> b | ε").unwrap();
+        assert_eq!(linearity(&both).unwrap_err(), &ProductionRule::new("<S>", &["<S>", "b"]));
+
+        let neither = build_grammar("<S> ::= a <S> b | ε").unwrap();
+        assert_eq!(linearity(&neither).unwrap_err(), &ProductionRule::new("<S>", &["a", "<S>", "b"]));
+    }
+
+    #[test]
+    fn test_right_linear_to_minimal_dfa() {
+        // a* b (b c)* with a redundant copy of <A>
+        let grammar = build_grammar("<S> ::= a <S> | b <A> | b <B>\n<A> ::= b c <A> | ε\n<B> ::= b c <B> | ε").unwrap();
+        let nfa = Nfa::from_grammar(&grammar).unwrap();
+        let dfa = nfa.to_dfa();
+        assert_same_language(&grammar, &dfa);
+
+        let minimal = dfa.minimize();
+        assert_same_language(&grammar, &minimal);
+        assert_eq!(minimal.states.len(), 3);
+        assert_eq!(
+            minimal.to_table(),
+            "     State   a    b    c\n->   q0      q0   q1   -\n*    q1      -    q2   -\n     q2      -    -    q1\n"
+        );
+    }
+
+    #[test]
+    fn test_left_linear_to_minimal_dfa() {
+        // a (a | b)* b
+        let grammar = build_grammar("<S> ::= <A> b\n<A> ::= <A> a | <A> b | a").unwrap();
+        let dfa = Nfa::from_grammar(&grammar).unwrap().to_dfa();
+        assert_same_language(&grammar, &dfa);
+
+        let minimal = dfa.minimize();
+        assert_same_language(&grammar, &minimal);
+        assert_eq!(minimal.states.len(), 3);
+    }
+
+    #[test]
+    fn test_empty_language() {
+        let grammar = build_grammar("<S> ::= a <S>").unwrap();
+        let minimal = Nfa::from_grammar(&grammar).unwrap().to_dfa().minimize();
+        assert_eq!(minimal.states.len(), 1);
+        assert!(!accepts(&minimal, &[]));
+    }
+
+    /// Returns the minimal DFA of a regex.
+    fn regex_dfa(pattern: &str) -> Dfa {
+        Nfa::from_regex(&Regex::parse(pattern).unwrap()).unwrap().to_dfa().minimize()
+    }
+
+    #[test]
+    fn test_regex_to_grammar() {
+        let grammar = regex_dfa("a(b|c)*").to_grammar();
+        assert_eq!(grammar.to_bnf(), "<Q0> ::= a <Q1>\n<Q1> ::= b <Q1> | c <Q1> | ε\n");
+
+        let grammar = regex_dfa("[a-c]{2,3}d?").to_grammar();
+        let sentence = |text: &str| text.chars().map(|c| c.to_string()).collect::<Vec<String>>();
+        for (text, accepted) in [("ab", true), ("abcd", true), ("cd", false), ("abbbd", false)] {
+            assert_eq!(earley::recognizes(&grammar, &sentence(text)), accepted, "{}", text);
+        }
+
+        assert!(Nfa::from_regex(&Regex::parse("a.b").unwrap()).is_err());
+        assert!(Nfa::from_regex(&Regex::parse("a b").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_grammar_to_regex() {
+        let to_regex = |bnf: &str| {
+            let grammar = build_grammar(bnf).unwrap();
+            let dfa = Nfa::from_grammar(&grammar).unwrap().to_dfa().minimize();
+            dfa.to_regex(Regex::literal).map(|regex| regex.to_string())
+        };
+
+        assert_eq!(to_regex("<S> ::= a <S> | b").as_deref(), Some("a*b"));
+        assert_eq!(to_regex("<S> ::= a <A>\n<A> ::= b <A> | c <A> | ε").as_deref(), Some("a(b|c)*"));
+        assert_eq!(to_regex("<S> ::= <S> x | id").as_deref(), Some("idx*"));
+        assert_eq!(to_regex("<S> ::= a <S>"), None);
+
+        // round trip through a regex keeps the language
+        for pattern in ["(ab|a)*b?", "x(yz)+|z", "(a|b)*abb"] {
+            let original = regex_dfa(pattern);
+            let round_trip = regex_dfa(&original.to_regex(Regex::literal).unwrap().to_string());
+            assert_eq!(round_trip.states.len(), original.states.len(), "{}", pattern);
+            for sentence in sentences(&original.to_grammar(), 6) {
+                assert_eq!(accepts(&round_trip, &sentence), accepts(&original, &sentence), "{} {:?}", pattern, sentence);
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let grammar = build_grammar("<S> ::= a <S> | b").unwrap();
+        let dot = Nfa::from_grammar(&grammar).unwrap().to_dfa().minimize().to_dot();
+        assert_eq!(
+            dot,
+            "digraph dfa {\n    rankdir=LR;\n    start [shape=point];\n    s0 [label=\"q0\", shape=circle];\n    \
+             s1 [label=\"q1\", shape=doublecircle];\n    start -> s0;\n    s0 -> s0 [label=\"a\"];\n    \
+             s0 -> s1 [label=\"b\"];\n}\n"
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+
+use crate::ambiguity::{self, Ambiguity};
+use crate::automaton::{self, Linearity};
+use crate::context_free_grammar::{format_rule, ContextFreeGrammar, EPSILON};
+use crate::ll;
+use crate::lr::{LrKind, LrTable};
+
+/// The classes of grammars `classify` checks, from the most specific to the most general.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GrammarClass {
+    Regular,
+    Ll1,
+    Slr1,
+    Lalr1,
+    Lr1,
+    /// No sentence has two parse trees.
+    Unambiguous,
+    /// No sentence of at most this many terminals has two parse trees.
+    UnambiguousWithinBound(usize),
+    ContextFree,
+}
+impl fmt::Display for GrammarClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GrammarClass::Regular => write!(f, "regular"),
+            GrammarClass::Ll1 => write!(f, "LL(1)"),
+            GrammarClass::Slr1 => write!(f, "SLR(1)"),
+            GrammarClass::Lalr1 => write!(f, "LALR(1)"),
+            GrammarClass::Lr1 => write!(f, "LR(1)"),
+            GrammarClass::Unambiguous => write!(f, "unambiguous"),
+            GrammarClass::UnambiguousWithinBound(max_len) => write!(f, "unambiguous up to length {}", max_len),
+            GrammarClass::ContextFree => write!(f, "context-free"),
+        }
+    }
+}
+
+/// Whether a grammar is in a class, with the reasons: what puts it in, or what keeps it out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Membership {
+    pub class: GrammarClass,
+    /// None if the checks couldn't tell either way.
+    pub holds: Option<bool>,
+    pub evidence: Vec<String>,
+}
+
+/// Checks a grammar against every class, in order from the most specific to the most general.
+/// Ambiguity is only searched for up to sentences of `max_len` terminals, and not at all for an LR(1) grammar.
+/// Only an LR(1) grammar is known to be unambiguous: a search that finds nothing leaves it unknown,
+/// and the grammar only unambiguous up to `max_len`.
+pub fn classify(grammar: &ContextFreeGrammar, max_len: usize) -> Vec<Membership> {
+    let mut memberships = Vec::new();
+    let mut add = |class, evidence: Vec<String>, holds| memberships.push(Membership { class, holds, evidence });
+
+    match automaton::linearity(grammar) {
+        Ok(Linearity::Right) => add(GrammarClass::Regular, vec!["every rule is right-linear".to_string()], Some(true)),
+        Ok(Linearity::Left) => add(GrammarClass::Regular, vec!["every rule is left-linear".to_string()], Some(true)),
+        Err(rule) => {
+            let line = rule.span.map(|span| format!("line {}: ", span.line)).unwrap_or_default();
+            let reason = format!("{}{} keeps the grammar from being right- or left-linear", line, format_rule(rule));
+            add(GrammarClass::Regular, vec![reason], Some(false));
+        }
+    }
+
+    let ll_conflicts: Vec<String> = ll::ll1_table(grammar).conflicts().iter().map(|c| c.to_string()).collect();
+    add(GrammarClass::Ll1, ll_conflicts.clone(), Some(ll_conflicts.is_empty()));
+
+    let mut lr1 = false;
+    for (class, kind) in [(GrammarClass::Slr1, LrKind::Slr), (GrammarClass::Lalr1, LrKind::Lalr), (GrammarClass::Lr1, LrKind::Lr1)] {
+        let conflicts: Vec<String> = LrTable::new(grammar, kind).conflicts().iter().map(|c| c.to_string()).collect();
+        lr1 = conflicts.is_empty();
+        add(class, conflicts, Some(lr1));
+    }
+
+    if lr1 {
+        add(GrammarClass::Unambiguous, vec!["every LR(1) grammar is unambiguous".to_string()], Some(true));
+    } else {
+        match ambiguity::find_ambiguity(grammar, max_len) {
+            Ambiguity::Ambiguous(witness) => {
+                let sentence =
+                    if witness.sentence.is_empty() { EPSILON.to_string() } else { witness.sentence.join(" ") };
+                add(GrammarClass::Unambiguous, vec![format!("{} has two parse trees", sentence)], Some(false));
+            }
+            Ambiguity::NotFound { checked } => {
+                let reason = format!("a sentence longer than {} terminals may be ambiguous", max_len);
+                add(GrammarClass::Unambiguous, vec![reason], None);
+                let reason = format!("no ambiguous sentence up to length {} ({} sentences checked)", max_len, checked);
+                add(GrammarClass::UnambiguousWithinBound(max_len), vec![reason], Some(true));
+            }
+        }
+    }
+
+    add(GrammarClass::ContextFree, Vec::new(), Some(true));
+    memberships
+}
+
+/// Returns the most specific class a grammar is known to be in, given its memberships in order.
+pub fn most_specific(memberships: &[Membership]) -> GrammarClass {
+    memberships.iter().find(|membership| membership.holds == Some(true)).map_or(GrammarClass::ContextFree, |m| m.class)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+
+    fn class_of(bnf: &str) -> GrammarClass {
+        most_specific(&classify(&build_grammar(bnf).unwrap(), 6))
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(class_of("<S> ::= a <S> | b"), GrammarClass::Regular);
+        assert_eq!(class_of("<E> ::= <T> <E'>\n<E'> ::= + <T> <E'> | ε\n<T> ::= id | ( <E> )"), GrammarClass::Ll1);
+        assert_eq!(class_of("<E> ::= <E> + <T> | <T>\n<T> ::= id | ( <E> )"), GrammarClass::Slr1);
+        assert_eq!(class_of("<S> ::= <L> = <R> | <R>\n<L> ::= * <R> | id\n<R> ::= <L>"), GrammarClass::Lalr1);
+        assert_eq!(class_of("<S> ::= a <A> d | b <B> d | a <B> e | b <A> e\n<A> ::= c\n<B> ::= c"), GrammarClass::Lr1);
+        // unambiguous, but LR(1) can't tell where the middle is, so only a bounded search finds nothing
+        assert_eq!(class_of("<S> ::= a <S> a | a"), GrammarClass::UnambiguousWithinBound(6));
+        assert_eq!(class_of("<E> ::= <E> + <E> | id"), GrammarClass::ContextFree);
+    }
+
+    #[test]
+    fn test_evidence() {
+        let grammar = build_grammar("<E> ::= <E> + <E> | id").unwrap();
+        let memberships = classify(&grammar, 6);
+        assert_eq!(memberships[0].evidence, ["line 1: <E> ::= <E> + <E> keeps the grammar from being right- or left-linear"]);
+        assert_eq!(memberships[1].evidence, ["<E> on id: <E> ::= <E> + <E> or <E> ::= id"]);
+        assert_eq!(memberships[5].evidence, ["id + id + id has two parse trees"]);
+        assert_eq!(memberships[5].holds, Some(false));
+
+        // the dangling else is only ambiguous in sentences longer than the search
+        let grammar = build_grammar("<S> ::= if c then <S> | if c then <S> else <S> | x").unwrap();
+        let memberships = classify(&grammar, 6);
+        assert_eq!(memberships[5].holds, None);
+        assert_eq!(memberships[6].class.to_string(), "unambiguous up to length 6");
+        assert_eq!(memberships[6].evidence, ["no ambiguous sentence up to length 6 (3 sentences checked)"]);
+        assert_eq!(most_specific(&memberships), GrammarClass::UnambiguousWithinBound(6));
+        assert_eq!(classify(&grammar, 9)[5].evidence, ["if c then if c then x else x has two parse trees"]);
+    }
+}
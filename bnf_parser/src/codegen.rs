@@ -0,0 +1,629 @@
+use std::collections::HashSet;
+
+use indexmap::IndexMap;
+
+use crate::context_free_grammar::{format_rule, ContextFreeGrammar, GrammarError, ProductionRule};
+use crate::ll;
+use crate::normal_form;
+use crate::regex::{Matcher, MatcherState, Regex};
+
+/// Generates the source of a standalone Rust module with a recursive-descent parser for an LL(1) grammar.
+///
+/// The module has a `Terminal` enum, a `tokenize` function that splits input like `bnf_parser parse`
+/// does, and for each variable reachable from the start symbol a tree node type with one variant per
+/// rule and a parse function choosing the rule by the next terminal. `parse` returns the node of the
+/// start symbol. The module doesn't depend on anything, so it can be written by a build script and
+/// included into a crate:
+///
+/// ```no_run
+/// // build.rs
+/// let source = std::fs::read_to_string("expr.bnf").unwrap();
+/// let grammar = bnf_parser::context_free_grammar::build_grammar(&source).unwrap();
+/// let parser = bnf_parser::codegen::generate_parser(&grammar).unwrap();
+/// let out_dir = std::env::var("OUT_DIR").unwrap();
+/// std::fs::write(std::path::Path::new(&out_dir).join("expr.rs"), parser).unwrap();
+/// println!("cargo:rerun-if-changed=expr.bnf");
+///
+/// // src/lib.rs: mod expr { include!(concat!(env!("OUT_DIR"), "/expr.rs")); }
+/// ```
+pub fn generate_parser(grammar: &ContextFreeGrammar) -> Result<String, GrammarError> {
+    normal_form::check_reachable_rules(grammar)?;
+    let reachable = normal_form::reachable_variables(grammar);
+    let table = ll::ll1_table(grammar);
+    let conflicts = table.conflicts();
+    if !conflicts.is_empty() {
+        return Err(GrammarError::conflicts("LL(1)", &conflicts));
+    }
+
+    let names = Names::new(grammar, reachable.iter().copied());
+    let mut output = format!(
+        "// Recursive-descent parser for the LL(1) grammar of {}, generated by bnf_parser.\n\
+         // Don't edit it by hand: change the grammar and generate it again.\n\n",
+        grammar.start_symbol()
+    );
+    output.push_str(&terminal_enum(grammar, &names));
+    output.push_str(TYPES);
+    output.push_str(&tokenizer(grammar, &names));
+
+    for &variable in &reachable {
+        output.push_str(&node_type(grammar, &names, variable));
+    }
+
+    let start = grammar.start_symbol();
+    output.push_str(&format!(
+        "\n/// Parses an input into the tree of the start symbol `{start}`.\n\
+         pub fn parse(input: &str) -> Result<{node}, ParseError> {{\n    \
+             parse_tokens(tokenize(input)?)\n\
+         }}\n\n\
+         /// Parses a sequence of tokens into the tree of the start symbol `{start}`.\n\
+         pub fn parse_tokens(tokens: Vec<Token>) -> Result<{node}, ParseError> {{\n    \
+             let end = tokens.last().map_or(0, |token| token.end);\n    \
+             let mut parser = Parser {{ tokens, position: 0, end }};\n    \
+             let tree = parser.{function}()?;\n    \
+             if parser.position < parser.tokens.len() {{\n        \
+                 return Err(parser.error(&[\"the end of the input\"]));\n    \
+             }}\n    \
+             Ok(tree)\n\
+         }}\n",
+        start = start,
+        node = names.variables[start],
+        function = names.functions[start],
+    ));
+
+    output.push_str(PARSER);
+    for &variable in &reachable {
+        output.push_str(&parse_function(grammar, &table, &names, variable));
+    }
+    output.push_str("}\n");
+    Ok(output)
+}
+
+/// Rust identifiers for the symbols of a grammar.
+struct Names<'g> {
+    terminals: IndexMap<&'g str, String>,
+    variables: IndexMap<&'g str, String>,
+    functions: IndexMap<&'g str, String>,
+}
+impl<'g> Names<'g> {
+    fn new(grammar: &'g ContextFreeGrammar, variables: impl Iterator<Item = &'g str>) -> Names<'g> {
+        let mut taken: HashSet<String> = RESERVED.iter().map(|name| name.to_string()).collect();
+        let terminals = grammar
+            .terminals()
+            .iter()
+            .map(|terminal| (terminal.as_str(), unique(type_name(terminal), &mut taken)))
+            .collect();
+
+        let mut taken: HashSet<String> = RESERVED.iter().map(|name| name.to_string()).collect();
+        let mut taken_functions = HashSet::new();
+        let mut names = Names { terminals, variables: IndexMap::new(), functions: IndexMap::new() };
+        for variable in variables {
+            let name = unique(type_name(variable), &mut taken);
+            let function = unique(format!("parse_{}", snake_case(&name)), &mut taken_functions);
+            names.variables.insert(variable, name);
+            names.functions.insert(variable, function);
+        }
+        names
+    }
+
+    /// Returns the name of a symbol of a rule's rhs as part of the name of the rule's variant.
+    fn symbol(&self, symbol: &str) -> &str {
+        self.variables.get(symbol).or_else(|| self.terminals.get(symbol)).expect("every symbol has a name")
+    }
+}
+
+/// Names used by the generated module itself, or which can't name a type or variant.
+const RESERVED: [&str; 14] = [
+    "Terminal", "Token", "ParseError", "Parser", "State", "Box", "Option", "Result", "Some", "None", "Ok", "Err", "String",
+    "Self",
+];
+
+/// Returns the name, with a number appended if it is already taken, and marks it as taken.
+fn unique(name: String, taken: &mut HashSet<String>) -> String {
+    let mut unique = name.clone();
+    let mut index = 2;
+    while !taken.insert(unique.clone()) {
+        unique = format!("{}{}", name, index);
+        index += 1;
+    }
+    unique
+}
+
+/// Turns a symbol into an UpperCamelCase identifier: `<expr.E>` becomes `ExprE`, `while` becomes
+/// `While`, and punctuation is spelled out, so `<=` becomes `LtEq`.
+fn type_name(symbol: &str) -> String {
+    let (symbol, variable) = match symbol.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+        Some(name) if !name.is_empty() => (name, true),
+        _ => (symbol, false),
+    };
+
+    let mut name = String::new();
+    let mut word_start = true;
+    for c in symbol.chars() {
+        if c.is_ascii_alphanumeric() {
+            if word_start {
+                name.push(c.to_ascii_uppercase());
+            } else {
+                name.push(c);
+            }
+            word_start = false;
+        } else {
+            word_start = true;
+            if c == '_' || (c == '.' && variable) {
+                continue;
+            }
+            name.push_str(&match c {
+                '+' => "Plus".to_string(),
+                '-' => "Minus".to_string(),
+                '*' => "Star".to_string(),
+                '/' => "Slash".to_string(),
+                '%' => "Percent".to_string(),
+                '(' => "LParen".to_string(),
+                ')' => "RParen".to_string(),
+                '[' => "LBracket".to_string(),
+                ']' => "RBracket".to_string(),
+                '{' => "LBrace".to_string(),
+                '}' => "RBrace".to_string(),
+                '<' => "Lt".to_string(),
+                '>' => "Gt".to_string(),
+                '=' => "Eq".to_string(),
+                '!' => "Bang".to_string(),
+                '&' => "Amp".to_string(),
+                '|' => "Pipe".to_string(),
+                '^' => "Caret".to_string(),
+                '~' => "Tilde".to_string(),
+                ',' => "Comma".to_string(),
+                ';' => "Semicolon".to_string(),
+                ':' => "Colon".to_string(),
+                '.' => "Dot".to_string(),
+                '?' => "Question".to_string(),
+                '@' => "At".to_string(),
+                '#' => "Hash".to_string(),
+                '$' => "Dollar".to_string(),
+                '\'' => "Quote".to_string(),
+                '"' => "DoubleQuote".to_string(),
+                '\\' => "Backslash".to_string(),
+                c => format!("U{:X}", c as u32),
+            });
+        }
+    }
+
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, 'T');
+    }
+    name
+}
+
+/// Turns an UpperCamelCase identifier into snake_case.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && previous_lower {
+            snake.push('_');
+        }
+        previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+fn terminal_enum(grammar: &ContextFreeGrammar, names: &Names) -> String {
+    let mut variants = String::new();
+    let mut arms = String::new();
+    for terminal in grammar.terminals() {
+        let name = &names.terminals[terminal.as_str()];
+        variants.push_str(&format!("    /// `{}`\n    {},\n", terminal, name));
+        arms.push_str(&format!("            Terminal::{} => {:?},\n", name, terminal));
+    }
+
+    format!(
+        "/// A terminal of the grammar.\n\
+         #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n\
+         pub enum Terminal {{\n{variants}}}\n\n\
+         impl Terminal {{\n    \
+             /// Returns the terminal as it is written in the grammar.\n    \
+             pub fn name(self) -> &'static str {{\n        \
+                 match self {{\n{arms}        }}\n    \
+             }}\n\
+         }}\n",
+        variants = variants,
+        arms = arms,
+    )
+}
+
+const TYPES: &str = "
+/// A terminal read from the input, with the text it matched and its byte offsets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub terminal: Terminal,
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Error for input that can't be tokenized or parsed, with the byte offsets of where it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, \"{} at bytes {}..{}\", self.message, self.start, self.end)
+    }
+}
+
+impl std::error::Error for ParseError {}
+";
+
+/// Returns the `tokenize` function, which like `lexer::tokenize` splits the input into whitespace separated
+/// words if the grammar has no token classes, and otherwise finds the longest matching terminal or token
+/// class, running the NFA of each token class's `Matcher`.
+fn tokenizer(grammar: &ContextFreeGrammar, names: &Names) -> String {
+    let literals: Vec<String> = grammar
+        .terminals()
+        .iter()
+        .filter(|terminal| !grammar.token_classes().contains_key(*terminal))
+        .map(|terminal| format!("    ({:?}, Terminal::{}),\n", terminal, names.terminals[terminal.as_str()]))
+        .collect();
+    let mut output = format!("\nconst LITERALS: &[(&str, Terminal)] = &[\n{}];\n", literals.concat());
+
+    let classes: Vec<(&String, &String)> =
+        grammar.token_classes().iter().filter(|(name, _)| grammar.terminals().contains(*name)).collect();
+    if classes.is_empty() {
+        output.push_str(WORD_TOKENIZER);
+        return output;
+    }
+
+    output.push_str("\n/// The token classes, each with the start state and states of the NFA matching it.\n");
+    output.push_str("const CLASSES: &[(Terminal, usize, &[State])] = &[\n");
+    for (name, pattern) in classes {
+        let regex = Regex::parse(pattern).expect("token class patterns are checked when the grammar is built");
+        let matcher = Matcher::new(&regex);
+        output.push_str(&format!("    // {} = /{}/\n", name, pattern));
+        output.push_str(&format!("    (Terminal::{}, {}, &[\n", names.terminals[name.as_str()], matcher.start()));
+        for state in matcher.states() {
+            let state = match state {
+                MatcherState::Char(atom, next) => {
+                    let (negated, ranges) = match atom {
+                        Regex::Literal(c) => (false, vec![(*c, *c)]),
+                        Regex::Any => (true, vec![('\n', '\n')]),
+                        Regex::Class { negated, ranges } => (*negated, ranges.clone()),
+                        _ => unreachable!("matcher states only consume single characters"),
+                    };
+                    let ranges: Vec<String> = ranges.iter().map(|(low, high)| format!("({:?}, {:?})", low, high)).collect();
+                    format!("State::Char({}, &[{}], {})", negated, ranges.join(", "), next)
+                }
+                MatcherState::Split(targets) => {
+                    let targets: Vec<String> = targets.iter().map(|target| target.to_string()).collect();
+                    format!("State::Split(&[{}])", targets.join(", "))
+                }
+                MatcherState::Accept => "State::Accept".to_string(),
+            };
+            output.push_str(&format!("        {},\n", state));
+        }
+        output.push_str("    ]),\n");
+    }
+    output.push_str("];\n");
+    output.push_str(LONGEST_MATCH_TOKENIZER);
+    output
+}
+
+const WORD_TOKENIZER: &str = "
+/// Splits an input into whitespace separated words, each of which must be a terminal.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    for word in input.split_whitespace() {
+        let start = word.as_ptr() as usize - input.as_ptr() as usize;
+        let end = start + word.len();
+        let Some(&(_, terminal)) = LITERALS.iter().find(|(text, _)| *text == word) else {
+            return Err(ParseError { message: format!(\"'{}' is not a terminal\", word), start, end });
+        };
+        tokens.push(Token { terminal, text: word.to_string(), start, end });
+    }
+    Ok(tokens)
+}
+";
+
+const LONGEST_MATCH_TOKENIZER: &str = "
+/// A state of the NFA of a token class.
+enum State {
+    /// Consumes a character in (or, if negated, not in) one of the inclusive ranges, then moves to a state.
+    Char(bool, &'static [(char, char)], usize),
+    /// Moves to any of the states without consuming input.
+    Split(&'static [usize]),
+    Accept,
+}
+
+/// Adds a state and every state reachable from it without consuming input.
+fn add_state(states: &[State], state: usize, set: &mut Vec<usize>) {
+    if set.contains(&state) {
+        return;
+    }
+    set.push(state);
+    if let State::Split(targets) = &states[state] {
+        for &target in *targets {
+            add_state(states, target, set);
+        }
+    }
+}
+
+/// Returns the length of the longest prefix of the input the NFA matches, if any prefix matches.
+fn longest_match(states: &[State], start: usize, input: &str) -> Option<usize> {
+    let accepts = |set: &[usize]| set.iter().any(|&state| matches!(states[state], State::Accept));
+    let mut current = Vec::new();
+    add_state(states, start, &mut current);
+    let mut longest = if accepts(&current) { Some(0) } else { None };
+
+    for (offset, c) in input.char_indices() {
+        let mut next = Vec::new();
+        for &state in &current {
+            if let State::Char(negated, ranges, target) = &states[state] {
+                if ranges.iter().any(|&(low, high)| low <= c && c <= high) != *negated {
+                    add_state(states, *target, &mut next);
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        if accepts(&next) {
+            longest = Some(offset + c.len_utf8());
+        }
+        current = next;
+    }
+    longest
+}
+
+/// Splits an input into the longest matching terminals and token classes, skipping whitespace between them.
+/// When several match the same longest prefix, a terminal wins over a token class, and earlier token classes
+/// win over later ones.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while let Some(c) = input[position..].chars().next() {
+        if c.is_whitespace() {
+            position += c.len_utf8();
+            continue;
+        }
+
+        let rest = &input[position..];
+        let literals = LITERALS.iter().filter(|(text, _)| rest.starts_with(text)).map(|&(text, terminal)| (terminal, text.len()));
+        let classes = CLASSES
+            .iter()
+            .filter_map(|&(terminal, start, states)| longest_match(states, start, rest).map(|length| (terminal, length)));
+        let mut longest: Option<(Terminal, usize)> = None;
+        for (terminal, length) in literals.chain(classes) {
+            if length > longest.map_or(0, |(_, longest_length)| longest_length) {
+                longest = Some((terminal, length));
+            }
+        }
+
+        let Some((terminal, length)) = longest else {
+            let message = format!(\"no terminal or token class matches the input at '{}'\", c);
+            return Err(ParseError { message, start: position, end: position + c.len_utf8() });
+        };
+        tokens.push(Token { terminal, text: rest[..length].to_string(), start: position, end: position + length });
+        position += length;
+    }
+    Ok(tokens)
+}
+";
+
+/// Returns the names of the variants of a variable's node type, one for each of its rules.
+fn variant_names<'g>(grammar: &'g ContextFreeGrammar, names: &Names, variable: &str) -> Vec<(&'g ProductionRule, String)> {
+    let mut taken = HashSet::from(["Self".to_string()]);
+    grammar
+        .rules_for(variable)
+        .map(|rule| {
+            let name = if rule.rhs.is_empty() {
+                "Empty".to_string()
+            } else {
+                rule.rhs.iter().map(|symbol| names.symbol(symbol)).collect()
+            };
+            (rule, unique(name, &mut taken))
+        })
+        .collect()
+}
+
+fn node_type(grammar: &ContextFreeGrammar, names: &Names, variable: &str) -> String {
+    let mut variants = String::new();
+    for (rule, name) in variant_names(grammar, names, variable) {
+        let fields: Vec<String> = rule
+            .rhs
+            .iter()
+            .map(|symbol| match names.variables.get(symbol.as_str()) {
+                Some(node) => format!("Box<{}>", node),
+                None => "Token".to_string(),
+            })
+            .collect();
+        let fields = if fields.is_empty() { String::new() } else { format!("({})", fields.join(", ")) };
+        variants.push_str(&format!("    /// `{}`\n    {}{},\n", format_rule(rule), name, fields));
+    }
+
+    format!(
+        "\n/// A node of `{}`.\n#[derive(Debug, Clone, PartialEq, Eq)]\npub enum {} {{\n{}}}\n",
+        variable, names.variables[variable], variants
+    )
+}
+
+const PARSER: &str = "
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// Offset of the end of the input, where an error about running out of input points.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<Terminal> {
+        self.tokens.get(self.position).map(|token| token.terminal)
+    }
+
+    fn expect(&mut self, terminal: Terminal) -> Result<Token, ParseError> {
+        match self.tokens.get(self.position) {
+            Some(token) if token.terminal == terminal => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            _ => Err(self.error(&[terminal.name()])),
+        }
+    }
+
+    fn error(&self, expected: &[&str]) -> ParseError {
+        let (found, start, end) = match self.tokens.get(self.position) {
+            Some(token) => (format!(\"'{}'\", token.text), token.start, token.end),
+            None => (\"the end of the input\".to_string(), self.end, self.end),
+        };
+        ParseError { message: format!(\"expected {} but found {}\", expected.join(\" or \"), found), start, end }
+    }
+";
+
+/// Returns the parse function of a variable, which chooses its rule by the next terminal as in the LL(1)
+/// table and parses the rule's symbols in order.
+fn parse_function(grammar: &ContextFreeGrammar, table: &ll::LlTable, names: &Names, variable: &str) -> String {
+    let node = &names.variables[variable];
+    let mut output = format!(
+        "\n    /// Parses `{}`.\n    fn {}(&mut self) -> Result<{}, ParseError> {{\n        match self.peek() {{\n",
+        variable, names.functions[variable], node
+    );
+
+    let pattern = |lookahead: &str| match lookahead {
+        "$" => "None".to_string(),
+        terminal => format!("Some(Terminal::{})", names.terminals[terminal]),
+    };
+    let row = &table.entries[variable];
+    for (rule, name) in variant_names(grammar, names, variable) {
+        let patterns: Vec<String> = row
+            .iter()
+            .filter(|(_, rules)| rules.contains(&rule))
+            .map(|(lookahead, _)| pattern(lookahead[0]))
+            .collect();
+        if patterns.is_empty() {
+            continue;
+        }
+
+        if rule.rhs.is_empty() {
+            output.push_str(&format!("            {} => Ok({}::{}),\n", patterns.join(" | "), node, name));
+            continue;
+        }
+
+        output.push_str(&format!("            {} => {{\n", patterns.join(" | ")));
+        let mut children = Vec::new();
+        for (index, symbol) in rule.rhs.iter().enumerate() {
+            let child = format!("child{}", index + 1);
+            let value = match names.functions.get(symbol.as_str()) {
+                Some(function) => format!("Box::new(self.{}()?)", function),
+                None => format!("self.expect(Terminal::{})?", names.terminals[symbol.as_str()]),
+            };
+            output.push_str(&format!("                let {} = {};\n", child, value));
+            children.push(child);
+        }
+        output.push_str(&format!("                Ok({}::{}({}))\n            }}\n", node, name, children.join(", ")));
+    }
+
+    // every lookahead has a rule when the row covers the end of the input and each terminal
+    if row.len() < grammar.terminals().len() + 1 {
+        let expected: Vec<String> = row
+            .keys()
+            .map(|lookahead| match lookahead[0] {
+                "$" => "\"the end of the input\"".to_string(),
+                terminal => format!("{:?}", terminal),
+            })
+            .collect();
+        output.push_str(&format!("            _ => Err(self.error(&[{}])),\n", expected.join(", ")));
+    }
+    output.push_str("        }\n    }\n");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+    use std::fs;
+    use std::process::Command;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(type_name("<expr.E>"), "ExprE");
+        assert_eq!(type_name("<number_literal>"), "NumberLiteral");
+        assert_eq!(type_name("<="), "LtEq");
+        assert_eq!(type_name("while"), "While");
+        assert_eq!(type_name("."), "Dot");
+        assert_eq!(type_name("2"), "T2");
+        assert_eq!(snake_case("ExprE"), "expr_e");
+        assert_eq!(snake_case("NP"), "np");
+
+        let grammar = build_grammar("<Token> ::= <token> | <Token2>\n<token> ::= a\n<Token2> ::= b").unwrap();
+        let names = Names::new(&grammar, grammar.variables().iter().map(|variable| variable.as_str()));
+        assert_eq!(names.variables.values().collect::<Vec<_>>(), ["Token2", "Token3", "Token22"]);
+        assert_eq!(names.functions.values().collect::<Vec<_>>(), ["parse_token2", "parse_token3", "parse_token22"]);
+    }
+
+    #[test]
+    fn test_rejects_grammars() {
+        let error = generate_parser(&build_grammar("<E> ::= <E> + id | id").unwrap()).unwrap_err();
+        assert_eq!(error.message, "the grammar is not LL(1):\n    <E> on id: <E> ::= <E> + id or <E> ::= id");
+        let error = generate_parser(&build_grammar("<S> ::= a <X>").unwrap()).unwrap_err();
+        assert_eq!(error.message, "<X> has no rules");
+    }
+
+    /// Compiles a generated parser with a main function printing the trees of some inputs, and runs it.
+    #[test]
+    fn test_generated_parser() {
+        let grammar = build_grammar(
+            "@token <num> = /[0-9]+/
+             @token <id> = /[a-z]+/
+             <E> ::= <T> <R>
+             <R> ::= + <T> <R> | ε
+             <T> ::= <num> | <id> | ( <E> ) | let <id> = <E> ;",
+        )
+        .unwrap();
+        let source = generate_parser(&grammar).unwrap();
+        let main = r#"
+fn main() {
+    for input in ["1 + x", "(letter+2)", "let y = 3;", "1 +", "1)", "1 # 2"] {
+        match parse(input) {
+            Ok(tree) => println!("{:?}", tree),
+            Err(error) => println!("{}", error),
+        }
+    }
+}
+"#;
+
+        let dir = std::env::temp_dir().join(format!("bnf_parser_codegen_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("main.rs"), source + main).unwrap();
+        let compiled = Command::new("rustc")
+            .args(["--edition", "2021", "-D", "warnings", "-o"])
+            .arg(dir.join("parser"))
+            .arg(dir.join("main.rs"))
+            .output()
+            .unwrap();
+        assert!(compiled.status.success(), "{}", String::from_utf8_lossy(&compiled.stderr));
+
+        let output = Command::new(dir.join("parser")).output().unwrap();
+        let lines: Vec<String> = String::from_utf8(output.stdout).unwrap().lines().map(|line| line.to_string()).collect();
+        let token = |terminal: &str, text: &str, start: usize| {
+            format!("Token {{ terminal: {}, text: {:?}, start: {}, end: {} }}", terminal, text, start, start + text.len())
+        };
+        assert_eq!(
+            lines[0],
+            format!(
+                "TR(Num({}), PlusTR({}, Id({}), Empty))",
+                token("Num", "1", 0),
+                token("Plus", "+", 2),
+                token("Id", "x", 4)
+            )
+        );
+        // keywords win over token classes only when they match as much of the input
+        assert!(lines[1].starts_with("TR(LParenERParen(") && lines[1].contains("text: \"letter\""), "{}", lines[1]);
+        assert!(lines[2].starts_with("TR(LetIdEqESemicolon("), "{}", lines[2]);
+        assert_eq!(lines[3], "expected <num> or <id> or ( or let but found the end of the input at bytes 3..3");
+        assert_eq!(lines[4], "expected the end of the input but found ')' at bytes 1..2");
+        assert_eq!(lines[5], "no terminal or token class matches the input at '#' at bytes 2..3");
+    }
+}
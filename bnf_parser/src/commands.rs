@@ -0,0 +1,884 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use indexmap::IndexSet;
+
+use crate::ambiguity::{self, Ambiguity};
+use crate::automaton::{Dfa, Nfa};
+use crate::classify;
+use crate::codegen;
+use crate::regex::Regex;
+use crate::context_free_grammar::{self as cfg, ContextFreeGrammar};
+use crate::derivation::{self, Highlight, Order};
+use crate::diff;
+use crate::earley;
+use crate::equivalence::{self, Equivalence};
+use crate::error::CliError;
+use crate::generator::{Rng, SentenceGenerator};
+use crate::include;
+use crate::intersection;
+use crate::language::{self, LanguageSize};
+use crate::lexer;
+use crate::lint::{self, LintCode};
+use crate::normal_form;
+use crate::parse_tree;
+use crate::pda::{Pda, Run};
+use crate::recovery;
+use crate::semantics;
+use crate::ll;
+use crate::sets::{GrammarSets, LookaheadSets};
+use crate::{
+    AutomatonFormat, AutomatonStage, GrammarFormat, NormalForm, OutputFormat, ParserKind, RegularLanguage, TreeFormat,
+};
+
+/// Most strings of a finite language the check command lists.
+const CHECK_MAX_STRINGS: usize = 50;
+
+/// Prints a grammar to the screen in the requested format.
+pub fn show(grammar_path: &str, output_format: OutputFormat) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+    print_grammar(&grammar, output_format);
+
+    Ok(())
+}
+
+/// Reports undefined, non-generating and unreachable variables of a grammar.
+pub fn check(grammar_path: &str) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+
+    let mut output = String::new();
+    let result = check_grammar(&grammar, &mut output);
+    print!("{}", output);
+    result
+}
+
+/// Writes the report of the check command for a grammar to `output`.
+fn check_grammar(grammar: &ContextFreeGrammar, output: &mut String) -> Result<(), CliError> {
+    writeln!(output, "Start symbol: {}", grammar.start_symbol()).unwrap();
+    writeln!(
+        output,
+        "Variables: {}, terminals: {}, production rules: {}",
+        grammar.variables().len(),
+        grammar.terminals().len(),
+        grammar.production_rules().len()
+    )
+    .unwrap();
+    match language::language_size(grammar, CHECK_MAX_STRINGS) {
+        LanguageSize::Empty => writeln!(output, "Language: empty").unwrap(),
+        LanguageSize::Infinite => writeln!(output, "Language: infinite").unwrap(),
+        LanguageSize::Finite(None) => {
+            writeln!(output, "Language: finite, more than {} strings", CHECK_MAX_STRINGS).unwrap()
+        }
+        LanguageSize::Finite(Some(strings)) => {
+            writeln!(output, "Language: finite, {}", counted(strings.len(), "string")).unwrap();
+            for string in &strings {
+                writeln!(output, "    {}", format_sentence(string)).unwrap();
+            }
+        }
+    }
+
+    let mut errors = Vec::new();
+    for variable in grammar.variables() {
+        if grammar.rules_for(variable).next().is_none() {
+            let line = grammar
+                .production_rules()
+                .iter()
+                .find(|rule| rule.rhs.contains(variable))
+                .and_then(|rule| rule.span)
+                .map(|span| format!("line {}: ", span.line))
+                .unwrap_or_default();
+            errors.push(format!("{}{} is used but has no production rules", line, variable));
+        }
+    }
+
+    let mut warnings = Vec::new();
+    let generating = normal_form::generating_variables(grammar);
+    let reachable = normal_form::reachable_variables(grammar);
+    for variable in grammar.variables() {
+        if !generating.contains(variable.as_str()) && grammar.rules_for(variable).next().is_some() {
+            warnings.push(format!("{} can't derive any string of terminals", variable));
+        }
+        if !reachable.contains(variable.as_str()) {
+            warnings.push(format!("{} can't be reached from the start symbol", variable));
+        }
+    }
+
+    for error in &errors {
+        writeln!(output, "error: {}", error).unwrap();
+    }
+    for warning in &warnings {
+        writeln!(output, "warning: {}", warning).unwrap();
+    }
+
+    if errors.is_empty() {
+        writeln!(output, "OK ({})", counted(warnings.len(), "warning")).unwrap();
+        Ok(())
+    } else {
+        Err(CliError::Grammar(format!("{}, {}", counted(errors.len(), "error"), counted(warnings.len(), "warning"))))
+    }
+}
+
+/// Prints the lints of a grammar, leaving out allowed ones and reporting denied ones as errors.
+pub fn lint(grammar_path: &str, allow: &[LintCode], deny: &[LintCode]) -> Result<(), CliError> {
+    // JSON grammars have no source to find duplicate alternatives or directive comments in
+    let (grammar, source) = if grammar_path.ends_with(".json") {
+        (read_grammar(grammar_path, None)?, String::new())
+    } else {
+        let source = read_input(grammar_path)?;
+        (include::build_grammar_with_includes(&source, &grammar_dir(grammar_path))?, source)
+    };
+
+    let mut output = String::new();
+    let result = lint_grammar(&grammar, &source, allow, deny, &mut output);
+    print!("{}", output);
+    result
+}
+
+/// Writes the lints of a grammar built from `source` to `output`, as the lint command reports them.
+fn lint_grammar(
+    grammar: &ContextFreeGrammar,
+    source: &str,
+    allow: &[LintCode],
+    deny: &[LintCode],
+    output: &mut String,
+) -> Result<(), CliError> {
+    let mut errors = 0;
+    let mut warnings = 0;
+    for lint in lint::lint(grammar, source) {
+        if deny.contains(&lint.code) {
+            writeln!(output, "error: {}", lint).unwrap();
+            errors += 1;
+        } else if !allow.contains(&lint.code) {
+            writeln!(output, "warning: {}", lint).unwrap();
+            warnings += 1;
+        }
+    }
+
+    if errors == 0 {
+        writeln!(output, "OK ({})", counted(warnings, "warning")).unwrap();
+        Ok(())
+    } else {
+        Err(CliError::Grammar(format!("{}, {}", counted(errors, "error"), counted(warnings, "warning"))))
+    }
+}
+
+/// Prints a grammar converted to a normal form.
+pub fn normalize(grammar_path: &str, form: NormalForm, output_format: OutputFormat) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+
+    let normalized = match form {
+        NormalForm::Chomsky => normal_form::to_chomsky_normal_form(&grammar),
+        NormalForm::Reduced => normal_form::reduce(&grammar),
+    };
+    print_grammar(&normalized, output_format);
+
+    Ok(())
+}
+
+/// Prints the nullable, FIRST and FOLLOW sets of every variable, or of a single variable.
+/// With more than one token of lookahead, prints the FIRST_k and FOLLOW_k sets instead.
+pub fn sets(grammar_path: &str, variable: Option<&str>, k: usize) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+
+    let variables: Vec<&str> = match variable {
+        Some(variable) if grammar.is_variable(variable) => vec![variable],
+        Some(variable) => return Err(CliError::Usage(format!("{} is not a variable of the grammar", variable))),
+        None => grammar.variables().iter().map(|v| v.as_str()).collect(),
+    };
+
+    if k > 1 {
+        let sets = LookaheadSets::new(&grammar, k);
+        let rows: Vec<[String; 3]> = variables
+            .iter()
+            .map(|variable| {
+                [variable.to_string(), format_strings(&sets.first[variable]), format_strings(&sets.follow[variable])]
+            })
+            .collect();
+
+        let header = ["Variable".to_string(), format!("FIRST_{}", k), format!("FOLLOW_{}", k)];
+        print!("{}", format_table(&header, &rows));
+        return Ok(());
+    }
+
+    let sets = GrammarSets::new(&grammar);
+
+    let rows: Vec<[String; 4]> = variables
+        .iter()
+        .map(|variable| {
+            [
+                variable.to_string(),
+                if sets.nullable.contains(variable) { "yes" } else { "no" }.to_string(),
+                format_set(&sets.first[variable]),
+                format_set(&sets.follow[variable]),
+            ]
+        })
+        .collect();
+
+    let header = ["Variable", "Nullable", "FIRST", "FOLLOW"].map(|s| s.to_string());
+    print!("{}", format_table(&header, &rows));
+
+    Ok(())
+}
+
+/// Parses a sentence and prints up to `trees` of its parse trees in the requested format.
+/// The LL(1) and LR parsers print one tree, with error nodes for any syntax errors, which are then reported.
+pub fn parse(
+    grammar_path: &str,
+    sentence: &str,
+    parser: ParserKind,
+    tree_format: TreeFormat,
+    trees: usize,
+) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+
+    let tokens = lexer::tokenize(&grammar, sentence)?;
+    let parse = match parser {
+        ParserKind::Earley => return parse_earley(&grammar, &tokens, tree_format, trees),
+        ParserKind::Ll1 => recovery::parse_ll1(&grammar, &tokens)?,
+        ParserKind::Lr(kind) => recovery::parse_lr(&grammar, kind, &tokens)?,
+    };
+
+    print_tree(&parse.tree, tree_format)?;
+    if parse.errors.is_empty() {
+        return Ok(());
+    }
+    let errors: Vec<String> = parse.errors.iter().map(|error| format!("    {}", error)).collect();
+    Err(CliError::Rejected(format!("Found {}:\n{}", counted(errors.len(), "syntax error"), errors.join("\n"))))
+}
+
+/// Parses tokens with the Earley parser and prints up to `trees` of their parse trees.
+fn parse_earley(
+    grammar: &ContextFreeGrammar,
+    tokens: &[lexer::Token],
+    tree_format: TreeFormat,
+    trees: usize,
+) -> Result<(), CliError> {
+    let terminals = lexer::terminals(tokens);
+    let parse_trees = earley::parse(grammar, &terminals, trees.max(1));
+    if parse_trees.is_empty() {
+        return Err(not_in_language(&terminals));
+    }
+
+    for (index, mut tree) in parse_trees.into_iter().enumerate() {
+        tree.attach_spans(tokens);
+        if trees > 1 && tree_format == TreeFormat::Ascii {
+            println!("Parse tree {}", index + 1);
+        }
+        print_tree(&tree, tree_format)?;
+    }
+
+    Ok(())
+}
+
+fn print_tree(tree: &parse_tree::ParseTree, tree_format: TreeFormat) -> Result<(), CliError> {
+    match tree_format {
+        TreeFormat::Ascii => print!("{}", tree.to_ascii()),
+        TreeFormat::Dot => print!("{}", tree.to_dot()),
+        TreeFormat::Json => println!("{}", tree.to_json().map_err(|e| CliError::Grammar(e.to_string()))?),
+    }
+    Ok(())
+}
+
+/// Prints the leftmost or rightmost derivation of a sentence, one sentential form per line.
+pub fn derive(grammar_path: &str, sentence: &str, rightmost: bool, color: bool) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+
+    let terminals = lexer::terminals(&lexer::tokenize(&grammar, sentence)?);
+    let trees = earley::parse(&grammar, &terminals, 2);
+    let Some(tree) = trees.first() else {
+        return Err(not_in_language(&terminals));
+    };
+    if trees.len() > 1 {
+        println!("Note: the sentence is ambiguous, showing the derivation of its first parse tree.\n");
+    }
+
+    let order = if rightmost { Order::Rightmost } else { Order::Leftmost };
+    let highlight = if color { Highlight::Ansi } else { Highlight::Brackets };
+
+    let steps = derivation::derive(tree, order);
+    print!("{}", derivation::format_derivation(&steps, highlight));
+
+    Ok(())
+}
+
+/// Evaluates the semantic actions of a grammar over the parse tree of a sentence and prints an attribute
+/// of its root.
+pub fn eval(grammar_path: &str, sentence: &str, attribute: &str) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+
+    let tokens = lexer::tokenize(&grammar, sentence)?;
+    let terminals = lexer::terminals(&tokens);
+    let trees = earley::parse(&grammar, &terminals, 2);
+    let Some(mut tree) = trees.first().cloned() else {
+        return Err(not_in_language(&terminals));
+    };
+    if trees.len() > 1 {
+        println!("Note: the sentence is ambiguous, evaluating its first parse tree.\n");
+    }
+    tree.attach_spans(&tokens);
+
+    let attributes = semantics::evaluate(&grammar, &tree).map_err(CliError::Grammar)?;
+    match attributes.get(attribute) {
+        Some(value) => println!("{}", value),
+        None => return Err(CliError::Grammar(format!("the actions don't set {} of {}", attribute, tree.name()))),
+    }
+
+    Ok(())
+}
+
+/// Prints random sentences of a grammar, one per line.
+pub fn generate(
+    grammar_path: &str,
+    count: usize,
+    max_depth: usize,
+    max_len: usize,
+    seed: Option<u64>,
+) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+
+    let rng = seed.map_or_else(Rng::from_time, Rng::new);
+    let sentences = generate_sentences(&grammar, count, max_depth, max_len, rng)?;
+    for sentence in sentences {
+        println!("{}", format_sentence(&sentence));
+    }
+
+    Ok(())
+}
+
+/// Generates up to `count` random sentences, failing if not a single one fits the limits.
+pub fn generate_sentences(
+    grammar: &ContextFreeGrammar,
+    count: usize,
+    max_depth: usize,
+    max_len: usize,
+    rng: Rng,
+) -> Result<Vec<Vec<String>>, CliError> {
+    let mut generator = SentenceGenerator::new(grammar, rng);
+
+    // give up after enough failed attempts rather than looping forever on a grammar that can't fit the limits
+    let mut sentences = Vec::new();
+    let mut attempts = 0;
+    while sentences.len() < count && attempts < count * 50 {
+        attempts += 1;
+        if let Some(sentence) = generator.generate(max_depth, max_len) {
+            sentences.push(sentence);
+        }
+    }
+
+    if sentences.is_empty() && count > 0 {
+        return Err(CliError::Grammar(format!(
+            "no sentence could be generated within a depth of {} and a length of {}",
+            max_depth, max_len
+        )));
+    }
+
+    Ok(sentences)
+}
+
+/// Prints a grammar converted from one file format to another.
+/// A grammar converted from a regex is given as the regex itself rather than a file, unless it is read from stdin.
+pub fn convert(grammar_path: &str, from: Option<GrammarFormat>, to: GrammarFormat) -> Result<(), CliError> {
+    let grammar = match from {
+        Some(GrammarFormat::Regex) if grammar_path != "-" => regex_grammar(grammar_path)?,
+        _ => read_grammar(grammar_path, from)?,
+    };
+
+    match to {
+        GrammarFormat::Bnf => print_grammar(&grammar, OutputFormat::Bnf),
+        GrammarFormat::Json => print_grammar(&grammar, OutputFormat::Json),
+        GrammarFormat::Regex => {
+            let dfa = regular_nfa(&grammar)?.to_dfa().minimize();
+            let symbol = |terminal: &str| match grammar.token_classes().get(terminal) {
+                Some(pattern) => Regex::parse(pattern).expect("token class patterns are checked when the grammar is read"),
+                None => Regex::literal(terminal),
+            };
+            let regex = dfa.to_regex(symbol).ok_or_else(|| {
+                CliError::Grammar("the language of the grammar is empty, which no regex can describe".to_string())
+            })?;
+            println!("{}", regex);
+        }
+    }
+
+    Ok(())
+}
+
+/// Compares two grammars and prints a shortest distinguishing string if their languages differ.
+pub fn equiv(
+    first_path: &str,
+    second_path: &str,
+    max_len: usize,
+    samples: usize,
+    seed: Option<u64>,
+) -> Result<(), CliError> {
+    let first = read_grammar(first_path, None)?;
+    let second = read_grammar(second_path, None)?;
+
+    let rng = seed.map_or_else(Rng::from_time, Rng::new);
+
+    match equivalence::check_equivalence(&first, &second, max_len, samples, rng) {
+        Equivalence::Agree { exhaustive, sampled } => {
+            println!(
+                "No difference found: checked {} strings up to length {} and {} random samples.",
+                exhaustive, max_len, sampled
+            );
+            Ok(())
+        }
+        Equivalence::Differ(difference) => {
+            let (accepted, rejected) = if difference.accepted_by_first {
+                (first_path, second_path)
+            } else {
+                (second_path, first_path)
+            };
+            println!(
+                "Languages differ: \"{}\" is accepted by {} but not by {}",
+                format_sentence(&difference.sentence),
+                accepted,
+                rejected
+            );
+            Err(CliError::Rejected("The languages of the grammars differ".to_string()))
+        }
+    }
+}
+
+/// Prints the renamed symbols, changed rules and changed properties between two versions of a grammar.
+pub fn diff(old_path: &str, new_path: &str) -> Result<(), CliError> {
+    let old = read_grammar(old_path, None)?;
+    let new = read_grammar(new_path, None)?;
+
+    let diff = diff::diff(&old, &new);
+    if diff.is_empty() {
+        println!("The grammars are the same.");
+        return Ok(());
+    }
+
+    for (from, to) in &diff.renames {
+        println!("renamed {} -> {}", from, to);
+    }
+    if let Some((from, to)) = &diff.start_symbol {
+        println!("start symbol {} -> {}", from, to);
+    }
+    for changes in &diff.changes {
+        println!("\n{} ({})", changes.lhs, changes.kind);
+        for rule in &changes.removed {
+            println!("  - {}", cfg::format_rule(rule));
+        }
+        for rule in &changes.added {
+            println!("  + {}", cfg::format_rule(rule));
+        }
+        let action = |rule: &cfg::ProductionRule| match &rule.action {
+            Some(action) => format!("{{ {} }}", action),
+            None => "no action".to_string(),
+        };
+        for (old_rule, new_rule) in &changes.actions {
+            println!("  ~ {}: {} -> {}", cfg::format_rule(old_rule), action(old_rule), action(new_rule));
+        }
+    }
+    if !diff.properties.is_empty() {
+        println!("\nProperties:");
+        for change in &diff.properties {
+            println!("  {}: {} -> {}", change.property, change.old, change.new);
+        }
+    }
+
+    Err(CliError::Rejected("The grammars differ".to_string()))
+}
+
+/// Searches a grammar for an ambiguous sentence and prints its two parse trees side by side.
+pub fn ambiguity(grammar_path: &str, max_len: usize) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+
+    match ambiguity::find_ambiguity(&grammar, max_len) {
+        Ambiguity::NotFound { checked } => {
+            println!("No ambiguity found up to length {} ({} sentences checked).", max_len, checked);
+        }
+        Ambiguity::Ambiguous(witness) => {
+            println!("Ambiguous sentence: {}\n", format_sentence(&witness.sentence));
+
+            let (first, second) = witness.trees;
+            let blocks = [
+                format!("Parse tree 1\n\n{}", first.to_ascii()),
+                format!("Parse tree 2\n\n{}", second.to_ascii()),
+            ];
+            print!("{}", parse_tree::side_by_side(&blocks, 4));
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the smallest k up to `max_k` for which a grammar is LL(k) and prints its parse table.
+pub fn ll(grammar_path: &str, max_k: usize) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+
+    let k = ll::minimum_k(&grammar, max_k).map_err(|conflicts| {
+        let conflicts: Vec<String> = conflicts.iter().map(|conflict| format!("    {}", conflict)).collect();
+        CliError::Rejected(format!("The grammar is not LL(k) for any k up to {}:\n{}", max_k, conflicts.join("\n")))
+    })?;
+
+    println!("The grammar is LL({}).\n", k);
+    let table = ll::llk_table(&grammar, k);
+    let rows: Vec<[String; 3]> = table
+        .entries
+        .iter()
+        .flat_map(|(variable, row)| {
+            row.iter().map(|(lookahead, rules)| [variable.to_string(), lookahead.join(" "), cfg::format_rule(rules[0])])
+        })
+        .collect();
+    let header = ["Variable", "Lookahead", "Rule"].map(|s| s.to_string());
+    print!("{}", format_table(&header, &rows));
+
+    Ok(())
+}
+
+/// Prints the source of a recursive-descent parser for an LL(1) grammar.
+pub fn codegen(grammar_path: &str) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+
+    print!("{}", codegen::generate_parser(&grammar)?);
+
+    Ok(())
+}
+
+/// Prints whether a grammar is in each class of grammars, with the evidence, and the most specific one.
+pub fn classify(grammar_path: &str, max_len: usize) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+
+    let memberships = classify::classify(&grammar, max_len);
+    for membership in &memberships {
+        let answer = match membership.holds {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "unknown",
+        };
+        println!("{}: {}", membership.class, answer);
+        for evidence in &membership.evidence {
+            println!("    {}", evidence);
+        }
+    }
+    println!("\nMost specific class: {}", classify::most_specific(&memberships));
+
+    Ok(())
+}
+
+/// Converts a right- or left-linear grammar to a finite automaton and prints the requested stage of it.
+pub fn automaton(grammar_path: &str, stage: AutomatonStage, output_format: AutomatonFormat) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+
+    let nfa = regular_nfa(&grammar)?;
+
+    let output = match stage {
+        AutomatonStage::Nfa => match output_format {
+            AutomatonFormat::Table => nfa.to_table(),
+            AutomatonFormat::Dot => nfa.to_dot(),
+        },
+        AutomatonStage::Dfa | AutomatonStage::Minimal => {
+            let dfa = nfa.to_dfa();
+            let dfa = if stage == AutomatonStage::Minimal { dfa.minimize() } else { dfa };
+            match output_format {
+                AutomatonFormat::Table => dfa.to_table(),
+                AutomatonFormat::Dot => dfa.to_dot(),
+            }
+        }
+    };
+    print!("{}", output);
+
+    Ok(())
+}
+
+/// Prints a grammar for the intersection of a grammar's language with a regular language.
+pub fn intersect(grammar_path: &str, regular: &RegularLanguage, output_format: OutputFormat) -> Result<(), CliError> {
+    let grammar = read_grammar(grammar_path, None)?;
+
+    let intersection = match regular {
+        RegularLanguage::Regex(pattern) => {
+            if !grammar.token_classes().is_empty() {
+                return Err(CliError::Usage(
+                    "Grammars with token classes can only be intersected with a DFA, not a regex".to_string(),
+                ));
+            }
+            let regex = Regex::parse(pattern).map_err(|e| CliError::Usage(format!("Invalid regex: {}", e)))?;
+
+            // the DFA reads the characters of each terminal in turn
+            let characters: IndexSet<char> = grammar.terminals().iter().flat_map(|terminal| terminal.chars()).collect();
+            let characters: Vec<char> = characters.into_iter().collect();
+            let dfa = Nfa::from_regex_over(&regex, &characters).to_dfa().minimize();
+            intersection::intersect(&grammar, &dfa, |state, terminal| {
+                terminal.chars().try_fold(state, |state, c| dfa.step(state, c.encode_utf8(&mut [0; 4])))
+            })
+        }
+        RegularLanguage::DfaFile(path) => {
+            let dfa = Dfa::parse(&read_input(path)?).map_err(|e| CliError::Grammar(format!("invalid DFA: {}", e)))?;
+            intersection::intersect(&grammar, &dfa, |state, terminal| dfa.step(state, terminal))
+        }
+    };
+
+    if intersection.production_rules().is_empty() {
+        return Err(CliError::Rejected(
+            "The intersection is empty: no sentence of the grammar is in the regular language".to_string(),
+        ));
+    }
+    print_grammar(&intersection, output_format);
+
+    Ok(())
+}
+
+/// Prints the PDA of a grammar or a PDA file, or runs it on a sentence and prints an accepting run.
+pub fn pda(path: &str, sentence: Option<&str>, max_steps: usize) -> Result<(), CliError> {
+    // a lone ε is the empty sentence
+    let sentence = sentence.map(|sentence| if sentence.trim() == cfg::EPSILON { "" } else { sentence });
+    let (pda, input) = if path.ends_with(".pda") {
+        let pda = Pda::parse(&read_input(path)?).map_err(|e| CliError::Grammar(format!("invalid PDA: {}", e)))?;
+        (pda, sentence.map(|sentence| lexer::terminals(&lexer::split_words(sentence))))
+    } else {
+        let grammar = read_grammar(path, None)?;
+        let input = sentence.map(|sentence| lexer::tokenize(&grammar, sentence)).transpose()?;
+        (Pda::from_grammar(&grammar), input.map(|tokens| lexer::terminals(&tokens)))
+    };
+
+    let Some(input) = input else {
+        print!("{}", pda.to_text());
+        return Ok(());
+    };
+
+    match pda.run(&input, max_steps) {
+        Run::Accepted(run) => {
+            let steps = run.len() - 1;
+            println!("Accepted in {}:", counted(steps, "step"));
+            for (index, configuration) in run.iter().enumerate() {
+                let prefix = if index == 0 { " " } else { "⊢" };
+                println!("{} {}", prefix, configuration.format(&input));
+            }
+            Ok(())
+        }
+        Run::Rejected { explored } => Err(CliError::Rejected(format!(
+            "\"{}\" is not accepted by the PDA ({} configurations explored)",
+            format_sentence(&input),
+            explored
+        ))),
+        Run::GaveUp { explored } => Err(CliError::Rejected(format!(
+            "No accepting run for \"{}\" found in {} configurations, try a larger --max-steps",
+            format_sentence(&input),
+            explored
+        ))),
+    }
+}
+
+/// Returns the NFA of a right- or left-linear grammar, or an error pointing at a rule that keeps it from being one.
+fn regular_nfa(grammar: &ContextFreeGrammar) -> Result<Nfa, CliError> {
+    Nfa::from_grammar(grammar).map_err(|rule| {
+        let line = rule.span.map(|span| format!("line {}: ", span.line)).unwrap_or_default();
+        CliError::Grammar(format!(
+            "{}{} keeps the grammar from being right- or left-linear",
+            line,
+            cfg::format_rule(rule)
+        ))
+    })
+}
+
+/// Returns the right-linear grammar of a regex's minimal DFA.
+fn regex_grammar(pattern: &str) -> Result<ContextFreeGrammar, CliError> {
+    let regex = Regex::parse(pattern).map_err(|e| CliError::Grammar(format!("invalid regex: {}", e)))?;
+    let nfa = Nfa::from_regex(&regex).map_err(|e| CliError::Grammar(format!("can't convert the regex: {}", e)))?;
+    Ok(nfa.to_dfa().minimize().to_grammar())
+}
+
+/// Prints a grammar in the requested format.
+fn print_grammar(grammar: &ContextFreeGrammar, output_format: OutputFormat) {
+    print!("{}", format_grammar(grammar, output_format));
+}
+
+/// Formats a grammar in the requested format, ending in a newline.
+fn format_grammar(grammar: &ContextFreeGrammar, output_format: OutputFormat) -> String {
+    match output_format {
+        // the grammar as it is shown on the screen
+        OutputFormat::Text => format!("{}\n", grammar),
+        // canonical BNF form
+        OutputFormat::Bnf => grammar.to_bnf(),
+        // JSON so other tools can consume it
+        OutputFormat::Json => format!("{}\n", grammar.to_json().expect("grammars always serialize")),
+    }
+}
+
+/// Formats a set of symbols like `{ a, b }`.
+pub fn format_set(set: &IndexSet<&str>) -> String {
+    format!("{{ {} }}", set.iter().copied().collect::<Vec<&str>>().join(", "))
+}
+
+/// Formats a set of terminal strings, with ε for the empty string.
+fn format_strings(strings: &BTreeSet<Vec<&str>>) -> String {
+    let strings: Vec<String> =
+        strings.iter().map(|string| if string.is_empty() { cfg::EPSILON.to_string() } else { string.join(" ") }).collect();
+    format!("{{ {} }}", strings.join(", "))
+}
+
+/// Formats rows of cells as a table with left aligned columns.
+pub fn format_table<R: AsRef<[String]>>(header: &[String], rows: &[R]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|cell| cell.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row.as_ref()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut output = String::new();
+    for row in std::iter::once(header).chain(rows.iter().map(|row| row.as_ref())) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - cell.chars().count())))
+            .collect();
+        output.push_str(cells.join("   ").trim_end());
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Formats a count of something, like `1 warning` or `2 warnings`.
+pub(crate) fn counted(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+/// Returns the error for a sentence the grammar doesn't derive.
+fn not_in_language(sentence: &[String]) -> CliError {
+    CliError::Rejected(format!("\"{}\" is not in the language of the grammar", format_sentence(sentence)))
+}
+
+/// Formats a sentence of terminals for printing, writing the empty sentence as epsilon.
+pub fn format_sentence(sentence: &[String]) -> String {
+    if sentence.is_empty() {
+        cfg::EPSILON.to_string()
+    } else {
+        sentence.join(" ")
+    }
+}
+
+/// Returns the directory the includes of a grammar file are relative to, the current one for stdin.
+fn grammar_dir(path: &str) -> PathBuf {
+    match Path::new(path).parent() {
+        Some(dir) if path != "-" => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Reads a file, or stdin if the path is `-`.
+pub fn read_input(path: &str) -> Result<String, CliError> {
+    if path == "-" {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents)?;
+        Ok(contents)
+    } else {
+        Ok(fs::read_to_string(path)?)
+    }
+}
+
+/// Reads a grammar file, either BNF, JSON previously exported with `--output json` or a regex.
+/// Without an explicit format, files ending in `.json` are read as JSON and anything else as BNF.
+pub fn read_grammar(path: &str, format: Option<GrammarFormat>) -> Result<ContextFreeGrammar, CliError> {
+    let contents = read_input(path)?;
+
+    let format = format.unwrap_or(if path.ends_with(".json") { GrammarFormat::Json } else { GrammarFormat::Bnf });
+    match format {
+        GrammarFormat::Json => {
+            ContextFreeGrammar::from_json(&contents).map_err(|e| CliError::Grammar(format!("invalid JSON grammar: {}", e)))
+        }
+        GrammarFormat::Bnf => Ok(include::build_grammar_with_includes(&contents, &grammar_dir(path))?),
+        GrammarFormat::Regex => regex_grammar(contents.trim()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{EXIT_GRAMMAR, EXIT_IO, EXIT_REJECTED, EXIT_SUCCESS, EXIT_USAGE};
+    use crate::Config;
+
+    const GRAMMAR: &str = "<S> ::= a <S> | b\n<U> ::= c";
+    const UNDEFINED: &str = "<S> ::= a <X>";
+
+    /// Writes a grammar to a new temporary file, returning its path.
+    fn write_grammar(name: &str, source: &str) -> String {
+        let path = std::env::temp_dir().join(format!("bnf_parser_commands_{}_{}.bnf", name, std::process::id()));
+        fs::write(&path, source).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn exit_code(result: Result<(), CliError>) -> i32 {
+        result.map_or_else(|e| e.exit_code(), |_| EXIT_SUCCESS)
+    }
+
+    #[test]
+    fn test_show() {
+        let grammar = cfg::build_grammar(GRAMMAR).unwrap();
+
+        assert_eq!(format_grammar(&grammar, OutputFormat::Bnf), "<S> ::= a <S> | b\n<U> ::= c\n");
+        let text = format_grammar(&grammar, OutputFormat::Text);
+        assert!(text.starts_with("Variables =\n\t<S>\n\t<U>\n"));
+        assert!(text.contains("Start Symbol = <S>") && !text.contains("bnf_grammar_filepath"));
+        let json = format_grammar(&grammar, OutputFormat::Json);
+        assert_eq!(ContextFreeGrammar::from_json(&json).unwrap().to_bnf(), grammar.to_bnf());
+    }
+
+    #[test]
+    fn test_check() {
+        let mut output = String::new();
+        check_grammar(&cfg::build_grammar(GRAMMAR).unwrap(), &mut output).unwrap();
+        assert_eq!(
+            output,
+            "Start symbol: <S>\nVariables: 2, terminals: 3, production rules: 3\nLanguage: infinite\n\
+             warning: <U> can't be reached from the start symbol\nOK (1 warning)\n"
+        );
+
+        let mut output = String::new();
+        let error = check_grammar(&cfg::build_grammar(UNDEFINED).unwrap(), &mut output).unwrap_err();
+        assert_eq!(error.to_string(), "Grammar error: 1 error, 1 warning");
+        assert!(output.contains("Language: empty\nerror: line 1: <X> is used but has no production rules\n"));
+        assert!(output.ends_with("warning: <S> can't derive any string of terminals\n"));
+    }
+
+    #[test]
+    fn test_lint() {
+        let grammar = cfg::build_grammar(GRAMMAR).unwrap();
+
+        let mut output = String::new();
+        lint_grammar(&grammar, GRAMMAR, &[], &[], &mut output).unwrap();
+        assert_eq!(output, "warning: line 2: [L002] <U> is never used by another variable's rules\nOK (1 warning)\n");
+
+        let mut output = String::new();
+        lint_grammar(&grammar, GRAMMAR, &[LintCode::UnusedVariable], &[], &mut output).unwrap();
+        assert_eq!(output, "OK (0 warnings)\n");
+
+        let mut output = String::new();
+        let error = lint_grammar(&grammar, GRAMMAR, &[], &[LintCode::UnusedVariable], &mut output).unwrap_err();
+        assert_eq!(error.exit_code(), EXIT_GRAMMAR);
+        assert_eq!(output, "error: line 2: [L002] <U> is never used by another variable's rules\n");
+        assert_eq!(error.to_string(), "Grammar error: 1 error, 0 warnings");
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let grammar_path = write_grammar("grammar", GRAMMAR);
+        let undefined_path = write_grammar("undefined", UNDEFINED);
+        let missing_path = std::env::temp_dir().join("bnf_parser_commands_missing.bnf").to_string_lossy().into_owned();
+
+        assert_eq!(exit_code(check(&grammar_path)), EXIT_SUCCESS);
+        assert_eq!(exit_code(parse(&grammar_path, "a a b", ParserKind::Earley, TreeFormat::Ascii, 1)), EXIT_SUCCESS);
+        assert_eq!(exit_code(parse(&grammar_path, "a a", ParserKind::Earley, TreeFormat::Ascii, 1)), EXIT_REJECTED);
+        let args: Vec<String> = ["bnf_parser", "check"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(Config::build(&args).err().map(|e| e.exit_code()), Some(EXIT_USAGE));
+        assert_eq!(exit_code(check(&undefined_path)), EXIT_GRAMMAR);
+        assert_eq!(exit_code(lint(&undefined_path, &[], &[LintCode::UndefinedVariable])), EXIT_GRAMMAR);
+        // a misspelled left hand side isn't a lint, the grammar can't be read at all
+        let misspelled_path = write_grammar("misspelled", "<S> ::= <NP>\n<NP ::= a");
+        assert_eq!(exit_code(lint(&misspelled_path, &[LintCode::MisspelledVariable], &[])), EXIT_GRAMMAR);
+        assert_eq!(exit_code(check(&missing_path)), EXIT_IO);
+        assert_eq!(exit_code(show(&missing_path, OutputFormat::Text)), EXIT_IO);
+
+        fs::remove_file(grammar_path).unwrap();
+        fs::remove_file(undefined_path).unwrap();
+        fs::remove_file(misspelled_path).unwrap();
+    }
+}
@@ -1,15 +1,502 @@
-use std::collections::HashSet;
+use std::error::Error;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 
-type ProductionRule = (String, Vec<String>);
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+
+use crate::regex::Regex;
+use crate::semantics;
+
+/// Symbol used to write an empty right hand side.
+pub const EPSILON: &str = "ε";
+
+/// Location of a production rule's alternative within the BNF source it was built from.
+/// `start` and `end` are byte offsets into the source, `line` is 1-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Error found while building a grammar, with where in the source it was found if known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrammarError {
+    pub message: String,
+    pub span: Option<Span>,
+}
+impl GrammarError {
+    pub fn new(message: &str, span: Option<Span>) -> GrammarError {
+        GrammarError { message: message.to_string(), span }
+    }
+
+    /// Returns the error for a grammar whose parse table for a class of grammars has conflicts, listing them.
+    pub fn conflicts(class: impl fmt::Display, conflicts: &[impl fmt::Display]) -> GrammarError {
+        let conflicts: Vec<String> = conflicts.iter().map(|conflict| format!("    {}", conflict)).collect();
+        GrammarError::new(&format!("the grammar is not {}:\n{}", class, conflicts.join("\n")), None)
+    }
+}
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "line {}: {}", span.line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+impl Error for GrammarError {}
+
+/// A single production rule `lhs ::= rhs`.
+/// Two rules are equal when their lhs and rhs are equal, regardless of where they came from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductionRule {
+    pub lhs: String,
+    pub rhs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    /// Source of the rule's semantic action, without its braces.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+}
+impl ProductionRule {
+    pub fn new(lhs: &str, rhs: &[&str]) -> ProductionRule {
+        ProductionRule {
+            lhs: lhs.to_string(),
+            rhs: rhs.iter().map(|s| s.to_string()).collect(),
+            span: None,
+            action: None,
+        }
+    }
+}
+impl PartialEq for ProductionRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.lhs == other.lhs && self.rhs == other.rhs
+    }
+}
+impl Eq for ProductionRule {}
+impl Hash for ProductionRule {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.lhs.hash(state);
+        self.rhs.hash(state);
+    }
+}
+
+/// What a symbol of a grammar is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Variable,
+    /// A terminal matched by exactly its own text.
+    Terminal,
+    /// A terminal declared with `@token`, matched by a regex.
+    TokenClass,
+}
+
+/// Interned symbol of a grammar. Variables come first, so ids below `variables().len()` are variables
+/// and the rest are terminals, in the order of `variables()` and `terminals()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(u32);
+impl SymbolId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A production rule with interned symbols, at the same index as the rule in `production_rules()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedRule {
+    pub lhs: SymbolId,
+    pub rhs: Vec<SymbolId>,
+}
+
+/// Interned rules and the rules of each variable, rebuilt whenever the grammar's rules or symbols change.
+#[derive(Debug, Clone, Default)]
+struct RuleIndex {
+    rules: Vec<IndexedRule>,
+    /// Indices of the rules of each variable, by variable id.
+    rules_by_lhs: Vec<Vec<usize>>,
+}
 
 /// Struct representing a context free grammar.
-#[derive(Debug, PartialEq)]
+/// Symbols and rules are kept in the order they were first added so output is stable between runs.
+/// Token classes declared with `@token <name> = /regex/` are terminals that match any input the regex matches.
+/// Deserialized grammars are checked and indexed like those read with `from_json`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(try_from = "RawGrammar")]
 pub struct ContextFreeGrammar {
-    variables: HashSet<String>,
-    terminals: HashSet<String>,
+    variables: IndexSet<String>,
+    terminals: IndexSet<String>,
     start_symbol: String,
-    production_rules: HashSet<ProductionRule>,
+    production_rules: IndexSet<ProductionRule>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    token_classes: IndexMap<String, String>,
+    #[serde(skip)]
+    index: RuleIndex,
+}
+impl PartialEq for ContextFreeGrammar {
+    fn eq(&self, other: &Self) -> bool {
+        // the index is derived from the other fields
+        self.variables == other.variables
+            && self.terminals == other.terminals
+            && self.start_symbol == other.start_symbol
+            && self.production_rules == other.production_rules
+            && self.token_classes == other.token_classes
+    }
+}
+impl TryFrom<RawGrammar> for ContextFreeGrammar {
+    type Error = GrammarError;
+
+    /// Checks that the start symbol is a variable, that no symbol is both a variable and a terminal,
+    /// that the rules only use the listed symbols and that the token class patterns are valid regexes.
+    fn try_from(raw: RawGrammar) -> Result<Self, Self::Error> {
+        if !raw.variables.contains(&raw.start_symbol) {
+            let message = format!("the start symbol {} is not a variable", raw.start_symbol);
+            return Err(GrammarError::new(&message, None));
+        }
+        if let Some(symbol) = raw.variables.iter().find(|variable| raw.terminals.contains(*variable)) {
+            return Err(GrammarError::new(&format!("{} is both a variable and a terminal", symbol), None));
+        }
+        for (name, pattern) in &raw.token_classes {
+            if let Err(e) = Regex::parse(pattern) {
+                return Err(GrammarError::new(&format!("invalid pattern for {}: {}", name, e), None));
+            }
+        }
+
+        let mut grammar = ContextFreeGrammar {
+            variables: raw.variables,
+            terminals: raw.terminals,
+            start_symbol: raw.start_symbol,
+            production_rules: raw.production_rules,
+            token_classes: raw.token_classes,
+            index: RuleIndex::default(),
+        };
+        grammar.reindex()?;
+        Ok(grammar)
+    }
+}
+
+/// The serialized fields of a grammar, before they are checked and indexed.
+#[derive(Deserialize)]
+struct RawGrammar {
+    variables: IndexSet<String>,
+    terminals: IndexSet<String>,
+    start_symbol: String,
+    production_rules: IndexSet<ProductionRule>,
+    #[serde(default)]
+    token_classes: IndexMap<String, String>,
+}
+
+impl ContextFreeGrammar {
+    /// Creates a grammar from its production rules. Every lhs and every rhs symbol written
+    /// like `<...>` is a variable, all other symbols are terminals.
+    pub fn from_rules(start_symbol: &str, rules: impl IntoIterator<Item = ProductionRule>) -> ContextFreeGrammar {
+        ContextFreeGrammar::from_parts(start_symbol, rules, IndexMap::new())
+    }
+
+    /// Creates a grammar from production rules, keeping this grammar's token classes.
+    pub fn with_rules(&self, start_symbol: &str, rules: impl IntoIterator<Item = ProductionRule>) -> ContextFreeGrammar {
+        ContextFreeGrammar::from_parts(start_symbol, rules, self.token_classes.clone())
+    }
+
+    /// Creates a grammar from production rules and token classes. Token class names are terminals
+    /// even though they are written like variables.
+    fn from_parts(
+        start_symbol: &str,
+        rules: impl IntoIterator<Item = ProductionRule>,
+        token_classes: IndexMap<String, String>,
+    ) -> ContextFreeGrammar {
+        let production_rules: IndexSet<ProductionRule> = rules.into_iter().collect();
+
+        let mut variables = IndexSet::new();
+        variables.insert(start_symbol.to_string());
+        variables.extend(production_rules.iter().map(|rule| rule.lhs.clone()));
+
+        let mut terminals = IndexSet::new();
+        for symbol in production_rules.iter().flat_map(|rule| &rule.rhs) {
+            if !variables.contains(symbol) {
+                if is_variable_name(symbol) && !token_classes.contains_key(symbol) {
+                    variables.insert(symbol.clone());
+                } else {
+                    terminals.insert(symbol.clone());
+                }
+            }
+        }
+
+        ContextFreeGrammar::indexed(variables, terminals, start_symbol.to_string(), production_rules, token_classes)
+    }
+
+    /// Creates a grammar from its fields and builds its rule index.
+    fn indexed(
+        variables: IndexSet<String>,
+        terminals: IndexSet<String>,
+        start_symbol: String,
+        production_rules: IndexSet<ProductionRule>,
+        token_classes: IndexMap<String, String>,
+    ) -> ContextFreeGrammar {
+        let mut grammar = ContextFreeGrammar {
+            variables,
+            terminals,
+            start_symbol,
+            production_rules,
+            token_classes,
+            index: RuleIndex::default(),
+        };
+        grammar.reindex().expect("rules only use the symbols collected from them");
+        grammar
+    }
+
+    /// Rebuilds the interned rules and the rules of each variable. Fails if a rule's lhs isn't
+    /// one of the variables or a symbol on its rhs is neither a variable nor a terminal.
+    fn reindex(&mut self) -> Result<(), GrammarError> {
+        let mut rules_by_lhs = vec![Vec::new(); self.variables.len()];
+        let mut rules = Vec::new();
+        for (index, rule) in self.production_rules.iter().enumerate() {
+            let indexed = self.index_rule(rule)?;
+            rules_by_lhs[indexed.lhs.index()].push(index);
+            rules.push(indexed);
+        }
+
+        self.index = RuleIndex { rules, rules_by_lhs };
+        Ok(())
+    }
+
+    /// Interns the symbols of a rule, failing like `reindex` does.
+    fn index_rule(&self, rule: &ProductionRule) -> Result<IndexedRule, GrammarError> {
+        let lhs = match self.symbol_id(&rule.lhs) {
+            Some(lhs) if self.is_variable_id(lhs) => lhs,
+            _ => {
+                let message = format!("the lhs of {} is not a variable", format_rule(rule));
+                return Err(GrammarError::new(&message, None));
+            }
+        };
+        let rhs = rule
+            .rhs
+            .iter()
+            .map(|symbol| {
+                self.symbol_id(symbol).ok_or_else(|| {
+                    let message = format!("{} in {} is neither a variable nor a terminal", symbol, format_rule(rule));
+                    GrammarError::new(&message, None)
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(IndexedRule { lhs, rhs })
+    }
+
+    /// Returns the id of a symbol, or None if the grammar doesn't use it.
+    pub fn symbol_id(&self, symbol: &str) -> Option<SymbolId> {
+        let index = match self.variables.get_index_of(symbol) {
+            Some(index) => index,
+            None => self.variables.len() + self.terminals.get_index_of(symbol)?,
+        };
+        Some(SymbolId(index as u32))
+    }
+
+    /// Returns the symbol an id stands for.
+    pub fn symbol(&self, id: SymbolId) -> &str {
+        match self.variables.get_index(id.index()) {
+            Some(variable) => variable,
+            None => &self.terminals[id.index() - self.variables.len()],
+        }
+    }
+
+    /// Returns the number of symbols, variables and terminals together.
+    pub fn symbol_count(&self) -> usize {
+        self.variables.len() + self.terminals.len()
+    }
+
+    /// Returns true if the id is one of a variable.
+    pub fn is_variable_id(&self, id: SymbolId) -> bool {
+        id.index() < self.variables.len()
+    }
+
+    /// Returns the production rules with interned symbols, in the same order as `production_rules()`.
+    pub fn indexed_rules(&self) -> &[IndexedRule] {
+        &self.index.rules
+    }
+
+    /// Returns the indices of the rules with the given lhs, which are empty for terminals.
+    pub fn rule_indices_for(&self, lhs: SymbolId) -> &[usize] {
+        self.index.rules_by_lhs.get(lhs.index()).map_or(&[], |rules| rules.as_slice())
+    }
+
+    /// Returns true if the symbol is one of the grammar's variables.
+    pub fn is_variable(&self, symbol: &str) -> bool {
+        self.variables.contains(symbol)
+    }
+
+    /// Returns whether a symbol is a variable, a plain terminal or a token class,
+    /// or None if the grammar doesn't use it.
+    pub fn symbol_kind(&self, symbol: &str) -> Option<SymbolKind> {
+        if self.variables.contains(symbol) {
+            Some(SymbolKind::Variable)
+        } else if self.token_classes.contains_key(symbol) {
+            Some(SymbolKind::TokenClass)
+        } else if self.terminals.contains(symbol) {
+            Some(SymbolKind::Terminal)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the production rules with the given lhs, in the order they were added.
+    pub fn rules_for<'a>(&'a self, lhs: &str) -> impl Iterator<Item = &'a ProductionRule> + 'a {
+        let indices = self.symbol_id(lhs).map_or(&[][..], |lhs| self.rule_indices_for(lhs));
+        indices.iter().map(|&index| &self.production_rules[index])
+    }
+
+    /// Serializes the grammar to a pretty-printed JSON string.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Deserializes a grammar from a JSON string produced by `to_json`, checking it like any deserialized
+    /// grammar is checked.
+    pub fn from_json(json: &str) -> Result<ContextFreeGrammar, GrammarError> {
+        let raw: RawGrammar = serde_json::from_str(json).map_err(|e| GrammarError::new(&e.to_string(), None))?;
+        ContextFreeGrammar::try_from(raw)
+    }
+
+    /// Returns the grammar's variables.
+    pub fn variables(&self) -> &IndexSet<String> {
+        &self.variables
+    }
+
+    /// Returns the grammar's terminals.
+    pub fn terminals(&self) -> &IndexSet<String> {
+        &self.terminals
+    }
+
+    /// Returns the grammar's start symbol.
+    pub fn start_symbol(&self) -> &str {
+        &self.start_symbol
+    }
+
+    /// Returns the grammar's production rules in the order they were added.
+    pub fn production_rules(&self) -> &IndexSet<ProductionRule> {
+        &self.production_rules
+    }
+
+    /// Returns the grammar's token classes, mapping each name to its regex pattern.
+    pub fn token_classes(&self) -> &IndexMap<String, String> {
+        &self.token_classes
+    }
+
+    /// Adds a production rule, returning false if the grammar already had it.
+    /// Its new symbols are added like `from_rules` adds them.
+    pub fn add_rule(&mut self, rule: ProductionRule) -> bool {
+        if self.production_rules.contains(&rule) {
+            return false;
+        }
+
+        let variable_count = self.variables.len();
+        let lhs_was_terminal = self.terminals.shift_remove(&rule.lhs);
+        self.variables.insert(rule.lhs.clone());
+        for symbol in &rule.rhs {
+            if !self.variables.contains(symbol) && !self.terminals.contains(symbol) {
+                if is_variable_name(symbol) && !self.token_classes.contains_key(symbol) {
+                    self.variables.insert(symbol.clone());
+                } else {
+                    self.terminals.insert(symbol.clone());
+                }
+            }
+        }
+
+        // a new variable shifts the ids of every terminal, so only then is the whole index rebuilt
+        if lhs_was_terminal || self.variables.len() != variable_count {
+            self.production_rules.insert(rule);
+            self.reindex().expect("the rule's symbols were just added");
+        } else {
+            let indexed = self.index_rule(&rule).expect("the rule's symbols were just added");
+            self.index.rules_by_lhs[indexed.lhs.index()].push(self.production_rules.len());
+            self.index.rules.push(indexed);
+            self.production_rules.insert(rule);
+        }
+        true
+    }
+
+    /// Removes a production rule, returning false if the grammar didn't have it.
+    /// Symbols no rule uses anymore are removed along with it.
+    pub fn remove_rule(&mut self, rule: &ProductionRule) -> bool {
+        let Some(index) = self.production_rules.get_index_of(rule) else {
+            return false;
+        };
+        self.production_rules.shift_remove_index(index);
+        let removed = self.index.rules.remove(index);
+        for rules in &mut self.index.rules_by_lhs {
+            rules.retain(|&i| i != index);
+            rules.iter_mut().filter(|i| **i > index).for_each(|i| *i -= 1);
+        }
+
+        let start = self.symbol_id(&self.start_symbol);
+        let mut unused: Vec<String> = Vec::new();
+        for id in std::iter::once(removed.lhs).chain(removed.rhs) {
+            let used = Some(id) == start || self.index.rules.iter().any(|r| r.lhs == id || r.rhs.contains(&id));
+            if !used && !unused.iter().any(|symbol| symbol == self.symbol(id)) {
+                unused.push(self.symbol(id).to_string());
+            }
+        }
+        // removing symbols changes the ids of the ones after them
+        if !unused.is_empty() {
+            for symbol in &unused {
+                self.variables.shift_remove(symbol);
+                self.terminals.shift_remove(symbol);
+            }
+            self.reindex().expect("only unused symbols were removed");
+        }
+        true
+    }
+
+    /// Makes a variable the start symbol, adding it to the variables if it is new.
+    pub fn set_start_symbol(&mut self, start_symbol: &str) {
+        self.start_symbol = start_symbol.to_string();
+        if self.variables.insert(start_symbol.to_string()) {
+            self.reindex().expect("adding a variable keeps every rule's symbols known");
+        }
+    }
+
+    /// Groups the production rules by lhs, with the start symbol first and the remaining
+    /// lhs symbols and their alternatives in the order they were added.
+    pub fn rules_by_lhs(&self) -> Vec<(&str, Vec<&ProductionRule>)> {
+        let start = self.symbol_id(&self.start_symbol);
+        let mut lhs_ids: Vec<SymbolId> = (0..self.variables.len() as u32)
+            .map(SymbolId)
+            .filter(|&id| Some(id) != start && !self.rule_indices_for(id).is_empty())
+            .collect();
+        lhs_ids.sort_by_key(|&id| self.rule_indices_for(id)[0]);
+
+        start
+            .into_iter()
+            .chain(lhs_ids)
+            .map(|id| {
+                let rules = self.rule_indices_for(id).iter().map(|&index| &self.production_rules[index]).collect();
+                (self.symbol(id), rules)
+            })
+            .filter(|(_, rules): &(&str, Vec<&ProductionRule>)| !rules.is_empty())
+            .collect()
+    }
+
+    /// Prints the grammar as canonical BNF, one line per lhs with alternatives separated by `|`.
+    /// Passing the output back to `build_grammar` reproduces an equal grammar.
+    pub fn to_bnf(&self) -> String {
+        let mut output = String::new();
+
+        for (name, pattern) in &self.token_classes {
+            output.push_str(&format!("@token {} = /{}/\n", name, pattern));
+        }
+
+        for (lhs, rules) in self.rules_by_lhs() {
+            let alternatives: Vec<String> = rules
+                .iter()
+                .map(|rule| match &rule.action {
+                    Some(action) => format!("{} {{ {} }}", format_rhs(&rule.rhs), action),
+                    None => format_rhs(&rule.rhs),
+                })
+                .collect();
+            output.push_str(&format!("{} ::= {}\n", lhs, alternatives.join(" | ")));
+        }
+
+        output
+    }
 }
 impl fmt::Display for ContextFreeGrammar {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -25,75 +512,251 @@ impl fmt::Display for ContextFreeGrammar {
             output.push_str(&format!("\t{}\n", terminal));
         }
 
+        if !self.token_classes.is_empty() {
+            output.push_str("\nToken Classes =\n");
+            for (name, pattern) in &self.token_classes {
+                output.push_str(&format!("\t{} = /{}/\n", name, pattern));
+            }
+        }
+
         output.push_str(&format!("\nStart Symbol = {}\n", self.start_symbol));
 
         output.push_str("\nProduction Rules =\n");
-        for rule in &self.production_rules {
-            output.push_str(&format!("\t{}\n", format_rule(rule)));
+        for (_, rules) in self.rules_by_lhs() {
+            for rule in rules {
+                output.push_str(&format!("\t{}\n", format_rule(rule)));
+            }
         }
 
         write!(f, "{}", output)
     }
 }
 
-/// Formats a production rule into a string.
-fn format_rule(rule: &ProductionRule) -> String {
-    let lhs = &rule.0;
-    let rhs = &rule.1;
+/// Builds a grammar in code, checking it when `build` is called.
+/// The start symbol is the lhs of the first rule unless set with `start_symbol`.
+///
+/// ```
+/// use bnf_parser::context_free_grammar::GrammarBuilder;
+///
+/// let grammar = GrammarBuilder::new()
+///     .rule("<E>", &["<E>", "+", "<number>"])
+///     .rule("<E>", &["<number>"])
+///     .token_class("<number>", "[0-9]+")
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(grammar.rules_for("<E>").count(), 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GrammarBuilder {
+    start_symbol: Option<String>,
+    rules: Vec<ProductionRule>,
+    token_classes: IndexMap<String, String>,
+}
+impl GrammarBuilder {
+    pub fn new() -> GrammarBuilder {
+        GrammarBuilder::default()
+    }
+
+    pub fn start_symbol(mut self, start_symbol: &str) -> GrammarBuilder {
+        self.start_symbol = Some(start_symbol.to_string());
+        self
+    }
+
+    /// Adds a rule `lhs ::= rhs`. An empty rhs is an epsilon rule.
+    pub fn rule(mut self, lhs: &str, rhs: &[&str]) -> GrammarBuilder {
+        self.rules.push(ProductionRule::new(lhs, rhs));
+        self
+    }
+
+    /// Adds a rule for each alternative, like `lhs ::= a | b c` in BNF.
+    pub fn alternatives(mut self, lhs: &str, alternatives: &[&[&str]]) -> GrammarBuilder {
+        self.rules.extend(alternatives.iter().map(|rhs| ProductionRule::new(lhs, rhs)));
+        self
+    }
+
+    /// Declares a token class matching the regex `pattern`, like `@token name = /pattern/` in BNF.
+    pub fn token_class(mut self, name: &str, pattern: &str) -> GrammarBuilder {
+        self.token_classes.insert(name.to_string(), pattern.to_string());
+        self
+    }
+
+    /// Checks the grammar and builds it. Fails if there are no rules, a symbol can't be written in BNF,
+    /// a token class is invalid or has rules, or a variable is used without having any rules.
+    pub fn build(self) -> Result<ContextFreeGrammar, GrammarError> {
+        let Some(first_rule) = self.rules.first() else {
+            return Err(GrammarError::new("the grammar has no production rules", None));
+        };
+        let start_symbol = self.start_symbol.clone().unwrap_or_else(|| first_rule.lhs.clone());
+
+        for (name, pattern) in &self.token_classes {
+            check_token_class(name, pattern, None)?;
+        }
+
+        for rule in &self.rules {
+            if !is_variable_name(&rule.lhs) || !is_plain_symbol(&rule.lhs) {
+                return Err(GrammarError::new(
+                    &format!("left hand side '{}' must be a single variable like <S>", rule.lhs),
+                    None,
+                ));
+            }
+            if self.token_classes.contains_key(&rule.lhs) {
+                return Err(GrammarError::new(
+                    &format!("{} is a token class and can't have production rules", rule.lhs),
+                    None,
+                ));
+            }
+            if let Some(symbol) = rule.rhs.iter().find(|symbol| !is_plain_symbol(symbol)) {
+                return Err(GrammarError::new(
+                    &format!("'{}' in {} can't be written as a BNF symbol", symbol, format_rule(rule)),
+                    None,
+                ));
+            }
+        }
 
-    let mut output = String::new();
-    output.push_str(&format!("{} ::= ", lhs));
+        let grammar = ContextFreeGrammar::from_parts(&start_symbol, self.rules, self.token_classes);
+        if let Some(undefined) = grammar.variables.iter().find(|variable| grammar.rules_for(variable).next().is_none()) {
+            return Err(GrammarError::new(&format!("{} is used but has no production rules", undefined), None));
+        }
 
-    for symbol in rhs {
-        output.push_str(&format!("{} ", symbol));
+        Ok(grammar)
     }
+}
 
-    output
+/// Returns true if a symbol can be written in BNF and read back as the same single symbol.
+pub fn is_plain_symbol(symbol: &str) -> bool {
+    !symbol.is_empty()
+        && symbol != EPSILON
+        && !symbol.contains(char::is_whitespace)
+        && !symbol.contains('|')
+        && !symbol.contains("::=")
+        && !symbol.starts_with('#')
+        && !symbol.starts_with('@')
+}
+
+/// Formats a production rule into a string.
+pub fn format_rule(rule: &ProductionRule) -> String {
+    format!("{} ::= {}", rule.lhs, format_rhs(&rule.rhs))
+}
+
+/// Formats the right hand side of a production rule, writing an empty rhs as epsilon.
+fn format_rhs(rhs: &[String]) -> String {
+    if rhs.is_empty() {
+        EPSILON.to_string()
+    } else {
+        rhs.join(" ")
+    }
+}
+
+/// Returns true if a symbol is written like a variable, `<...>`.
+pub fn is_variable_name(symbol: &str) -> bool {
+    symbol.len() > 2 && symbol.starts_with('<') && symbol.ends_with('>')
 }
 
 /// Builds a context free grammar from a BNF grammar string.
-pub fn build_grammar(bnf_grammar: &str) -> ContextFreeGrammar {
-    let mut variables = HashSet::new();
-    let mut terminals = HashSet::new();
+pub fn build_grammar(bnf_grammar: &str) -> Result<ContextFreeGrammar, GrammarError> {
+    let mut variables = IndexSet::new();
+    let mut terminals = IndexSet::new();
     let mut start_symbol = String::new();
-    let mut production_rules = HashSet::new();
+    let mut production_rules: IndexSet<ProductionRule> = IndexSet::new();
+
+    // token classes are read first so rules can use them before they are declared
+    let mut token_classes = IndexMap::new();
+    for (line_index, line) in bnf_grammar.lines().enumerate() {
+        let line = line.trim();
+        if let Some(directive) = line.strip_prefix('@') {
+            let line_span = line_span(bnf_grammar, line, line_index);
+            let (name, pattern) = parse_token_class(directive, line_span)?;
+            if token_classes.insert(name.clone(), pattern).is_some() {
+                return Err(GrammarError::new(
+                    &format!("token class {} is declared more than once", name),
+                    Some(line_span),
+                ));
+            }
+        }
+    }
 
     let mut first_iteration = true;
-    for line in bnf_grammar.lines() {
+    for (line_index, line) in bnf_grammar.lines().enumerate() {
         let line = line.trim();
 
-        if line.is_empty() || line.starts_with('#') {
-            // skip empty and comment lines
+        if line.is_empty() || line.starts_with('#') || line.starts_with('@') {
+            // skip empty and comment lines, and the directives read above
             continue;
         }
 
+        let line_span = line_span(bnf_grammar, line, line_index);
+
         let parts: Vec<&str> = line.splitn(2, "::=").map(|s| s.trim()).collect();
+        if parts.len() < 2 {
+            return Err(GrammarError::new("expected '::=' after the rule's left hand side", Some(line_span)));
+        }
 
         let lhs_symbol = parts[0].to_string();
+        if !is_variable_name(&lhs_symbol) || lhs_symbol.contains(char::is_whitespace) {
+            return Err(GrammarError::new(
+                &format!("left hand side '{}' must be a single variable like <S>", lhs_symbol),
+                Some(line_span),
+            ));
+        }
+        if token_classes.contains_key(&lhs_symbol) {
+            return Err(GrammarError::new(
+                &format!("{} is a token class and can't have production rules", lhs_symbol),
+                Some(line_span),
+            ));
+        }
         variables.insert(lhs_symbol.clone());
 
-        let expressions: Vec<String> = parts[1].split('|').map(|s| s.trim().to_string()).collect();
-        for expression in expressions {
+        for raw_expression in split_alternatives(parts[1]) {
+            // byte offset of the alternative within the whole source
+            let expression = raw_expression.trim();
+            let expression_start = expression.as_ptr() as usize - bnf_grammar.as_ptr() as usize;
+            let span = Span { line: line_index + 1, start: expression_start, end: expression_start + expression.len() };
+
+            let (expression, action) = split_action(expression).map_err(|e| GrammarError::new(e, Some(span)))?;
+
+            // an empty alternative or a lone epsilon is an empty rhs
             let rhs_symbols: Vec<String> = expression
-                .split(' ')
-                .map(|s| s.trim().to_string())
+                .split_whitespace()
+                .filter(|s| *s != EPSILON)
+                .map(|s| s.to_string())
                 .collect();
 
+            if let Some(action) = action {
+                let is_variable: Vec<bool> = rhs_symbols
+                    .iter()
+                    .map(|symbol| is_variable_name(symbol) && !token_classes.contains_key(symbol))
+                    .collect();
+                semantics::Action::parse(action)
+                    .and_then(|parsed| parsed.check(&is_variable))
+                    .map_err(|e| GrammarError::new(&format!("invalid action: {}", e), Some(span)))?;
+            }
+
             // add all new symbols to terminals or variables as appropriate
             for symbol in &rhs_symbols {
                 if !variables.contains(symbol) && !terminals.contains(symbol) {
-                    if symbol.starts_with('<') && symbol.ends_with('>') {
+                    if is_variable_name(symbol) && !token_classes.contains_key(symbol) {
                         variables.insert(symbol.clone());
                     } else {
                         terminals.insert(symbol.clone());
                     }
                 }
-            }                
+            }
 
-            let rule: ProductionRule = (
-                lhs_symbol.clone(),
-                rhs_symbols,
-            );
+            let rule = ProductionRule {
+                lhs: lhs_symbol.clone(),
+                rhs: rhs_symbols,
+                span: Some(span),
+                action: action.map(|action| action.to_string()),
+            };
+            // a repeated rule is harmless unless it would silently lose its action
+            if let Some(existing) = production_rules.get(&rule) {
+                if existing.action != rule.action {
+                    let line = existing.span.map_or(String::new(), |span| format!(" on line {}", span.line));
+                    let message = format!("{} is already defined{} with a different action", format_rule(&rule), line);
+                    return Err(GrammarError::new(&message, Some(span)));
+                }
+            }
             production_rules.insert(rule);
         }
 
@@ -104,33 +767,131 @@ pub fn build_grammar(bnf_grammar: &str) -> ContextFreeGrammar {
         }
     }
 
-    ContextFreeGrammar {
-        variables,
-        terminals,
-        start_symbol,
-        production_rules,
+    if production_rules.is_empty() {
+        return Err(GrammarError::new("the grammar has no production rules", None));
+    }
+
+    Ok(ContextFreeGrammar::indexed(variables, terminals, start_symbol, production_rules, token_classes))
+}
+
+/// Splits the right hand side of a rule into its alternatives at each `|` that isn't inside a semantic action.
+pub fn split_alternatives(rhs: &str) -> Vec<&str> {
+    let mut alternatives = Vec::new();
+    let mut start = 0;
+    let mut position = 0;
+    while let Some(c) = rhs[position..].chars().next() {
+        if let Some(length) = action_length(&rhs[position..]) {
+            position += length;
+            continue;
+        }
+        if c == '|' {
+            alternatives.push(&rhs[start..position]);
+            start = position + 1;
+        }
+        position += c.len_utf8();
+    }
+    alternatives.push(&rhs[start..]);
+    alternatives
+}
+
+/// Splits an alternative into its symbols and the text of the semantic action ending it, without its braces.
+pub fn split_action(alternative: &str) -> Result<(&str, Option<&str>), &'static str> {
+    let action_start = alternative.char_indices().map(|(i, _)| i).find(|&i| action_length(&alternative[i..]).is_some());
+    let Some(action_start) = action_start else {
+        return Ok((alternative, None));
+    };
+    let action_end = action_start + action_length(&alternative[action_start..]).expect("found above");
+    if !alternative[action_end..].trim().is_empty() {
+        return Err("a semantic action must end its alternative");
+    }
+    Ok((&alternative[..action_start], Some(alternative[action_start + 1..action_end - 1].trim())))
+}
+
+/// Returns the length of the semantic action the text starts with, if it does. An action is a `{ }`
+/// group using `$`; other braces are terminals, so grammars of languages with braces still work.
+fn action_length(text: &str) -> Option<usize> {
+    if !text.starts_with('{') {
+        return None;
+    }
+    let mut depth = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return text[..index].contains('$').then_some(index + 1);
+        }
+    }
+    None
+}
+
+/// Returns the span of a whole line, which must be a slice of the source.
+fn line_span(source: &str, line: &str, line_index: usize) -> Span {
+    let line_start = line.as_ptr() as usize - source.as_ptr() as usize;
+    Span { line: line_index + 1, start: line_start, end: line_start + line.len() }
+}
+
+/// Parses a `token <name> = /regex/` directive, the part of the line after its `@`.
+fn parse_token_class(directive: &str, span: Span) -> Result<(String, String), GrammarError> {
+    let keyword = directive.split_whitespace().next().unwrap_or_default();
+    if keyword == "include" {
+        return Err(GrammarError::new("includes need the grammar's directory, see build_grammar_with_includes", Some(span)));
+    }
+    if keyword != "token" {
+        return Err(GrammarError::new(&format!("unknown directive '@{}'", keyword), Some(span)));
+    }
+
+    let Some((name, pattern)) = directive["token".len()..].split_once('=') else {
+        return Err(GrammarError::new("expected '=' after the token class name", Some(span)));
+    };
+    let name = name.trim();
+    let pattern = pattern.trim();
+    let Some(pattern) = pattern.strip_prefix('/').and_then(|pattern| pattern.strip_suffix('/')) else {
+        return Err(GrammarError::new(
+            &format!("the pattern of {} must be written between slashes like /[0-9]+/", name),
+            Some(span),
+        ));
+    };
+    check_token_class(name, pattern, Some(span))?;
+
+    Ok((name.to_string(), pattern.to_string()))
+}
+
+/// Checks that a token class is named like <name> and has a valid regex pattern.
+fn check_token_class(name: &str, pattern: &str, span: Option<Span>) -> Result<(), GrammarError> {
+    if !is_variable_name(name) || name.contains(char::is_whitespace) {
+        return Err(GrammarError::new(&format!("token class name '{}' must be written like <name>", name), span));
+    }
+    if let Err(e) = Regex::parse(pattern) {
+        return Err(GrammarError::new(&format!("invalid pattern for {}: {}", name, e), span));
     }
+    Ok(())
 }
 
 /// If the start symbol is on the right hand side of any production rules, removes it and add a new start symbol.
-fn remove_start_on_rhs(grammar: &mut ContextFreeGrammar) -> () {
+pub fn remove_start_on_rhs(grammar: &mut ContextFreeGrammar) {
     let mut start_on_rhs = false;
     for rule in &grammar.production_rules {
-        if rule.1.contains(&grammar.start_symbol) {
+        if rule.rhs.contains(&grammar.start_symbol) {
             start_on_rhs = true;
             break;
         }
     }
     if start_on_rhs {
-
-        let new_start_symbol = format!("<{}'>", grammar.start_symbol.trim_matches(|c| c == '<' || c == '>'));
+        // adds primes until the new start symbol doesn't clash with an existing variable
+        let mut new_start_symbol = grammar.start_symbol.clone();
+        while grammar.variables.contains(&new_start_symbol) {
+            new_start_symbol.insert(new_start_symbol.len() - 1, '\'');
+        }
         grammar.variables.insert(new_start_symbol.clone());
-        grammar.production_rules.insert((new_start_symbol.clone(), vec![grammar.start_symbol.clone()]));
+        grammar.production_rules.insert(ProductionRule::new(&new_start_symbol, &[&grammar.start_symbol]));
         grammar.start_symbol = new_start_symbol;
+        grammar.reindex().expect("the new start rule only uses variables");
     }
 }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -142,23 +903,23 @@ mod tests {
                             <VP> ::= played with the ball | ate its food";
                         
 
-        let expected_rules: HashSet<(String, Vec<String>)> = vec![
-            ("<S>".to_string(), vec!["<NP>".to_string(), "<VP>".to_string()]),
-            ("<NP>".to_string(), vec!["The".to_string(), "dog".to_string()]),
-            ("<NP>".to_string(), vec!["The".to_string(), "cat".to_string()]),
-            ("<VP>".to_string(), vec!["played".to_string(), "with".to_string(), "the".to_string(), "ball".to_string()]),
-            ("<VP>".to_string(), vec!["ate".to_string(), "its".to_string(), "food".to_string()]),
+        let expected_rules: IndexSet<ProductionRule> = vec![
+            ProductionRule::new("<S>", &["<NP>", "<VP>"]),
+            ProductionRule::new("<NP>", &["The", "dog"]),
+            ProductionRule::new("<NP>", &["The", "cat"]),
+            ProductionRule::new("<VP>", &["played", "with", "the", "ball"]),
+            ProductionRule::new("<VP>", &["ate", "its", "food"]),
         ].into_iter()
         .collect();
 
-        let expected_variables: HashSet<String> = vec![
+        let expected_variables: IndexSet<String> = vec![
             "<S>".to_string(),
             "<NP>".to_string(),
             "<VP>".to_string(),
         ].into_iter()
         .collect();
 
-        let expected_terminals: HashSet<String> = vec![
+        let expected_terminals: IndexSet<String> = vec![
             "The".to_string(),
             "dog".to_string(),
             "cat".to_string(),
@@ -177,9 +938,11 @@ mod tests {
             terminals: expected_terminals,
             start_symbol: "<S>".to_string(),
             production_rules: expected_rules,
+            token_classes: IndexMap::new(),
+            index: RuleIndex::default(),
         };
 
-        let actual_grammar = build_grammar(bnf_contents);
+        let actual_grammar = build_grammar(bnf_contents).unwrap();
 
         assert_eq!(actual_grammar, expected_grammar);
     }
@@ -189,16 +952,220 @@ mod tests {
         let bnf_grammar = "<S> ::= <X> <Y> \n
                             <X> ::= a <S> | b \n
                             <Y> ::= bb | c";
-        let mut input_cfg = build_grammar(bnf_grammar);
+        let mut input_cfg = build_grammar(bnf_grammar).unwrap();
 
         let expected_bnf = "<S'> ::= <S> \n
                             <S> ::= <X> <Y> \n
                             <X> ::= a <S> | b \n
                             <Y> ::= bb | c";
 
-        let expected_cfg = build_grammar(expected_bnf);
+        let expected_cfg = build_grammar(expected_bnf).unwrap();
         remove_start_on_rhs(&mut input_cfg);
 
         assert_eq!(input_cfg, expected_cfg);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_rule_spans() {
+        let bnf_grammar = "<S> ::= <X> <Y>\n<X> ::= a <S> | b\n";
+        let grammar = build_grammar(bnf_grammar).unwrap();
+
+        let rule = grammar.production_rules.get(&ProductionRule::new("<X>", &["b"])).unwrap();
+        let span = rule.span.unwrap();
+
+        assert_eq!(span.line, 2);
+        assert_eq!(&bnf_grammar[span.start..span.end], "b");
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let bnf_grammar = "<S> ::= <NP> <VP>\n<NP> ::= The dog | The cat\n<VP> ::= ate its food";
+        let grammar = build_grammar(bnf_grammar).unwrap();
+
+        let json = grammar.to_json().unwrap();
+        let deserialized = ContextFreeGrammar::from_json(&json).unwrap();
+
+        assert_eq!(deserialized, grammar);
+    }
+
+    #[test]
+    fn test_malformed_json() {
+        let json = |variables: &str, terminals: &str| {
+            format!(
+                r#"{{"variables": [{}], "terminals": [{}], "start_symbol": "<S>",
+                    "production_rules": [{{"lhs": "<S>", "rhs": ["a", "<T>"]}}, {{"lhs": "<T>", "rhs": ["b"]}}]}}"#,
+                variables, terminals
+            )
+        };
+        assert!(ContextFreeGrammar::from_json(&json(r#""<S>", "<T>""#, r#""a", "b""#)).is_ok());
+
+        let error = ContextFreeGrammar::from_json(&json(r#""<S>""#, r#""a", "b""#)).unwrap_err();
+        assert_eq!(error.message, "<T> in <S> ::= a <T> is neither a variable nor a terminal");
+        let error = ContextFreeGrammar::from_json(&json(r#""<S>""#, r#""a", "b", "<T>""#)).unwrap_err();
+        assert_eq!(error.message, "the lhs of <T> ::= b is not a variable");
+        let error = ContextFreeGrammar::from_json(&json(r#""<T>""#, r#""a", "b""#)).unwrap_err();
+        assert_eq!(error.message, "the start symbol <S> is not a variable");
+        let error = ContextFreeGrammar::from_json(&json(r#""<S>", "<T>", "a""#, r#""a", "b""#)).unwrap_err();
+        assert_eq!(error.message, "a is both a variable and a terminal");
+
+        assert!(ContextFreeGrammar::from_json(r#"{"variables": ["<S>"]"#).is_err());
+        assert!(serde_json::from_str::<ContextFreeGrammar>(&json(r#""<S>""#, r#""a", "b""#)).is_err());
+        assert!(ContextFreeGrammar::from_json(r#"{"variables": "<S>", "terminals": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let grammar = build_grammar("<S> ::= a <T> | <T>\n<T> ::= b").unwrap();
+        let deserialized: ContextFreeGrammar = serde_json::from_str(&grammar.to_json().unwrap()).unwrap();
+
+        assert_eq!(deserialized, grammar);
+        assert_eq!(deserialized.rules_for("<S>").count(), 2);
+        assert_eq!(deserialized.rules_for("<T>").collect::<Vec<_>>(), [&ProductionRule::new("<T>", &["b"])]);
+        assert_eq!(deserialized.indexed_rules(), grammar.indexed_rules());
+    }
+
+    #[test]
+    fn test_epsilon_rules() {
+        let grammar = build_grammar("<A> ::= a <A> | ε\n<B> ::= b |").unwrap();
+
+        assert!(grammar.production_rules.contains(&ProductionRule::new("<A>", &[])));
+        assert!(grammar.production_rules.contains(&ProductionRule::new("<B>", &[])));
+        assert!(!grammar.terminals.contains(""));
+        assert!(!grammar.terminals.contains(EPSILON));
+    }
+
+    #[test]
+    fn test_to_bnf() {
+        let bnf_grammar = "<X> ::= a <S> | b\n<S> ::= <X> <Y>\n<Y> ::= bb\n<X> ::= ε\n<Y> ::= c";
+        let mut grammar = build_grammar(bnf_grammar).unwrap();
+        grammar.start_symbol = "<S>".to_string();
+
+        let expected = "<S> ::= <X> <Y>\n<X> ::= a <S> | b | ε\n<Y> ::= bb | c\n";
+
+        assert_eq!(grammar.to_bnf(), expected);
+    }
+
+    #[test]
+    fn test_to_bnf_round_trip() {
+        let bnf_grammar = "<S> ::= <X> <Y> \n
+                            <X> ::= a <S> | b \n
+                            <Y> ::= bb | c | ";
+        let mut grammar = build_grammar(bnf_grammar).unwrap();
+        remove_start_on_rhs(&mut grammar);
+
+        let printed = grammar.to_bnf();
+
+        assert_eq!(build_grammar(&printed).unwrap(), grammar);
+        assert_eq!(build_grammar(&printed).unwrap().to_bnf(), printed);
+    }
+
+    #[test]
+    fn test_semantic_actions() {
+        let bnf_grammar = "<S> ::= <S> or <B> { $$ = $1 || $3 } | <B>\n<B> ::= t { $$ = \"|\" == \"|\" } | { } | { f }";
+        let grammar = build_grammar(bnf_grammar).unwrap();
+
+        let rule = grammar.production_rules.get(&ProductionRule::new("<S>", &["<S>", "or", "<B>"])).unwrap();
+        assert_eq!(rule.action.as_deref(), Some("$$ = $1 || $3"));
+        assert_eq!(&bnf_grammar[rule.span.unwrap().start..rule.span.unwrap().end], "<S> or <B> { $$ = $1 || $3 }");
+        // braces without a `$` are terminals
+        assert!(grammar.production_rules.contains(&ProductionRule::new("<B>", &["{", "}"])));
+        assert!(grammar.production_rules.contains(&ProductionRule::new("<B>", &["{", "f", "}"])));
+        assert_eq!(grammar.production_rules.len(), 5);
+
+        let printed = grammar.to_bnf();
+        assert!(printed.contains("<B> ::= t { $$ = \"|\" == \"|\" } | { } | { f }"), "{}", printed);
+        assert_eq!(build_grammar(&printed).unwrap().to_bnf(), printed);
+
+        // the same rule may only be repeated with the same action
+        assert!(build_grammar("<S> ::= a { $$ = 1 } | a { $$ = 1 }").is_ok());
+        let error = build_grammar("<S> ::= a { $$ = 1 }\n<S> ::= a { $$ = 2 }").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: <S> ::= a is already defined on line 1 with a different action");
+        assert!(build_grammar("<S> ::= a | a { $$ = 2 }").is_err());
+    }
+
+    #[test]
+    fn test_add_and_remove_rules() {
+        let mut grammar = build_grammar("<S> ::= a <A>\n<A> ::= b").unwrap();
+
+        assert!(grammar.add_rule(ProductionRule::new("<A>", &["<B>", "c"])));
+        assert!(!grammar.add_rule(ProductionRule::new("<A>", &["b"])));
+        assert!(grammar.is_variable("<B>"));
+        assert!(grammar.terminals().contains("c"));
+
+        assert!(grammar.remove_rule(&ProductionRule::new("<A>", &["<B>", "c"])));
+        assert!(!grammar.remove_rule(&ProductionRule::new("<A>", &["<B>", "c"])));
+        assert_eq!(grammar, build_grammar("<S> ::= a <A>\n<A> ::= b").unwrap());
+
+        // the index is updated in place, so it must match one built from scratch after every edit
+        let assert_indexed = |grammar: &ContextFreeGrammar| {
+            let rebuilt = ContextFreeGrammar::from_json(&grammar.to_json().unwrap()).unwrap();
+            assert_eq!(grammar.indexed_rules(), rebuilt.indexed_rules());
+            for variable in grammar.variables() {
+                let id = grammar.symbol_id(variable).unwrap();
+                assert_eq!(grammar.rule_indices_for(id), rebuilt.rule_indices_for(id));
+            }
+            assert_eq!(grammar.to_bnf(), rebuilt.to_bnf());
+        };
+        grammar.add_rule(ProductionRule::new("<S>", &["a", "b"]));
+        assert_indexed(&grammar);
+        grammar.add_rule(ProductionRule::new("<A>", &["<C>", "d"]));
+        assert_indexed(&grammar);
+        grammar.add_rule(ProductionRule::new("<C>", &["b"]));
+        assert_indexed(&grammar);
+        grammar.remove_rule(&ProductionRule::new("<S>", &["a", "<A>"]));
+        assert_indexed(&grammar);
+        assert_eq!(grammar.to_bnf(), "<S> ::= a b\n<A> ::= b | <C> d\n<C> ::= b\n");
+        grammar.remove_rule(&ProductionRule::new("<A>", &["<C>", "d"]));
+        assert_indexed(&grammar);
+        assert!(!grammar.terminals().contains("d") && grammar.is_variable("<C>"));
+    }
+
+    #[test]
+    fn test_token_classes() {
+        let bnf_grammar = "<S> ::= <id> = <number>\n@token <number> = /[0-9]+/\n@token <id> = /[a-z]+/";
+        let grammar = build_grammar(bnf_grammar).unwrap();
+
+        assert_eq!(grammar.variables().len(), 1);
+        assert!(grammar.terminals().contains("<number>"));
+        assert_eq!(grammar.token_classes()["<id>"], "[a-z]+");
+        assert_eq!(build_grammar(&grammar.to_bnf()).unwrap(), grammar);
+        assert_eq!(ContextFreeGrammar::from_json(&grammar.to_json().unwrap()).unwrap(), grammar);
+
+        let error = build_grammar("<S> ::= a\n@token <n> = /[0-9/").unwrap_err();
+        assert_eq!(error.span.unwrap().line, 2);
+        assert!(build_grammar("@token <S> = /a/\n<S> ::= a").is_err());
+        assert!(build_grammar("@tokens <n> = /a/\n<S> ::= a").is_err());
+    }
+
+    #[test]
+    fn test_grammar_builder() {
+        let grammar = GrammarBuilder::new()
+            .alternatives("<S>", &[&["<id>", "=", "<E>"], &[]])
+            .alternatives("<E>", &[&["<E>", "+", "<id>"], &["<id>"]])
+            .token_class("<id>", "[a-z]+")
+            .build()
+            .unwrap();
+
+        let expected = build_grammar("@token <id> = /[a-z]+/\n<S> ::= <id> = <E> | ε\n<E> ::= <E> + <id> | <id>").unwrap();
+        assert_eq!(grammar, expected);
+        assert_eq!(grammar.symbol_kind("<E>"), Some(SymbolKind::Variable));
+        assert_eq!(grammar.symbol_kind("+"), Some(SymbolKind::Terminal));
+        assert_eq!(grammar.symbol_kind("<id>"), Some(SymbolKind::TokenClass));
+        assert_eq!(grammar.symbol_kind("-"), None);
+
+        let by_lhs = grammar.rules_by_lhs();
+        assert_eq!(by_lhs[1].0, "<E>");
+        assert_eq!(by_lhs[1].1.len(), 2);
+    }
+
+    #[test]
+    fn test_grammar_builder_validation() {
+        assert!(GrammarBuilder::new().build().is_err());
+        assert!(GrammarBuilder::new().rule("S", &["a"]).build().is_err());
+        assert!(GrammarBuilder::new().rule("<S>", &["a b"]).build().is_err());
+        assert!(GrammarBuilder::new().rule("<S>", &["a", "|"]).build().is_err());
+        assert!(GrammarBuilder::new().rule("<S>", &["<A>"]).build().is_err());
+        assert!(GrammarBuilder::new().rule("<S>", &["a"]).start_symbol("<T>").build().is_err());
+        assert!(GrammarBuilder::new().rule("<S>", &["<n>"]).token_class("<n>", "[0-").build().is_err());
+    }
+}
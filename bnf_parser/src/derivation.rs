@@ -0,0 +1,144 @@
+use crate::context_free_grammar::{ProductionRule, EPSILON};
+use crate::parse_tree::ParseTree;
+
+/// Which variable of a sentential form is expanded at each step of a derivation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Order {
+    Leftmost,
+    Rightmost,
+}
+
+/// How the variable expanded at each step is marked when printing a derivation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Highlight {
+    /// Wraps the variable in square brackets.
+    Brackets,
+    /// Underlines the variable in bold using ANSI escape codes.
+    Ansi,
+}
+
+/// One sentential form of a derivation, with the variable expanded next and the production used on it.
+/// The last step of a derivation is the sentence itself and has no expansion.
+#[derive(Debug, PartialEq)]
+pub struct Step {
+    pub form: Vec<String>,
+    pub expansion: Option<(usize, ProductionRule)>,
+}
+
+/// Lists the sentential forms of the leftmost or rightmost derivation described by a parse tree,
+/// starting from the symbol at its root.
+pub fn derive(tree: &ParseTree, order: Order) -> Vec<Step> {
+    let mut form: Vec<&ParseTree> = vec![tree];
+    let mut steps = Vec::new();
+
+    loop {
+        let symbols: Vec<String> = form.iter().map(|node| node.name().to_string()).collect();
+
+        let mut variables = form
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node, ParseTree::Variable { .. }));
+        let next = match order {
+            Order::Leftmost => variables.next(),
+            Order::Rightmost => variables.next_back(),
+        };
+
+        let Some((index, ParseTree::Variable { name, children })) = next else {
+            steps.push(Step { form: symbols, expansion: None });
+            return steps;
+        };
+
+        let rhs: Vec<&str> = children.iter().map(|child| child.name()).collect();
+        steps.push(Step { form: symbols, expansion: Some((index, ProductionRule::new(name, &rhs))) });
+
+        form.splice(index..index + 1, children.iter());
+    }
+}
+
+/// Formats a derivation one sentential form per line, marking the variable expanded at each
+/// step and listing the production used on it next to the form.
+pub fn format_derivation(steps: &[Step], highlight: Highlight) -> String {
+    let forms: Vec<(String, usize)> = steps.iter().map(|step| format_form(step, highlight)).collect();
+    let width = forms.iter().map(|(_, visible_len)| *visible_len).max().unwrap_or(0);
+
+    let mut output = String::new();
+    for (index, (step, (form, visible_len))) in steps.iter().zip(&forms).enumerate() {
+        let arrow = if index == 0 { "  " } else { "=>" };
+        output.push_str(&format!("{} {}", arrow, form));
+
+        if let Some((_, rule)) = &step.expansion {
+            let rhs = if rule.rhs.is_empty() { EPSILON.to_string() } else { rule.rhs.join(" ") };
+            output.push_str(&" ".repeat(width - visible_len + 4));
+            output.push_str(&format!("({} ::= {})", rule.lhs, rhs));
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Formats a sentential form, returning it with its length as it appears on screen.
+fn format_form(step: &Step, highlight: Highlight) -> (String, usize) {
+    if step.form.is_empty() {
+        return (EPSILON.to_string(), EPSILON.chars().count());
+    }
+
+    let expanded = step.expansion.as_ref().map(|(index, _)| *index);
+    let symbols: Vec<String> = step
+        .form
+        .iter()
+        .enumerate()
+        .map(|(index, symbol)| match (Some(index) == expanded, highlight) {
+            (false, _) => symbol.clone(),
+            (true, Highlight::Brackets) => format!("[{}]", symbol),
+            (true, Highlight::Ansi) => format!("\x1b[1;4m{}\x1b[0m", symbol),
+        })
+        .collect();
+
+    let mut visible_len = step.form.iter().map(|symbol| symbol.chars().count()).sum::<usize>() + step.form.len() - 1;
+    if expanded.is_some() && highlight == Highlight::Brackets {
+        visible_len += 2;
+    }
+
+    (symbols.join(" "), visible_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+    use crate::earley::parse;
+
+    fn tree(bnf_grammar: &str, sentence: &str) -> ParseTree {
+        let grammar = build_grammar(bnf_grammar).unwrap();
+        let sentence: Vec<String> = sentence.split_whitespace().map(|s| s.to_string()).collect();
+        parse(&grammar, &sentence, 1).remove(0)
+    }
+
+    fn forms(steps: &[Step]) -> Vec<String> {
+        steps.iter().map(|step| step.form.join(" ")).collect()
+    }
+
+    #[test]
+    fn test_leftmost_and_rightmost() {
+        let tree = tree("<E> ::= <E> + <T> | <T>\n<T> ::= id", "id + id");
+
+        let leftmost = derive(&tree, Order::Leftmost);
+        let rightmost = derive(&tree, Order::Rightmost);
+
+        assert_eq!(forms(&leftmost), vec!["<E>", "<E> + <T>", "<T> + <T>", "id + <T>", "id + id"]);
+        assert_eq!(forms(&rightmost), vec!["<E>", "<E> + <T>", "<E> + id", "<T> + id", "id + id"]);
+        assert_eq!(leftmost[1].expansion, Some((0, ProductionRule::new("<E>", &["<T>"]))));
+        assert_eq!(rightmost[1].expansion, Some((2, ProductionRule::new("<T>", &["id"]))));
+    }
+
+    #[test]
+    fn test_format_derivation() {
+        let tree = tree("<S> ::= a <S> | ε", "a");
+        let steps = derive(&tree, Order::Leftmost);
+
+        let expected = "   [<S>]      (<S> ::= a <S>)\n=> a [<S>]    (<S> ::= ε)\n=> a\n";
+
+        assert_eq!(format_derivation(&steps, Highlight::Brackets), expected);
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::context_free_grammar::{ContextFreeGrammar, ProductionRule};
+use crate::language::{self, LanguageSize};
+use crate::ll;
+use crate::sets::GrammarSets;
+
+/// Whether a variable's rules were all added, all removed, or some of each.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChangeKind::Added => write!(f, "added"),
+            ChangeKind::Removed => write!(f, "removed"),
+            ChangeKind::Changed => write!(f, "changed"),
+        }
+    }
+}
+
+/// The rules of a variable that one grammar has and the other doesn't, and the rules both have
+/// with different semantic actions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleChanges {
+    pub lhs: String,
+    pub kind: ChangeKind,
+    pub added: Vec<ProductionRule>,
+    pub removed: Vec<ProductionRule>,
+    /// The old and new versions of each rule whose action changed.
+    pub actions: Vec<(ProductionRule, ProductionRule)>,
+}
+
+/// A property derived from a grammar, like whether it is LL(1), that differs between two grammars.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyChange {
+    pub property: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// Structural differences between two grammars. Rules of the old grammar are compared after renaming
+/// its symbols as in `renames`, so a renamed symbol doesn't show up as every rule using it changing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrammarDiff {
+    pub renames: Vec<(String, String)>,
+    pub start_symbol: Option<(String, String)>,
+    pub changes: Vec<RuleChanges>,
+    pub properties: Vec<PropertyChange>,
+}
+impl GrammarDiff {
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty() && self.start_symbol.is_none() && self.changes.is_empty() && self.properties.is_empty()
+    }
+}
+
+/// Compares two grammars: the symbols that look renamed, the rules added and removed for each variable,
+/// and the derived properties that changed.
+pub fn diff(old: &ContextFreeGrammar, new: &ContextFreeGrammar) -> GrammarDiff {
+    let renames = find_renames(old, new);
+    let renamed = rename(old, &renames);
+
+    let start_symbol = (renamed.start_symbol() != new.start_symbol())
+        .then(|| (old.start_symbol().to_string(), new.start_symbol().to_string()));
+
+    let mut changes: IndexMap<String, RuleChanges> = IndexMap::new();
+    for rule in renamed.production_rules() {
+        // rules are equal regardless of their actions, so those are compared on their own
+        let new_rule = new.production_rules().get(rule);
+        if new_rule.is_some_and(|new_rule| new_rule.action == rule.action) {
+            continue;
+        }
+        let entry = changes.entry(rule.lhs.clone()).or_insert_with(|| empty_changes(&rule.lhs));
+        match new_rule {
+            Some(new_rule) => entry.actions.push((rule.clone(), new_rule.clone())),
+            None => entry.removed.push(rule.clone()),
+        }
+    }
+    for rule in new.production_rules() {
+        if !renamed.production_rules().contains(rule) {
+            changes.entry(rule.lhs.clone()).or_insert_with(|| empty_changes(&rule.lhs)).added.push(rule.clone());
+        }
+    }
+
+    for changes in changes.values_mut() {
+        changes.kind = if renamed.rules_for(&changes.lhs).next().is_none() {
+            ChangeKind::Added
+        } else if new.rules_for(&changes.lhs).next().is_none() {
+            ChangeKind::Removed
+        } else {
+            ChangeKind::Changed
+        };
+    }
+
+    GrammarDiff {
+        renames,
+        start_symbol,
+        changes: changes.into_values().collect(),
+        properties: property_changes(&renamed, new),
+    }
+}
+
+fn empty_changes(lhs: &str) -> RuleChanges {
+    RuleChanges {
+        lhs: lhs.to_string(),
+        kind: ChangeKind::Changed,
+        added: Vec::new(),
+        removed: Vec::new(),
+        actions: Vec::new(),
+    }
+}
+
+/// Guesses which symbols of the old grammar were renamed in the new one. A symbol only in the old grammar
+/// and one of the same kind only in the new grammar are paired when renaming the first to the second makes
+/// the most rules of the two grammars the same, and pairs are chosen greedily until none makes more match.
+fn find_renames(old: &ContextFreeGrammar, new: &ContextFreeGrammar) -> Vec<(String, String)> {
+    let only_in = |grammar: &ContextFreeGrammar, other: &ContextFreeGrammar, variables: bool| -> Vec<String> {
+        let symbols = if variables { grammar.variables() } else { grammar.terminals() };
+        symbols.iter().filter(|symbol| other.symbol_id(symbol).is_none()).cloned().collect()
+    };
+
+    let mut renames: Vec<(String, String)> = Vec::new();
+    let matching = |renames: &[(String, String)]| -> usize {
+        rename(old, renames).production_rules().iter().filter(|rule| new.production_rules().contains(*rule)).count()
+    };
+
+    for variables in [true, false] {
+        let mut old_only = only_in(old, new, variables);
+        let mut new_only = only_in(new, old, variables);
+        loop {
+            let current = matching(&renames);
+            let mut best: Option<(usize, usize, usize)> = None;
+            for (old_index, from) in old_only.iter().enumerate() {
+                for (new_index, to) in new_only.iter().enumerate() {
+                    let mut candidate = renames.clone();
+                    candidate.push((from.clone(), to.clone()));
+                    let gain = matching(&candidate).saturating_sub(current);
+                    if gain > 0 && best.is_none_or(|(best_gain, _, _)| gain > best_gain) {
+                        best = Some((gain, old_index, new_index));
+                    }
+                }
+            }
+
+            let Some((_, old_index, new_index)) = best else {
+                break;
+            };
+            renames.push((old_only.remove(old_index), new_only.remove(new_index)));
+        }
+    }
+
+    renames.sort_by_key(|(from, _)| old.symbol_id(from));
+    renames
+}
+
+/// Returns the grammar with its symbols renamed.
+fn rename(grammar: &ContextFreeGrammar, renames: &[(String, String)]) -> ContextFreeGrammar {
+    let renamed = |symbol: &String| -> String {
+        renames.iter().find(|(from, _)| from == symbol).map_or_else(|| symbol.clone(), |(_, to)| to.clone())
+    };
+    let rules = grammar.production_rules().iter().map(|rule| ProductionRule {
+        lhs: renamed(&rule.lhs),
+        rhs: rule.rhs.iter().map(renamed).collect(),
+        span: rule.span,
+        action: rule.action.clone(),
+    });
+    grammar.with_rules(&renamed(&grammar.start_symbol().to_string()), rules.collect::<Vec<_>>())
+}
+
+/// Compares whether the grammars are LL(1), the size of their languages, and the nullable, FIRST and
+/// FOLLOW sets of the variables they share.
+fn property_changes(old: &ContextFreeGrammar, new: &ContextFreeGrammar) -> Vec<PropertyChange> {
+    let mut changes = Vec::new();
+    let mut compare = |property: String, old: String, new: String| {
+        if old != new {
+            changes.push(PropertyChange { property, old, new });
+        }
+    };
+
+    let ll1 = |grammar: &ContextFreeGrammar| if ll::ll1_table(grammar).conflicts().is_empty() { "yes" } else { "no" };
+    compare("LL(1)".to_string(), ll1(old).to_string(), ll1(new).to_string());
+
+    let size = |grammar: &ContextFreeGrammar| match language::language_size(grammar, 0) {
+        LanguageSize::Empty => "empty",
+        LanguageSize::Finite(_) => "finite",
+        LanguageSize::Infinite => "infinite",
+    };
+    compare("language".to_string(), size(old).to_string(), size(new).to_string());
+
+    let (old_sets, new_sets) = (GrammarSets::new(old), GrammarSets::new(new));
+    let format_set = |set: &IndexSet<&str>, other: &IndexSet<&str>| -> String {
+        // in the same order for both grammars, so only the differences stand out
+        let mut symbols: Vec<&str> = set.iter().copied().collect();
+        symbols.sort_by_key(|symbol| (other.get_index_of(symbol).unwrap_or(usize::MAX), *symbol));
+        format!("{{ {} }}", symbols.join(", "))
+    };
+    for variable in old.variables().iter().filter(|variable| new.is_variable(variable)) {
+        let variable = variable.as_str();
+        let nullable = |sets: &GrammarSets| if sets.nullable.contains(variable) { "yes" } else { "no" }.to_string();
+        compare(format!("nullable({})", variable), nullable(&old_sets), nullable(&new_sets));
+
+        for (name, old_set, new_set) in [
+            ("FIRST", &old_sets.first[variable], &new_sets.first[variable]),
+            ("FOLLOW", &old_sets.follow[variable], &new_sets.follow[variable]),
+        ] {
+            let same: HashSet<&str> = old_set.iter().copied().collect();
+            if same != new_set.iter().copied().collect() {
+                compare(format!("{}({})", name, variable), format_set(old_set, new_set), format_set(new_set, new_set));
+            }
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+
+    #[test]
+    fn test_diff_rules() {
+        let old = build_grammar("<E> ::= <E> + <T> | <T>\n<T> ::= id | ( <E> )").unwrap();
+        let new = build_grammar("<E> ::= <E> + <T> | <E> - <T> | <T>\n<T> ::= id | ( <E> ) | num").unwrap();
+        let diff = diff(&old, &new);
+
+        assert!(diff.renames.is_empty() && diff.start_symbol.is_none());
+        let changes: Vec<(&str, usize, usize)> =
+            diff.changes.iter().map(|c| (c.lhs.as_str(), c.added.len(), c.removed.len())).collect();
+        assert_eq!(changes, [("<E>", 1, 0), ("<T>", 1, 0)]);
+        assert_eq!(
+            diff.properties,
+            [
+                ("FIRST(<E>)", "{ id, ( }", "{ id, (, num }"),
+                ("FOLLOW(<E>)", "{ $, +, ) }", "{ $, +, -, ) }"),
+                ("FIRST(<T>)", "{ id, ( }", "{ id, (, num }"),
+                ("FOLLOW(<T>)", "{ $, +, ) }", "{ $, +, -, ) }"),
+            ]
+            .map(|(property, old, new)| PropertyChange {
+                property: property.to_string(),
+                old: old.to_string(),
+                new: new.to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_diff_renames() {
+        let old = build_grammar("<Expr> ::= <Expr> plus <Term> | <Term>\n<Term> ::= id").unwrap();
+        let new = build_grammar("<E> ::= <E> + <T> | <T>\n<T> ::= id | num").unwrap();
+        let diff = diff(&old, &new);
+
+        assert_eq!(
+            diff.renames,
+            [
+                ("<Expr>".to_string(), "<E>".to_string()),
+                ("<Term>".to_string(), "<T>".to_string()),
+                ("plus".to_string(), "+".to_string()),
+            ]
+        );
+        assert!(diff.start_symbol.is_none());
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].added, [ProductionRule::new("<T>", &["num"])]);
+
+        let same = build_grammar("<E> ::= <E> + <T> | <T>\n<T> ::= id | num").unwrap();
+        assert!(super::diff(&new, &same).is_empty());
+    }
+
+    #[test]
+    fn test_diff_actions() {
+        let old = build_grammar("<E> ::= <E> + <T> { $$ = $1 + $3 } | <T>\n<T> ::= id { $$ = 1 }").unwrap();
+        let new = build_grammar("<E> ::= <E> + <T> { $$ = $3 + $1 } | <T>\n<T> ::= id").unwrap();
+        let diff = diff(&old, &new);
+
+        let actions: Vec<(&str, Option<&str>, Option<&str>)> = diff
+            .changes
+            .iter()
+            .flat_map(|c| &c.actions)
+            .map(|(old, new)| (old.lhs.as_str(), old.action.as_deref(), new.action.as_deref()))
+            .collect();
+        assert_eq!(actions, [("<E>", Some("$$ = $1 + $3"), Some("$$ = $3 + $1")), ("<T>", Some("$$ = 1"), None)]);
+        assert!(diff.changes.iter().all(|c| c.kind == ChangeKind::Changed));
+        assert!(diff.changes.iter().all(|c| c.added.is_empty() && c.removed.is_empty()));
+        assert!(super::diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_diff_properties() {
+        let old = build_grammar("<E> ::= <T> <R>\n<R> ::= + <T> <R> | ε\n<T> ::= id").unwrap();
+        let new = build_grammar("<E> ::= <E> + <T> | <T>\n<T> ::= id").unwrap();
+        let diff = diff(&old, &new);
+        let ll1 = PropertyChange { property: "LL(1)".to_string(), old: "yes".to_string(), new: "no".to_string() };
+        assert_eq!(diff.properties[0], ll1);
+        let changes: Vec<(&str, ChangeKind)> = diff.changes.iter().map(|c| (c.lhs.as_str(), c.kind)).collect();
+        assert_eq!(changes, [("<E>", ChangeKind::Changed), ("<R>", ChangeKind::Removed)]);
+    }
+}
@@ -0,0 +1,397 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::context_free_grammar::{ContextFreeGrammar, IndexedRule, SymbolId};
+use crate::parse_tree::ParseTree;
+use crate::sets::nullable_ids;
+
+/// How many times the same variable may span the same input on one path of a parse tree.
+/// Allowing one repeat exposes ambiguity caused by cycles like `<A> ::= <A>` while keeping trees finite.
+const MAX_CYCLE_REPEATS: usize = 2;
+
+/// An Earley item: a production rule with a dot position and the input position it was predicted at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Item {
+    rule: usize,
+    dot: usize,
+    origin: usize,
+}
+
+/// The items of one Earley set, with the items waiting on each variable for completion.
+#[derive(Debug, Default)]
+struct EarleySet {
+    items: Vec<Item>,
+    waiting: HashMap<SymbolId, Vec<Item>>,
+}
+
+/// Incremental Earley recognizer for any context free grammar.
+/// Terminals are fed one at a time with `push` and can be taken back with `pop`,
+/// which makes it cheap to explore every sentence sharing a common prefix.
+pub struct EarleyRecognizer<'g> {
+    grammar: &'g ContextFreeGrammar,
+    rules: &'g [IndexedRule],
+    nullable: Vec<bool>,
+    sets: Vec<EarleySet>,
+}
+impl<'g> EarleyRecognizer<'g> {
+    pub fn new(grammar: &'g ContextFreeGrammar) -> EarleyRecognizer<'g> {
+        let mut recognizer = EarleyRecognizer {
+            grammar,
+            rules: grammar.indexed_rules(),
+            nullable: nullable_ids(grammar),
+            sets: Vec::new(),
+        };
+
+        let start_items = match grammar.symbol_id(grammar.start_symbol()) {
+            Some(start_symbol) => recognizer.predict(start_symbol, 0),
+            None => Vec::new(),
+        };
+        recognizer.sets.push(EarleySet::default());
+        recognizer.close(start_items);
+
+        recognizer
+    }
+
+    /// Feeds the next terminal of the input. Returns true if the input so far is still
+    /// a prefix of some sentence in the language.
+    pub fn push(&mut self, terminal: &str) -> bool {
+        let current = self.sets.len() - 1;
+
+        // a terminal the grammar doesn't use can't be scanned by any item
+        let terminal = self.grammar.symbol_id(terminal).filter(|&id| !self.grammar.is_variable_id(id));
+        let scanned: Vec<Item> = match terminal {
+            Some(terminal) => self.sets[current]
+                .items
+                .iter()
+                .filter(|item| self.next_symbol(item) == Some(terminal))
+                .map(|item| Item { dot: item.dot + 1, ..*item })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        self.sets.push(EarleySet::default());
+        self.close(scanned);
+
+        self.is_viable()
+    }
+
+    /// Takes back the last terminal fed with `push`.
+    pub fn pop(&mut self) {
+        if self.sets.len() > 1 {
+            self.sets.pop();
+        }
+    }
+
+    /// Returns true if the input so far is a prefix of some sentence in the language.
+    pub fn is_viable(&self) -> bool {
+        !self.sets[self.sets.len() - 1].items.is_empty()
+    }
+
+    /// Returns true if the input so far is a sentence in the language.
+    pub fn accepts(&self) -> bool {
+        let start_symbol = self.grammar.symbol_id(self.grammar.start_symbol());
+        self.sets[self.sets.len() - 1].items.iter().any(|item| {
+            item.origin == 0
+                && Some(self.rules[item.rule].lhs) == start_symbol
+                && item.dot == self.rules[item.rule].rhs.len()
+        })
+    }
+
+    /// Builds up to `limit` distinct parse trees of `sentence`, which must be the terminals fed so far.
+    pub fn parse_trees(&self, sentence: &[String], limit: usize) -> Vec<ParseTree> {
+        if !self.accepts() {
+            return Vec::new();
+        }
+        let Some(start_symbol) = self.grammar.symbol_id(self.grammar.start_symbol()) else {
+            return Vec::new();
+        };
+
+        let mut forest = Forest {
+            recognizer: self,
+            sentence: sentence.iter().map(|terminal| self.grammar.symbol_id(terminal)).collect(),
+            completed: HashMap::new(),
+            path: HashMap::new(),
+            fits: HashMap::new(),
+            limit,
+        };
+        for (end, set) in self.sets.iter().enumerate() {
+            for item in set.items.iter().filter(|item| self.next_symbol(item).is_none()) {
+                let lhs = self.rules[item.rule].lhs;
+                forest.completed.entry((lhs, item.origin)).or_default().push((item.rule, end));
+            }
+        }
+
+        forest.trees(start_symbol, 0, sentence.len())
+    }
+
+    /// Returns the symbol after the dot of an item, or None if the item is complete.
+    fn next_symbol(&self, item: &Item) -> Option<SymbolId> {
+        self.rules[item.rule].rhs.get(item.dot).copied()
+    }
+
+    /// Returns the items predicting every rule of a variable at a position.
+    fn predict(&self, variable: SymbolId, position: usize) -> Vec<Item> {
+        self.grammar
+            .rule_indices_for(variable)
+            .iter()
+            .map(|&rule| Item { rule, dot: 0, origin: position })
+            .collect()
+    }
+
+    /// Adds items to the last Earley set and runs prediction and completion until nothing changes.
+    fn close(&mut self, items: Vec<Item>) {
+        let current = self.sets.len() - 1;
+        let mut seen: HashSet<Item> = self.sets[current].items.iter().copied().collect();
+        let mut worklist = items;
+
+        while let Some(item) = worklist.pop() {
+            if !seen.insert(item) {
+                continue;
+            }
+            self.sets[current].items.push(item);
+
+            match self.next_symbol(&item) {
+                Some(symbol) if self.grammar.is_variable_id(symbol) => {
+                    self.sets[current].waiting.entry(symbol).or_default().push(item);
+                    worklist.extend(self.predict(symbol, current));
+
+                    // a nullable variable can be skipped over right away (Aycock and Horspool)
+                    if self.nullable[symbol.index()] {
+                        worklist.push(Item { dot: item.dot + 1, ..item });
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    let lhs = self.rules[item.rule].lhs;
+                    if let Some(waiting) = self.sets[item.origin].waiting.get(&lhs) {
+                        worklist.extend(waiting.iter().map(|waiting| Item { dot: waiting.dot + 1, ..*waiting }));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reads parse trees back out of a finished Earley chart.
+struct Forest<'r, 'g> {
+    recognizer: &'r EarleyRecognizer<'g>,
+    /// The sentence's terminals, None for terminals the grammar doesn't use.
+    sentence: Vec<Option<SymbolId>>,
+    /// Completed rules by (lhs, start position), with the position each one ends at.
+    completed: HashMap<(SymbolId, usize), Vec<(usize, usize)>>,
+    /// How often each variable and span is being expanded on the current path, used to cut off cycles.
+    path: HashMap<(SymbolId, usize, usize), usize>,
+    /// Whether the symbols of a rule from a dot position on can derive a span, by (rule, dot, start, end).
+    fits: HashMap<(usize, usize, usize, usize), bool>,
+    limit: usize,
+}
+impl<'g> Forest<'_, 'g> {
+    /// Returns up to `limit` parse trees of `symbol` deriving `sentence[start..end]`.
+    fn trees(&mut self, symbol: SymbolId, start: usize, end: usize) -> Vec<ParseTree> {
+        let grammar = self.recognizer.grammar;
+        if !grammar.is_variable_id(symbol) {
+            return if end == start + 1 && self.sentence[start] == Some(symbol) {
+                vec![ParseTree::Terminal { name: grammar.symbol(symbol).to_string(), text: None, span: None }]
+            } else {
+                Vec::new()
+            };
+        }
+
+        let key = (symbol, start, end);
+        let repeats = self.path.entry(key).or_default();
+        if *repeats >= MAX_CYCLE_REPEATS {
+            return Vec::new();
+        }
+        *repeats += 1;
+
+        let rules: Vec<usize> = self
+            .completed
+            .get(&(symbol, start))
+            .map(|completed| completed.iter().filter(|(_, e)| *e == end).map(|(rule, _)| *rule).collect())
+            .unwrap_or_default();
+
+        let mut trees = Vec::new();
+        for rule in rules {
+            for children in self.sequences(rule, 0, start, end) {
+                trees.push(ParseTree::Variable { name: grammar.symbol(symbol).to_string(), children });
+                if trees.len() >= self.limit {
+                    break;
+                }
+            }
+            if trees.len() >= self.limit {
+                break;
+            }
+        }
+
+        *self.path.get_mut(&key).expect("key was added above") -= 1;
+        trees
+    }
+
+    /// Returns up to `limit` ways for the symbols of a rule after the dot to derive `sentence[start..end]` in order.
+    fn sequences(&mut self, rule: usize, dot: usize, start: usize, end: usize) -> Vec<Vec<ParseTree>> {
+        let rhs: &'g [SymbolId] = &self.recognizer.rules[rule].rhs;
+        let Some(&first) = rhs.get(dot) else {
+            return if start == end { vec![Vec::new()] } else { Vec::new() };
+        };
+
+        let mut sequences = Vec::new();
+        for middle in self.ends(first, start, end) {
+            // only build trees for splits the rest of the rule can finish
+            if !self.fits(rule, dot + 1, middle, end) {
+                continue;
+            }
+            let trees = self.trees(first, start, middle);
+            if trees.is_empty() {
+                continue;
+            }
+
+            // moves the last tree and sequences instead of copying them, subtrees can be deep
+            let mut rest_sequences = self.sequences(rule, dot + 1, middle, end);
+            let tree_count = trees.len();
+            for (i, tree) in trees.into_iter().enumerate() {
+                let rests = if i + 1 == tree_count { std::mem::take(&mut rest_sequences) } else { rest_sequences.clone() };
+                let rest_count = rests.len();
+                let mut tree = Some(tree);
+                for (j, rest) in rests.into_iter().enumerate() {
+                    let first = if j + 1 == rest_count { tree.take() } else { tree.clone() };
+                    let mut sequence = Vec::with_capacity(rest.len() + 1);
+                    sequence.extend(first);
+                    sequence.extend(rest);
+                    sequences.push(sequence);
+                    if sequences.len() >= self.limit {
+                        return sequences;
+                    }
+                }
+            }
+        }
+
+        sequences
+    }
+
+    /// Returns true if the symbols of a rule after the dot can derive `sentence[start..end]`.
+    fn fits(&mut self, rule: usize, dot: usize, start: usize, end: usize) -> bool {
+        let rhs: &'g [SymbolId] = &self.recognizer.rules[rule].rhs;
+        let Some(&first) = rhs.get(dot) else {
+            return start == end;
+        };
+        if let Some(&fits) = self.fits.get(&(rule, dot, start, end)) {
+            return fits;
+        }
+
+        let fits = self.ends(first, start, end).into_iter().any(|middle| {
+            (self.recognizer.grammar.is_variable_id(first) || self.sentence[start] == Some(first))
+                && self.fits(rule, dot + 1, middle, end)
+        });
+        self.fits.insert((rule, dot, start, end), fits);
+        fits
+    }
+
+    /// Returns the positions up to `end` where a derivation of `symbol` starting at `start` can end.
+    fn ends(&self, symbol: SymbolId, start: usize, end: usize) -> Vec<usize> {
+        if !self.recognizer.grammar.is_variable_id(symbol) {
+            return if start < end { vec![start + 1] } else { Vec::new() };
+        }
+
+        let mut ends: Vec<usize> = self
+            .completed
+            .get(&(symbol, start))
+            .map(|completed| completed.iter().map(|(_, e)| *e).filter(|e| *e <= end).collect())
+            .unwrap_or_default();
+        ends.sort_unstable();
+        ends.dedup();
+        ends
+    }
+}
+
+/// Returns up to `limit` distinct parse trees of a sentence, or none if the grammar doesn't derive it.
+pub fn parse(grammar: &ContextFreeGrammar, sentence: &[String], limit: usize) -> Vec<ParseTree> {
+    let mut recognizer = EarleyRecognizer::new(grammar);
+    for terminal in sentence {
+        if !recognizer.push(terminal) {
+            return Vec::new();
+        }
+    }
+    recognizer.parse_trees(sentence, limit)
+}
+
+/// Returns true if the grammar derives the given sentence of terminals.
+pub fn recognizes(grammar: &ContextFreeGrammar, sentence: &[String]) -> bool {
+    let mut recognizer = EarleyRecognizer::new(grammar);
+    for terminal in sentence {
+        if !recognizer.push(terminal) {
+            return false;
+        }
+    }
+    recognizer.accepts()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+
+    fn sentence(words: &str) -> Vec<String> {
+        words.split_whitespace().map(|s| s.to_string()).collect()
+    }
+
+
+    #[test]
+    fn test_recognizes() {
+        let grammar = build_grammar("<E> ::= <E> + <T> | <T>\n<T> ::= <T> * id | id").unwrap();
+
+        assert!(recognizes(&grammar, &sentence("id")));
+        assert!(recognizes(&grammar, &sentence("id + id * id")));
+        assert!(!recognizes(&grammar, &sentence("id +")));
+        assert!(!recognizes(&grammar, &sentence("")));
+    }
+
+    #[test]
+    fn test_recognizes_nullable() {
+        let grammar = build_grammar("<S> ::= <A> <A> x | ε\n<A> ::= a | ε").unwrap();
+
+        assert!(recognizes(&grammar, &sentence("")));
+        assert!(recognizes(&grammar, &sentence("x")));
+        assert!(recognizes(&grammar, &sentence("a a x")));
+        assert!(!recognizes(&grammar, &sentence("a a a x")));
+    }
+
+    #[test]
+    fn test_push_and_pop() {
+        let grammar = build_grammar("<S> ::= a <S> b | ε").unwrap();
+        let mut recognizer = EarleyRecognizer::new(&grammar);
+
+        assert!(recognizer.accepts());
+        assert!(recognizer.push("a"));
+        assert!(!recognizer.accepts());
+        assert!(recognizer.push("a"));
+        recognizer.pop();
+        assert!(recognizer.push("b"));
+        assert!(recognizer.accepts());
+        assert!(!recognizer.push("b"));
+    }
+
+    #[test]
+    fn test_parse() {
+        let grammar = build_grammar("<E> ::= <E> + <T> | <T>\n<T> ::= id").unwrap();
+        let trees = parse(&grammar, &sentence("id + id"), 10);
+
+        assert_eq!(trees.len(), 1);
+        assert_eq!(trees[0].to_ascii(), "<E>\n├── <E>\n│   └── <T>\n│       └── id\n├── +\n└── <T>\n    └── id\n");
+    }
+
+    #[test]
+    fn test_parse_ambiguous() {
+        let grammar = build_grammar("<E> ::= <E> + <E> | id").unwrap();
+
+        assert_eq!(parse(&grammar, &sentence("id + id"), 10).len(), 1);
+        assert_eq!(parse(&grammar, &sentence("id + id + id"), 10).len(), 2);
+        assert_eq!(parse(&grammar, &sentence("id + id + id + id"), 10).len(), 5);
+        assert!(parse(&grammar, &sentence("id +"), 10).is_empty());
+    }
+
+    #[test]
+    fn test_parse_cycle() {
+        let grammar = build_grammar("<A> ::= <A> | a | <B> <A>\n<B> ::= ε").unwrap();
+
+        assert!(parse(&grammar, &sentence("a"), 10).len() >= 2);
+    }
+}
@@ -0,0 +1,162 @@
+use indexmap::IndexSet;
+
+use crate::context_free_grammar::ContextFreeGrammar;
+use crate::earley::{recognizes, EarleyRecognizer};
+use crate::generator::{Rng, SentenceGenerator};
+
+/// Maximum derivation depth used when sampling sentences longer than the exhaustive bound.
+const SAMPLE_DEPTH: usize = 24;
+
+/// A sentence accepted by exactly one of two grammars.
+#[derive(Debug, PartialEq)]
+pub struct Difference {
+    pub sentence: Vec<String>,
+    pub accepted_by_first: bool,
+}
+
+/// Outcome of comparing the languages of two grammars.
+#[derive(Debug, PartialEq)]
+pub enum Equivalence {
+    /// No difference was found. Holds the number of sentences checked exhaustively and by sampling.
+    Agree { exhaustive: usize, sampled: usize },
+    Differ(Difference),
+}
+
+/// Compares the languages of two grammars on every string of at most `max_len` terminals,
+/// then on `samples` random sentences of each grammar that may be longer.
+/// The exhaustive search runs by increasing length, so a reported difference found there is a shortest one.
+pub fn check_equivalence(
+    first: &ContextFreeGrammar,
+    second: &ContextFreeGrammar,
+    max_len: usize,
+    samples: usize,
+    mut rng: Rng,
+) -> Equivalence {
+    let alphabet: IndexSet<&str> = first
+        .terminals()
+        .iter()
+        .chain(second.terminals())
+        .map(|s| s.as_str())
+        .collect();
+
+    let mut search = Search {
+        first: EarleyRecognizer::new(first),
+        second: EarleyRecognizer::new(second),
+        alphabet: alphabet.into_iter().collect(),
+        prefix: Vec::new(),
+        checked: 0,
+    };
+
+    for len in 0..=max_len {
+        if let Some(difference) = search.find(len) {
+            return Equivalence::Differ(difference);
+        }
+    }
+
+    // random sentences of each grammar, checked against the other
+    let mut first_generator = SentenceGenerator::new(first, Rng::new(rng.next_u64()));
+    let mut second_generator = SentenceGenerator::new(second, Rng::new(rng.next_u64()));
+    let max_sample_len = 4 * max_len + 16;
+
+    let mut shortest: Option<Difference> = None;
+    let mut sampled = 0;
+    for _ in 0..samples {
+        let candidates = [
+            (first_generator.generate(SAMPLE_DEPTH, max_sample_len), second, true),
+            (second_generator.generate(SAMPLE_DEPTH, max_sample_len), first, false),
+        ];
+        for (sentence, other, accepted_by_first) in candidates {
+            let Some(sentence) = sentence else { continue };
+            sampled += 1;
+            if !recognizes(other, &sentence) && shortest.as_ref().is_none_or(|d| sentence.len() < d.sentence.len()) {
+                shortest = Some(Difference { sentence, accepted_by_first });
+            }
+        }
+    }
+
+    match shortest {
+        Some(difference) => Equivalence::Differ(difference),
+        None => Equivalence::Agree { exhaustive: search.checked, sampled },
+    }
+}
+
+/// Depth first search over every string, pruned to prefixes viable in at least one grammar.
+struct Search<'g> {
+    first: EarleyRecognizer<'g>,
+    second: EarleyRecognizer<'g>,
+    alphabet: Vec<&'g str>,
+    prefix: Vec<String>,
+    checked: usize,
+}
+impl Search<'_> {
+    /// Looks for a string of exactly `len` terminals extending the current prefix
+    /// that is accepted by one grammar but not the other.
+    fn find(&mut self, len: usize) -> Option<Difference> {
+        if self.prefix.len() == len {
+            self.checked += 1;
+            let accepted_by_first = self.first.accepts();
+            if accepted_by_first != self.second.accepts() {
+                return Some(Difference { sentence: self.prefix.clone(), accepted_by_first });
+            }
+            return None;
+        }
+
+        for index in 0..self.alphabet.len() {
+            let terminal = self.alphabet[index];
+            let first_viable = self.first.push(terminal);
+            let second_viable = self.second.push(terminal);
+            self.prefix.push(terminal.to_string());
+
+            let found = if first_viable || second_viable { self.find(len) } else { None };
+
+            self.prefix.pop();
+            self.first.pop();
+            self.second.pop();
+
+            if found.is_some() {
+                return found;
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+
+    #[test]
+    fn test_equivalent_grammars() {
+        let left_recursive = build_grammar("<E> ::= <E> + id | id").unwrap();
+        let right_recursive = build_grammar("<E> ::= id <R>\n<R> ::= + id <R> | ε").unwrap();
+
+        let result = check_equivalence(&left_recursive, &right_recursive, 6, 20, Rng::new(1));
+
+        assert!(matches!(result, Equivalence::Agree { .. }));
+    }
+
+    #[test]
+    fn test_shortest_difference() {
+        let reference = build_grammar("<S> ::= a <S> b | ε").unwrap();
+        let student = build_grammar("<S> ::= a <S> b | a b | a a b b b").unwrap();
+
+        let result = check_equivalence(&reference, &student, 6, 0, Rng::new(1));
+
+        let expected = Difference { sentence: vec![], accepted_by_first: true };
+        assert_eq!(result, Equivalence::Differ(expected));
+    }
+
+    #[test]
+    fn test_difference_found_by_sampling() {
+        let reference = build_grammar("<S> ::= a <S> | a").unwrap();
+        let bounded = build_grammar("<S> ::= a | a a | a a a").unwrap();
+
+        let exhaustive = check_equivalence(&reference, &bounded, 3, 0, Rng::new(7));
+        let sampled = check_equivalence(&reference, &bounded, 3, 200, Rng::new(7));
+
+        assert!(matches!(exhaustive, Equivalence::Agree { .. }));
+        assert!(matches!(sampled, Equivalence::Differ(Difference { accepted_by_first: true, .. })));
+    }
+}
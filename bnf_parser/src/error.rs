@@ -0,0 +1,63 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::context_free_grammar::GrammarError;
+use crate::lexer::LexError;
+
+/// Exit code when the binary succeeds.
+pub const EXIT_SUCCESS: i32 = 0;
+/// Exit code when an input sentence or grammar is rejected.
+pub const EXIT_REJECTED: i32 = 1;
+/// Exit code when the command line arguments are invalid.
+pub const EXIT_USAGE: i32 = 2;
+/// Exit code when a grammar can't be built or fails its checks.
+pub const EXIT_GRAMMAR: i32 = 3;
+/// Exit code when a file can't be read or written.
+pub const EXIT_IO: i32 = 4;
+
+/// Error returned by the bnf_parser binary, each kind with its own exit code.
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Grammar(String),
+    Rejected(String),
+    Io(io::Error),
+}
+impl CliError {
+    /// Returns the process exit code for the error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Rejected(_) => EXIT_REJECTED,
+            CliError::Usage(_) => EXIT_USAGE,
+            CliError::Grammar(_) => EXIT_GRAMMAR,
+            CliError::Io(_) => EXIT_IO,
+        }
+    }
+}
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}", message),
+            CliError::Grammar(message) => write!(f, "Grammar error: {}", message),
+            CliError::Rejected(message) => write!(f, "{}", message),
+            CliError::Io(error) => write!(f, "I/O error: {}", error),
+        }
+    }
+}
+impl Error for CliError {}
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> Self {
+        CliError::Io(error)
+    }
+}
+impl From<GrammarError> for CliError {
+    fn from(error: GrammarError) -> Self {
+        CliError::Grammar(error.to_string())
+    }
+}
+impl From<LexError> for CliError {
+    fn from(error: LexError) -> Self {
+        CliError::Rejected(error.to_string())
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::context_free_grammar::{ContextFreeGrammar, ProductionRule};
+
+/// Small xorshift pseudo random number generator, good enough for picking productions.
+pub struct Rng {
+    state: u64,
+}
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // the state must never be zero
+        Rng { state: (seed ^ 0x9E37_79B9_7F4A_7C15) | 1 }
+    }
+
+    /// Seeds the generator from the system clock.
+    pub fn from_time() -> Rng {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Rng::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Returns a number in `0..bound`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// Generates random sentences of a grammar by expanding the leftmost variable with a random production.
+/// Every derivation is kept below a maximum tree depth, so generation always terminates.
+pub struct SentenceGenerator<'g> {
+    grammar: &'g ContextFreeGrammar,
+    min_depth: HashMap<&'g str, usize>,
+    rng: Rng,
+}
+impl<'g> SentenceGenerator<'g> {
+    pub fn new(grammar: &'g ContextFreeGrammar, rng: Rng) -> SentenceGenerator<'g> {
+        SentenceGenerator {
+            grammar,
+            min_depth: min_derivation_depths(grammar),
+            rng,
+        }
+    }
+
+    /// Generates a random sentence whose derivation tree is at most `max_depth` deep and which has
+    /// at most `max_len` terminals. Returns None if no such sentence was found on this attempt.
+    pub fn generate(&mut self, max_depth: usize, max_len: usize) -> Option<Vec<String>> {
+        let start_symbol = self.grammar.start_symbol();
+        let mut sentence = Vec::new();
+
+        if self.expand(start_symbol, max_depth, max_len, &mut sentence) {
+            Some(sentence)
+        } else {
+            None
+        }
+    }
+
+    /// Expands a symbol into the sentence, returning false if the length limit was exceeded
+    /// or the symbol can't derive anything within the remaining depth.
+    fn expand(&mut self, symbol: &str, depth: usize, max_len: usize, sentence: &mut Vec<String>) -> bool {
+        if !self.grammar.variables().contains(symbol) {
+            sentence.push(symbol.to_string());
+            return sentence.len() <= max_len;
+        }
+
+        let candidates: Vec<&ProductionRule> =
+            self.grammar.rules_for(symbol).filter(|rule| self.fits(rule, depth)).collect();
+
+        if candidates.is_empty() {
+            return false;
+        }
+
+        let rule = candidates[self.rng.below(candidates.len())];
+        rule.rhs
+            .iter()
+            .all(|symbol| self.expand(symbol, depth - 1, max_len, sentence))
+    }
+
+    /// Returns true if every variable on the rule's rhs can finish deriving within `depth - 1` levels.
+    fn fits(&self, rule: &ProductionRule, depth: usize) -> bool {
+        depth > 0
+            && rule.rhs.iter().all(|symbol| {
+                !self.grammar.variables().contains(symbol)
+                    || self.min_depth.get(symbol.as_str()).is_some_and(|&d| d < depth)
+            })
+    }
+}
+
+/// Computes the depth of the shallowest derivation tree of a terminal string for every generating variable.
+/// Variables that can't derive any terminal string are left out.
+pub fn min_derivation_depths(grammar: &ContextFreeGrammar) -> HashMap<&str, usize> {
+    let mut depths: HashMap<&str, usize> = HashMap::new();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for rule in grammar.production_rules() {
+            let mut depth = 1;
+            let mut generating = true;
+            for symbol in &rule.rhs {
+                if grammar.variables().contains(symbol) {
+                    match depths.get(symbol.as_str()) {
+                        Some(d) => depth = depth.max(d + 1),
+                        None => {
+                            generating = false;
+                            break;
+                        }
+                    }
+                }
+            }
+
+            if generating && depths.get(rule.lhs.as_str()).is_none_or(|&d| depth < d) {
+                depths.insert(rule.lhs.as_str(), depth);
+                changed = true;
+            }
+        }
+    }
+
+    depths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+    use crate::earley::recognizes;
+
+    #[test]
+    fn test_min_derivation_depths() {
+        let grammar = build_grammar("<S> ::= <A> <B> | <S> s\n<A> ::= a <A> | a\n<B> ::= <A> b\n<C> ::= <C> c").unwrap();
+        let depths = min_derivation_depths(&grammar);
+
+        assert_eq!(depths.get("<A>"), Some(&1));
+        assert_eq!(depths.get("<B>"), Some(&2));
+        assert_eq!(depths.get("<S>"), Some(&3));
+        assert_eq!(depths.get("<C>"), None);
+    }
+
+    #[test]
+    fn test_generated_sentences_are_in_language() {
+        let grammar = build_grammar("<E> ::= <E> + <T> | <T>\n<T> ::= ( <E> ) | id").unwrap();
+        let mut generator = SentenceGenerator::new(&grammar, Rng::new(327));
+
+        for _ in 0..50 {
+            if let Some(sentence) = generator.generate(8, 40) {
+                assert!(sentence.len() <= 40);
+                assert!(recognizes(&grammar, &sentence));
+            }
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::context_free_grammar::{
+    build_grammar, is_variable_name, split_action, split_alternatives, ContextFreeGrammar, GrammarError, Span,
+};
+
+/// Directive that includes the rules of another grammar file, as in `@include "expr.bnf"`, or with its
+/// variables renamed into a namespace to avoid clashes, as in `@include "expr.bnf" as expr`, which turns
+/// `<E>` into `<expr.E>`.
+pub const INCLUDE_DIRECTIVE: &str = "@include";
+
+/// Builds a grammar from BNF source that may include other files, with their paths relative to `dir`.
+///
+/// The included rules come after the source's own, so the start symbol is still the lhs of the first
+/// rule of the source if it has any. Only the source's own rules have spans. A file is only included
+/// once into each namespace, and a file that includes itself, directly or not, is an error.
+pub fn build_grammar_with_includes(source: &str, dir: &Path) -> Result<ContextFreeGrammar, GrammarError> {
+    let mut included = Vec::new();
+    let lines = expand(source, dir, &mut vec![], &mut included)?;
+
+    let main_lines = source.lines().count();
+    let expanded: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+    let mut grammar = build_grammar(&expanded.join("\n")).map_err(|error| {
+        match error.span.and_then(|span| lines.get(span.line - 1)) {
+            Some(SourceLine { origin: Some((file, line)), .. }) => {
+                GrammarError::new(&format!("{}, line {}: {}", file.display(), line, error.message), None)
+            }
+            _ => error,
+        }
+    })?;
+
+    if main_lines < lines.len() {
+        let rules = grammar.production_rules().iter().cloned().map(|mut rule| {
+            if rule.span.is_some_and(|span| span.line > main_lines) {
+                rule.span = None;
+            }
+            rule
+        });
+        grammar = grammar.with_rules(grammar.start_symbol(), rules.collect::<Vec<_>>());
+    }
+    Ok(grammar)
+}
+
+/// A line of expanded source, with the file and line it came from if it was included.
+struct SourceLine {
+    text: String,
+    origin: Option<(PathBuf, usize)>,
+}
+
+/// Expands the include directives of a source, returning its lines followed by those of the files it includes.
+/// `stack` holds the canonical and displayed paths of the files being included, to find cycles,
+/// and `included` each file and namespace already in.
+fn expand(
+    source: &str,
+    dir: &Path,
+    stack: &mut Vec<(PathBuf, PathBuf)>,
+    included: &mut Vec<(PathBuf, Option<String>)>,
+) -> Result<Vec<SourceLine>, GrammarError> {
+    let file = stack.last().map(|(_, path)| path.clone());
+    let mut lines = Vec::new();
+    let mut included_lines = Vec::new();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let origin = file.clone().map(|file| (file, line_index + 1));
+        let Some(directive) = line.trim().strip_prefix(INCLUDE_DIRECTIVE) else {
+            lines.push(SourceLine { text: line.to_string(), origin });
+            continue;
+        };
+        // the directive becomes a comment of the same length, so the spans of later rules are unchanged
+        lines.push(SourceLine { text: line.replacen('@', "#", 1), origin: origin.clone() });
+
+        let error = |message: String| match &origin {
+            Some((file, line)) => GrammarError::new(&format!("{}, line {}: {}", file.display(), line, message), None),
+            None => {
+                let start = line.as_ptr() as usize - source.as_ptr() as usize;
+                GrammarError::new(&message, Some(Span { line: line_index + 1, start, end: start + line.len() }))
+            }
+        };
+
+        let (path, namespace) = parse_include(directive).map_err(error)?;
+        let path = dir.join(path);
+        let canonical = fs::canonicalize(&path).map_err(|e| error(format!("can't read {}: {}", path.display(), e)))?;
+        if let Some(position) = stack.iter().position(|(file, _)| *file == canonical) {
+            let cycle: Vec<String> =
+                stack[position..].iter().map(|(_, file)| file).chain([&path]).map(|f| f.display().to_string()).collect();
+            return Err(error(format!("include cycle: {}", cycle.join(" -> "))));
+        }
+        if included.contains(&(canonical.clone(), namespace.clone())) {
+            continue;
+        }
+        included.push((canonical.clone(), namespace.clone()));
+
+        let contents = fs::read_to_string(&canonical).map_err(|e| error(format!("can't read {}: {}", path.display(), e)))?;
+        let parent = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        stack.push((canonical, path));
+        let mut file_lines = expand(&contents, &parent, stack, included)?;
+        stack.pop();
+
+        if let Some(namespace) = &namespace {
+            for line in &mut file_lines {
+                line.text = namespaced(&line.text, namespace);
+            }
+        }
+        included_lines.extend(file_lines);
+    }
+
+    lines.extend(included_lines);
+    Ok(lines)
+}
+
+/// Parses the part of an include directive after `@include`: a quoted path and an optional `as <namespace>`.
+fn parse_include(directive: &str) -> Result<(String, Option<String>), String> {
+    let usage = || format!("expected {} \"file.bnf\" or {} \"file.bnf\" as name", INCLUDE_DIRECTIVE, INCLUDE_DIRECTIVE);
+    let rest = directive.trim().strip_prefix('"').ok_or_else(usage)?;
+    let (path, rest) = rest.split_once('"').ok_or_else(usage)?;
+
+    match rest.split_whitespace().collect::<Vec<&str>>().as_slice() {
+        [] => Ok((path.to_string(), None)),
+        ["as", namespace] if namespace.chars().all(|c| c.is_alphanumeric() || c == '_') => {
+            Ok((path.to_string(), Some(namespace.to_string())))
+        }
+        _ => Err(usage()),
+    }
+}
+
+/// Renames the variables and token classes of a line of BNF from `<E>` to `<namespace.E>`, in place, leaving
+/// semantic actions and token patterns as they are.
+fn namespaced(line: &str, namespace: &str) -> String {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('#') || (trimmed.starts_with('@') && !trimmed.starts_with("@token")) {
+        return line.to_string();
+    }
+
+    if let Some(declaration) = trimmed.strip_prefix("@token") {
+        // only the name of a token class, not its pattern
+        let name_start = line.len() - declaration.trim_start().len();
+        let name_end = line[name_start..].find(char::is_whitespace).map_or(line.len(), |i| name_start + i);
+        let name = renamed_symbols(&line[name_start..name_end], namespace);
+        return format!("{}{}{}", &line[..name_start], name, &line[name_end..]);
+    }
+    let Some((lhs, rhs)) = line.split_once("::=") else {
+        return line.to_string();
+    };
+
+    let mut renamed = renamed_symbols(lhs, namespace) + "::=";
+    for (index, alternative) in split_alternatives(rhs).into_iter().enumerate() {
+        if index > 0 {
+            renamed.push('|');
+        }
+        // a misplaced action is an error later on, so that alternative is left alone
+        let (symbols, _) = split_action(alternative).unwrap_or(("", None));
+        renamed += &renamed_symbols(symbols, namespace);
+        renamed += &alternative[symbols.len()..];
+    }
+    renamed
+}
+
+/// Renames the variables among whitespace separated symbols, keeping the whitespace as it is.
+fn renamed_symbols(text: &str, namespace: &str) -> String {
+    text.split_inclusive(char::is_whitespace)
+        .map(|piece| {
+            let symbol = piece.trim_end_matches(char::is_whitespace);
+            if is_variable_name(symbol) {
+                format!("<{}.{}>{}", namespace, &symbol[1..symbol.len() - 1], &piece[symbol.len()..])
+            } else {
+                piece.to_string()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes files to a new temporary directory, returning its path.
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bnf_parser_include_{}_{}", name, std::process::id()));
+        for (path, contents) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = write_files(
+            "include",
+            &[
+                ("lib/expr.bnf", "@include \"term.bnf\"\n<E> ::= <E> + <T> | <T>"),
+                ("lib/term.bnf", "@token <num> = /[0-9]+/\n<T> ::= <num> | ( <E> )"),
+            ],
+        );
+        let source = "<S> ::= <E> ;\n@include \"lib/expr.bnf\"\n<S> ::= ε\n@include \"lib/term.bnf\"";
+        let grammar = build_grammar_with_includes(source, &dir).unwrap();
+
+        assert_eq!(grammar.start_symbol(), "<S>");
+        assert_eq!(grammar.to_bnf(), build_grammar(&grammar.to_bnf()).unwrap().to_bnf());
+        assert_eq!(grammar.production_rules().len(), 6);
+        assert!(grammar.token_classes().contains_key("<num>"));
+        let spans: Vec<Option<usize>> = grammar.production_rules().iter().map(|rule| rule.span.map(|s| s.line)).collect();
+        assert_eq!(spans, [Some(1), Some(3), None, None, None, None]);
+    }
+
+    #[test]
+    fn test_include_namespace() {
+        let dir = write_files("namespace", &[("list.bnf", "@token <x> = /x/\n<L> ::= <x> | <x> , <L>")]);
+        let source = "<S> ::= <a.L> | <b.L> ;\n<L> ::= ε\n@include \"list.bnf\" as a\n@include \"list.bnf\" as b";
+        let grammar = build_grammar_with_includes(source, &dir).unwrap();
+
+        assert!(grammar.is_variable("<a.L>") && grammar.is_variable("<b.L>") && grammar.is_variable("<L>"));
+        assert!(grammar.production_rules().iter().any(|rule| rule.lhs == "<b.L>" && rule.rhs == ["<b.x>", ",", "<b.L>"]));
+        assert!(grammar.token_classes().contains_key("<a.x>"));
+    }
+
+    #[test]
+    fn test_namespaced() {
+        assert_eq!(namespaced("<L>  ::= <x>\t, <L> { $$ = $1 }", "a"), "<a.L>  ::= <a.x>\t, <a.L> { $$ = $1 }");
+        assert_eq!(
+            namespaced("<E> ::= <T>  { $$ = \"<T>  | <E>\" } |<E> + <T>", "e"),
+            "<e.E> ::= <e.T>  { $$ = \"<T>  | <E>\" } |<e.E> + <e.T>"
+        );
+        assert_eq!(namespaced("  @token  <num> = /<num>  [0-9]+/", "n"), "  @token  <n.num> = /<num>  [0-9]+/");
+        assert_eq!(namespaced("# <E> ::= <T>", "e"), "# <E> ::= <T>");
+
+        let dir = write_files("namespaced", &[("num.bnf", "<N> ::= <D> { $$ = $1 } | <D> <N> { $$ = \"<N>\" }\n<D> ::= 0")]);
+        let grammar = build_grammar_with_includes("<S> ::= <n.N>\n@include \"num.bnf\" as n", &dir).unwrap();
+        let actions: Vec<_> = grammar.rules_for("<n.N>").map(|rule| rule.action.clone()).collect();
+        assert_eq!(actions, [Some("$$ = $1".to_string()), Some("$$ = \"<N>\"".to_string())]);
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let dir = write_files(
+            "errors",
+            &[("a.bnf", "<A> ::= a\n@include \"b.bnf\""), ("b.bnf", "@include \"a.bnf\""), ("bad.bnf", "\n<B> b")],
+        );
+
+        let error = build_grammar_with_includes("<S> ::= <A>\n@include \"a.bnf\"", &dir).unwrap_err();
+        let (a, b) = (dir.join("a.bnf").display().to_string(), dir.join("b.bnf").display().to_string());
+        assert_eq!(error.message, format!("{}, line 1: include cycle: {} -> {} -> {}", b, a, b, a));
+
+        let error = build_grammar_with_includes("<S> ::= <B>\n@include \"bad.bnf\"", &dir).unwrap_err();
+        assert!(error.to_string().ends_with("bad.bnf, line 2: expected '::=' after the rule's left hand side"), "{}", error);
+
+        let error = build_grammar_with_includes("<S> ::= s\n@include \"missing.bnf\"", &dir).unwrap_err();
+        assert_eq!(error.span.map(|span| span.line), Some(2));
+        assert!(error.message.starts_with("can't read"));
+        assert_eq!(
+            build_grammar_with_includes("@include expr.bnf", &dir).unwrap_err().message,
+            "expected @include \"file.bnf\" or @include \"file.bnf\" as name"
+        );
+    }
+}
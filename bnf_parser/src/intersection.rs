@@ -0,0 +1,149 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::automaton::Dfa;
+use crate::context_free_grammar::{ContextFreeGrammar, ProductionRule};
+use crate::normal_form::{reduce, FreshNames};
+
+/// Intersects the language of a grammar with that of a DFA with the Bar-Hillel product construction.
+///
+/// Each variable `<A>` of the result stands for a triple `<A_p_q>`: the strings `<A>` derives that
+/// take the DFA from state p to state q. `step` gives the state a terminal takes the DFA to from a
+/// state, which lets a terminal stand for one symbol of the DFA or for several. Only triples that
+/// derive some string are built, and the result is reduced, so an empty intersection has no rules.
+pub fn intersect(
+    grammar: &ContextFreeGrammar,
+    dfa: &Dfa,
+    step: impl Fn(usize, &str) -> Option<usize>,
+) -> ContextFreeGrammar {
+    let states = dfa.states.len();
+
+    // the states each variable can take the DFA to from each state, grown until nothing changes
+    let mut ends: HashMap<(&str, usize), BTreeSet<usize>> = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for rule in grammar.production_rules() {
+            for start in 0..states {
+                let mut current = BTreeSet::from([start]);
+                for symbol in &rule.rhs {
+                    current = current.iter().flat_map(|&state| next_states(grammar, &ends, &step, state, symbol)).collect();
+                }
+                let lhs_ends = ends.entry((rule.lhs.as_str(), start)).or_default();
+                for end in current {
+                    changed |= lhs_ends.insert(end);
+                }
+            }
+        }
+    }
+
+    let base = |variable: &str| variable.trim_start_matches('<').trim_end_matches('>').to_string();
+    let mut names = FreshNames::new(grammar);
+    let start_symbol = names.fresh(&base(grammar.start_symbol()));
+    let mut triples: HashMap<(&str, usize, usize), String> = HashMap::new();
+    let mut name = |variable: &str, from: usize, to: usize| -> String {
+        let variable = grammar.variables().get(variable).expect("only variables get triples").as_str();
+        triples
+            .entry((variable, from, to))
+            .or_insert_with(|| names.fresh(&format!("{}_{}_{}", base(variable), dfa.states[from], dfa.states[to])))
+            .clone()
+    };
+
+    let mut rules = Vec::new();
+    for &end in ends.get(&(grammar.start_symbol(), dfa.start)).into_iter().flatten() {
+        if dfa.accepting[end] {
+            rules.push(ProductionRule::new(&start_symbol, &[&name(grammar.start_symbol(), dfa.start, end)]));
+        }
+    }
+
+    for rule in grammar.production_rules() {
+        for start in 0..states {
+            // every way through the rhs, as the symbols of the new rule and the state it ends at
+            let mut paths: Vec<(Vec<String>, usize)> = vec![(Vec::new(), start)];
+            for symbol in &rule.rhs {
+                let mut next_paths = Vec::new();
+                for (rhs, state) in &paths {
+                    for next in next_states(grammar, &ends, &step, *state, symbol) {
+                        let symbol = if grammar.is_variable(symbol) { name(symbol, *state, next) } else { symbol.clone() };
+                        let mut rhs = rhs.clone();
+                        rhs.push(symbol);
+                        next_paths.push((rhs, next));
+                    }
+                }
+                paths = next_paths;
+            }
+
+            for (rhs, end) in paths {
+                let lhs = name(&rule.lhs, start, end);
+                rules.push(ProductionRule { lhs, rhs, span: None, action: None });
+            }
+        }
+    }
+
+    reduce(&grammar.with_rules(&start_symbol, rules))
+}
+
+/// Returns the states a symbol can take the DFA to from a state, given the ends found so far for variables.
+fn next_states(
+    grammar: &ContextFreeGrammar,
+    ends: &HashMap<(&str, usize), BTreeSet<usize>>,
+    step: &impl Fn(usize, &str) -> Option<usize>,
+    state: usize,
+    symbol: &str,
+) -> BTreeSet<usize> {
+    if grammar.is_variable(symbol) {
+        ends.get(&(symbol, state)).cloned().unwrap_or_default()
+    } else {
+        step(state, symbol).into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::Nfa;
+    use crate::context_free_grammar::build_grammar;
+    use crate::earley;
+    use crate::language::{self, LanguageSize};
+    use crate::regex::Regex;
+
+    const EVEN_LENGTH: &str = "start even\naccept even\neven a -> odd\neven b -> odd\nodd a -> even\nodd b -> even\n";
+
+    fn sentence(words: &str) -> Vec<String> {
+        words.split_whitespace().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_intersect_with_dfa_file() {
+        let grammar = build_grammar("<S> ::= a <S> b | <S> <S> | ε").unwrap();
+        let dfa = Dfa::parse(EVEN_LENGTH).unwrap();
+        let intersection = intersect(&grammar, &dfa, |state, terminal| dfa.step(state, terminal));
+
+        for words in ["", "a b", "a a b b", "a b a b", "a a b", "b a"] {
+            let words = sentence(words);
+            let expected = earley::recognizes(&grammar, &words) && words.len().is_multiple_of(2);
+            assert_eq!(earley::recognizes(&intersection, &words), expected, "{:?}", words);
+        }
+
+        // every sentence with balanced a's and b's has even length
+        let odd = Dfa::parse(&EVEN_LENGTH.replace("accept even", "accept odd")).unwrap();
+        let intersection = intersect(&grammar, &odd, |state, terminal| odd.step(state, terminal));
+        assert_eq!(language::language_size(&intersection, 10), LanguageSize::Empty);
+    }
+
+    #[test]
+    fn test_intersect_with_regex() {
+        let grammar = build_grammar("<E> ::= <E> + <E> | x | ( <E> )").unwrap();
+        let regex = Regex::parse(r"\(*x\)*(\+x)?").unwrap();
+        let dfa = Nfa::from_regex_over(&regex, &['x', '+', '(', ')']).to_dfa().minimize();
+        let intersection = intersect(&grammar, &dfa, |state, terminal| dfa.step(state, terminal));
+
+        assert!(earley::recognizes(&intersection, &sentence("( ( x ) ) + x")));
+        assert!(!earley::recognizes(&intersection, &sentence("x + x + x")));
+        assert!(!earley::recognizes(&intersection, &sentence("( x ) )")));
+        assert_eq!(language::language_size(&intersection, 10), LanguageSize::Infinite);
+
+        let dfa = Nfa::from_regex_over(&Regex::parse("x.x").unwrap(), &['x', '+', '(', ')']).to_dfa().minimize();
+        let intersection = intersect(&grammar, &dfa, |state, terminal| dfa.step(state, terminal));
+        assert_eq!(language::finite_language(&intersection, 10), Some(vec![sentence("x + x")]));
+    }
+}
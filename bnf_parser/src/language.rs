@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::context_free_grammar::ContextFreeGrammar;
+use crate::normal_form::{generating_variables, reduce};
+
+/// Whether the language of a grammar is empty, finite or infinite.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LanguageSize {
+    Empty,
+    /// The language is finite, with every string in it if there are at most the requested number,
+    /// sorted by length and then alphabetically.
+    Finite(Option<Vec<Vec<String>>>),
+    Infinite,
+}
+
+/// Returns true if the grammar doesn't derive any string of terminals.
+pub fn is_empty(grammar: &ContextFreeGrammar) -> bool {
+    !generating_variables(grammar).contains(grammar.start_symbol())
+}
+
+/// Returns true if the grammar derives finitely many strings.
+///
+/// In the reduced grammar every variable is used in some derivation, so the language is infinite
+/// exactly when a variable can derive itself along with a non-empty string of terminals. That is
+/// when a cycle of the dependency graph, with an edge from each lhs to the variables on its rhs,
+/// takes an edge whose rule also has a symbol that can derive a non-empty string.
+pub fn is_finite(grammar: &ContextFreeGrammar) -> bool {
+    let reduced = reduce(grammar);
+    let non_empty = non_empty_variables(&reduced);
+    let grows = |symbol: &str| !reduced.is_variable(symbol) || non_empty.contains(symbol);
+
+    // edges from each variable to the variables on its right hand sides
+    let mut edges: IndexMap<&str, Vec<&str>> = IndexMap::new();
+    let mut growing_edges = Vec::new();
+    for rule in reduced.production_rules() {
+        for (index, symbol) in rule.rhs.iter().enumerate() {
+            if !reduced.is_variable(symbol) {
+                continue;
+            }
+            edges.entry(rule.lhs.as_str()).or_default().push(symbol.as_str());
+            let others = rule.rhs.iter().enumerate().filter(|(other, _)| *other != index);
+            if others.map(|(_, symbol)| symbol).any(|symbol| grows(symbol)) {
+                growing_edges.push((rule.lhs.as_str(), symbol.as_str()));
+            }
+        }
+    }
+
+    // a growing edge is on a cycle if its lhs can be reached back from its target
+    !growing_edges.into_iter().any(|(from, to)| {
+        let mut seen: HashSet<&str> = HashSet::from([to]);
+        let mut worklist = vec![to];
+        while let Some(variable) = worklist.pop() {
+            if variable == from {
+                return true;
+            }
+            for &next in edges.get(variable).into_iter().flatten() {
+                if seen.insert(next) {
+                    worklist.push(next);
+                }
+            }
+        }
+        false
+    })
+}
+
+/// Returns the variables of a reduced grammar that can derive some non-empty string of terminals.
+fn non_empty_variables(grammar: &ContextFreeGrammar) -> HashSet<&str> {
+    let mut non_empty: HashSet<&str> = HashSet::new();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for rule in grammar.production_rules() {
+            if !non_empty.contains(rule.lhs.as_str())
+                && rule.rhs.iter().any(|symbol| !grammar.is_variable(symbol) || non_empty.contains(symbol.as_str()))
+            {
+                non_empty.insert(rule.lhs.as_str());
+                changed = true;
+            }
+        }
+    }
+
+    non_empty
+}
+
+/// Returns every string of a finite language, sorted by length and then alphabetically,
+/// or None if there are more than `limit` of them. The grammar's language must be finite.
+pub fn finite_language(grammar: &ContextFreeGrammar, limit: usize) -> Option<Vec<Vec<String>>> {
+    let reduced = reduce(grammar);
+
+    // the strings each variable derives, grown until nothing changes, which ends because they are finite
+    let mut languages: IndexMap<&str, IndexSet<Vec<String>>> =
+        reduced.variables().iter().map(|variable| (variable.as_str(), IndexSet::new())).collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for rule in reduced.production_rules() {
+            let mut strings: IndexSet<Vec<String>> = IndexSet::from([Vec::new()]);
+            for symbol in &rule.rhs {
+                let symbol_strings = match languages.get(symbol.as_str()) {
+                    Some(language) => language.clone(),
+                    None => IndexSet::from([vec![symbol.clone()]]),
+                };
+                strings = strings
+                    .iter()
+                    .flat_map(|prefix| {
+                        symbol_strings.iter().map(move |suffix| [prefix.as_slice(), suffix.as_slice()].concat())
+                    })
+                    .collect();
+            }
+
+            let language = languages.get_mut(rule.lhs.as_str()).expect("every lhs is a variable");
+            for string in strings {
+                changed |= language.insert(string);
+            }
+            if language.len() > limit {
+                return None;
+            }
+        }
+    }
+
+    let mut strings: Vec<Vec<String>> =
+        languages.swap_remove(reduced.start_symbol()).unwrap_or_default().into_iter().collect();
+    strings.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    Some(strings)
+}
+
+/// Decides whether the language of a grammar is empty, finite or infinite, listing the strings
+/// of a finite language if there are at most `limit` of them.
+pub fn language_size(grammar: &ContextFreeGrammar, limit: usize) -> LanguageSize {
+    if is_empty(grammar) {
+        LanguageSize::Empty
+    } else if is_finite(grammar) {
+        LanguageSize::Finite(finite_language(grammar, limit))
+    } else {
+        LanguageSize::Infinite
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+
+    fn size(bnf: &str) -> LanguageSize {
+        language_size(&build_grammar(bnf).unwrap(), 100)
+    }
+
+    fn strings(sentences: &[&str]) -> Vec<Vec<String>> {
+        sentences.iter().map(|sentence| sentence.split_whitespace().map(|s| s.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(size("<S> ::= a <S>"), LanguageSize::Empty);
+        assert_eq!(size("<S> ::= <A> | <B>\n<A> ::= a <A>\n<B> ::= <B> b"), LanguageSize::Empty);
+        assert_eq!(size("<S> ::= ε"), LanguageSize::Finite(Some(strings(&[""]))));
+    }
+
+    #[test]
+    fn test_finite() {
+        // unit cycles and nullable companions don't make a language infinite
+        let bnf = "<S> ::= <A> <B> | <S>\n<A> ::= a | b | <A> <N>\n<B> ::= c | ε\n<N> ::= ε\n<U> ::= <U> x";
+        assert_eq!(size(bnf), LanguageSize::Finite(Some(strings(&["a", "b", "a c", "b c"]))));
+
+        let grammar = build_grammar("<S> ::= <X> <X> <X>\n<X> ::= 0 | 1").unwrap();
+        assert!(is_finite(&grammar));
+        assert_eq!(finite_language(&grammar, 8).map(|strings| strings.len()), Some(8));
+        assert_eq!(finite_language(&grammar, 7), None);
+    }
+
+    #[test]
+    fn test_infinite() {
+        assert_eq!(size("<S> ::= a <S> | b"), LanguageSize::Infinite);
+        assert_eq!(size("<S> ::= <A> <B>\n<A> ::= <B> <A> | a\n<B> ::= b | ε"), LanguageSize::Infinite);
+        // the cycle is only reachable through a useless rule
+        assert!(is_finite(&build_grammar("<S> ::= a | <A> <D>\n<A> ::= a <A> | a\n<D> ::= <D> d").unwrap()));
+    }
+}
@@ -0,0 +1,194 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::context_free_grammar::{ContextFreeGrammar, Span};
+use crate::regex::{Matcher, Regex};
+
+/// A terminal read from the input, with the text it matched and where it was found.
+/// The text differs from the terminal only for token classes, e.g. `42` read as `<number>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub terminal: String,
+    pub text: String,
+    pub span: Span,
+}
+
+/// Error for input that no terminal or token class matches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub message: String,
+    pub span: Span,
+}
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.span.line, self.message)
+    }
+}
+impl Error for LexError {}
+
+/// Splits an input into tokens of a grammar. Grammars without token classes have their input split
+/// into whitespace separated words, otherwise the input is tokenized by longest match with a `Lexer`.
+pub fn tokenize(grammar: &ContextFreeGrammar, input: &str) -> Result<Vec<Token>, LexError> {
+    if grammar.token_classes().is_empty() {
+        Ok(split_words(input))
+    } else {
+        Lexer::new(grammar).tokenize(input)
+    }
+}
+
+/// Longest-match lexer for the terminals and token classes of a grammar.
+/// When several match the same longest prefix, a terminal wins over a token class, so keywords
+/// can be carved out of identifiers, and earlier token classes win over later ones.
+pub struct Lexer {
+    literals: Vec<String>,
+    classes: Vec<(String, Matcher)>,
+}
+impl Lexer {
+    pub fn new(grammar: &ContextFreeGrammar) -> Lexer {
+        let classes = grammar
+            .token_classes()
+            .iter()
+            .map(|(name, pattern)| {
+                let regex = Regex::parse(pattern).expect("token class patterns are checked when the grammar is built");
+                (name.clone(), Matcher::new(&regex))
+            })
+            .collect();
+        let literals = grammar
+            .terminals()
+            .iter()
+            .filter(|terminal| !grammar.token_classes().contains_key(*terminal))
+            .cloned()
+            .collect();
+
+        Lexer { literals, classes }
+    }
+
+    /// Splits the input into tokens, skipping whitespace between them.
+    pub fn tokenize(&self, input: &str) -> Result<Vec<Token>, LexError> {
+        let mut tokens = Vec::new();
+        let mut line = 1;
+        let mut position = 0;
+
+        while position < input.len() {
+            let rest = &input[position..];
+            let c = rest.chars().next().expect("position is inside the input");
+            if c.is_whitespace() {
+                if c == '\n' {
+                    line += 1;
+                }
+                position += c.len_utf8();
+                continue;
+            }
+
+            let Some((terminal, length)) = self.longest_match(rest) else {
+                return Err(LexError {
+                    message: format!("no terminal or token class matches the input at '{}'", c),
+                    span: Span { line, start: position, end: position + c.len_utf8() },
+                });
+            };
+
+            let text = &rest[..length];
+            tokens.push(Token {
+                terminal: terminal.to_string(),
+                text: text.to_string(),
+                span: Span { line, start: position, end: position + length },
+            });
+            line += text.matches('\n').count();
+            position += length;
+        }
+
+        Ok(tokens)
+    }
+
+    /// Returns the terminal matching the longest non-empty prefix of the input, and the prefix's length.
+    fn longest_match(&self, input: &str) -> Option<(&str, usize)> {
+        let literals = self
+            .literals
+            .iter()
+            .filter(|literal| input.starts_with(literal.as_str()))
+            .map(|literal| (literal.as_str(), literal.len()));
+        let classes = self
+            .classes
+            .iter()
+            .filter_map(|(name, matcher)| matcher.longest_match(input).map(|length| (name.as_str(), length)));
+
+        // only a strictly longer match replaces an earlier one, so ties go to the earlier candidate
+        let mut longest: Option<(&str, usize)> = None;
+        for (terminal, length) in literals.chain(classes) {
+            if length > longest.map_or(0, |(_, longest_length)| longest_length) {
+                longest = Some((terminal, length));
+            }
+        }
+        longest
+    }
+}
+
+/// Splits an input into whitespace separated words, each of which is matched against the terminals.
+pub fn split_words(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+
+    let mut line_start = 0;
+    for (line_index, line) in input.split_inclusive('\n').enumerate() {
+        let mut word_start = None;
+        for (offset, c) in line.char_indices().chain([(line.len(), ' ')]) {
+            match (word_start, c.is_whitespace()) {
+                (None, false) => word_start = Some(offset),
+                (Some(start), true) => {
+                    tokens.push(Token {
+                        terminal: line[start..offset].to_string(),
+                        text: line[start..offset].to_string(),
+                        span: Span { line: line_index + 1, start: line_start + start, end: line_start + offset },
+                    });
+                    word_start = None;
+                }
+                _ => {}
+            }
+        }
+        line_start += line.len();
+    }
+
+    tokens
+}
+
+/// Returns the terminals of a sequence of tokens.
+pub fn terminals(tokens: &[Token]) -> Vec<String> {
+    tokens.iter().map(|token| token.terminal.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+
+    #[test]
+    fn test_split_words() {
+        let input = "id +  id\n  * id";
+        let tokens = split_words(input);
+
+        assert_eq!(terminals(&tokens), vec!["id", "+", "id", "*", "id"]);
+        assert_eq!(tokens[2].span, Span { line: 1, start: 6, end: 8 });
+        assert_eq!(tokens[3].span, Span { line: 2, start: 11, end: 12 });
+        assert_eq!(&input[tokens[4].span.start..tokens[4].span.end], "id");
+    }
+
+    #[test]
+    fn test_longest_match_tokens() {
+        let grammar = build_grammar(
+            "@token <number> = /[0-9]+(\\.[0-9]+)?/
+             @token <id> = /[a-z][a-z0-9]*/
+             <S> ::= let <id> = <E>
+             <E> ::= <E> + <number> | <number> | <id>",
+        )
+        .unwrap();
+
+        let tokens = tokenize(&grammar, "let x1=3.5+\n  letter").unwrap();
+
+        assert_eq!(terminals(&tokens), vec!["let", "<id>", "=", "<number>", "+", "<id>"]);
+        assert_eq!(tokens[3].text, "3.5");
+        assert_eq!(tokens[5].text, "letter");
+        assert_eq!(tokens[5].span, Span { line: 2, start: 14, end: 20 });
+
+        let error = tokenize(&grammar, "let x = 3 ; 4").unwrap_err();
+        assert_eq!(error.span, Span { line: 1, start: 10, end: 11 });
+    }
+}
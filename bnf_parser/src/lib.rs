@@ -1,40 +1,944 @@
-use std::error::Error;
-use std::fs;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 
-mod context_free_grammar;
-use context_free_grammar as cfg;
+use crate::lr::LrKind;
 
-/// Configuration struct for the bnf_lexer binary.
+mod ambiguity;
+mod automaton;
+mod classify;
+pub mod codegen;
+mod commands;
+pub mod context_free_grammar;
+mod derivation;
+mod diff;
+pub mod earley;
+mod equivalence;
+mod error;
+mod generator;
+pub mod include;
+mod intersection;
+mod language;
+mod lexer;
+pub mod lint;
+pub mod ll;
+pub mod lr;
+mod normal_form;
+mod parse_tree;
+mod pda;
+mod recovery;
+mod regex;
+mod repl;
+pub mod semantics;
+pub mod sets;
+pub use context_free_grammar::{ContextFreeGrammar, GrammarBuilder, GrammarError, ProductionRule, SymbolKind};
+pub use lint::LintCode;
+pub use error::{CliError, EXIT_GRAMMAR, EXIT_IO, EXIT_REJECTED, EXIT_SUCCESS, EXIT_USAGE};
+
+const HELP: &str = "bnf_parser - tools for context free grammars written in BNF
+
+Usage: bnf_parser <command> [options]
+
+Commands:
+    show        Print a grammar
+    check       Report problems with a grammar
+    lint        Warn about likely mistakes in a grammar
+    normalize   Convert a grammar to a normal form
+    sets        Print the nullable, FIRST and FOLLOW sets of a grammar's variables
+    parse       Parse a sentence and print its parse tree
+    derive      Print the leftmost or rightmost derivation of a sentence
+    eval        Evaluate the semantic actions of a grammar on a sentence
+    generate    Print random sentences of a grammar
+    convert     Convert a grammar between BNF, JSON and regular expressions
+    equiv       Compare the languages of two grammars
+    diff        Compare the rules and derived properties of two versions of a grammar
+    ambiguity   Search a grammar for an ambiguous sentence
+    automaton   Convert a regular grammar to a finite automaton
+    intersect   Intersect a grammar with a regular expression or a DFA
+    pda         Convert a grammar to a pushdown automaton and run it on a sentence
+    ll          Find the smallest k for which a grammar is LL(k)
+    classify    Report the most specific class of grammars a grammar is in
+    codegen     Generate the Rust source of a recursive-descent parser for an LL(1) grammar
+    repl        Edit and explore a grammar interactively
+    help        Print help for a command
+
+Grammar files ending in .json are read as JSON, anything else as BNF. Use - to read from stdin.
+A BNF grammar can include the rules of other files with `@include \"expr.bnf\"`, relative to its
+own directory, or with `@include \"expr.bnf\" as expr` to rename their variables to `<expr.E>`.
+Run `bnf_parser <command> --help` for the options of a command.
+
+Exit codes: 0 success, 1 input rejected, 2 usage error, 3 grammar error, 4 I/O error";
+
+const SHOW_HELP: &str = "Usage: bnf_parser show <grammar> [--output text|bnf|json]
+
+Prints a grammar. `text` lists its variables, terminals, start symbol and rules,
+`bnf` prints it as canonical BNF and `json` as JSON.";
+
+const CHECK_HELP: &str = "Usage: bnf_parser check <grammar>
+
+Prints counts of a grammar's variables, terminals and rules and whether its language is empty,
+finite (listing every string if there are at most 50) or infinite. Reports variables that are
+used but never defined (errors), and variables that can't derive any string or can't be reached
+from the start symbol (warnings).
+Exits with code 3 if there are errors.";
+
+const LINT_HELP: &str = "Usage: bnf_parser lint <grammar> [--allow <codes>] [--deny <codes>]
+
+Warns about likely mistakes in a grammar, each with a code and the line it is on:
+    L001 undefined-variable     a variable is used but has no rules
+    L002 unused-variable        a variable's rules are never used by another variable
+    L003 duplicate-alternative  the same alternative is written twice
+    L004 misspelled-variable    a terminal looks like a variable, like `<NP` or `NP`
+    L005 unreachable-rule       a rule can't be reached from the start symbol
+    L006 left-recursion         a variable derives a string starting with itself
+
+L004 only looks at the right hand sides of rules: a left hand side with a missing bracket,
+like `<NP ::= ...`, is an error when the grammar is read, so the grammar isn't linted at all.
+
+--allow and --deny take comma separated codes or names. Allowed lints aren't reported and
+denied lints are reported as errors. A `# bnf-lint: allow L001, L006` comment allows lints
+on the next rule line.
+Exits with code 3 if there are errors.";
+
+const NORMALIZE_HELP: &str = "Usage: bnf_parser normalize <grammar> [--form cnf|reduced] [--output text|bnf|json]
+
+Converts a grammar to Chomsky normal form (cnf, the default) or removes its useless rules (reduced).";
+
+const SETS_HELP: &str = "Usage: bnf_parser sets <grammar> [--variable <V>] [--k N]
+
+Prints whether each variable is nullable along with its FIRST and FOLLOW sets.
+With --k greater than 1, prints the FIRST_k and FOLLOW_k sets instead: the strings of up to k
+terminals that can begin and follow each variable, with ε for the empty string and `$` for
+the end of the input.";
+
+const PARSE_HELP: &str = "Usage: bnf_parser parse <grammar> [--parser earley|ll1|slr|lalr|lr1] [--tree ascii|dot|json] [--trees N]
+                        [--input <file>] [<sentence>...]
+
+Parses a sentence of whitespace separated terminals, given either as arguments or read
+from a file with --input (- for stdin), and prints up to N of its parse trees (default 1).
+If the grammar declares token classes like `@token <number> = /[0-9]+/`, the sentence is
+instead split into the longest matching terminals and token classes.
+Exits with code 1 if the sentence is not in the language.
+
+The default Earley parser handles any grammar. --parser ll1 uses a predictive parser and
+slr, lalr or lr1 a shift-reduce parser, for grammars of that class. These recover from
+syntax errors: they print a tree of the whole sentence with error nodes where the errors
+are, and then every error with its position and the terminals expected there.";
+
+const DERIVE_HELP: &str = "Usage: bnf_parser derive <grammar> [--rightmost] [--color] <sentence>...
+
+Prints the leftmost (or rightmost) derivation of a sentence, one sentential form per line,
+marking the variable expanded at each step and the production used on it.
+Exits with code 1 if the sentence is not in the language.";
+
+const EVAL_HELP: &str = "Usage: bnf_parser eval <grammar> [--attribute NAME] <sentence>...
+
+Parses a sentence and evaluates the semantic actions of the grammar's rules over its parse tree,
+printing the value of the start symbol (or of its attribute NAME). An action ends an alternative,
+as in `<E> ::= <E> + <T> { $$ = $1 + $3 }`, and assigns expressions separated by `;`:
+`$$.name = ...` sets an attribute of the rule's lhs and `$2.name = ...` an inherited attribute
+of the second symbol of its rhs. `$$` and `$2` alone stand for the attribute `value`, and the
+value of a terminal is its text, or the number it spells. Expressions can use numbers, \"strings\",
+true and false, + - * / %, comparisons, && || !, parentheses and `condition ? a : b`.
+A rule without an action passes up the value of its first symbol.
+Exits with code 1 if the sentence is not in the language and 3 if an action fails.";
+
+const GENERATE_HELP: &str = "Usage: bnf_parser generate <grammar> [--count N] [--max-depth N] [--max-len N] [--seed N]
+
+Prints N random sentences of a grammar (default 10) whose derivation trees are at most
+--max-depth deep (default 12) and which have at most --max-len terminals (default 30).";
+
+const CONVERT_HELP: &str = "Usage: bnf_parser convert <grammar> --to bnf|json|regex [--from bnf|json|regex]
+
+Converts a grammar between BNF and JSON. The input format is guessed from the file
+extension unless --from is given.
+
+With --from regex the argument is a regular expression instead of a file (- reads it from
+stdin), and is converted to a right-linear grammar with one variable per state of its
+minimal DFA and every character as a terminal. `.` and negated classes can't be converted.
+With --to regex a right- or left-linear grammar is converted to a regular expression by
+state elimination, where terminals stand for their text and token classes for their pattern.
+For example: bnf_parser convert 'a(b|c)*' --from regex --to bnf";
+
+const EQUIV_HELP: &str = "Usage: bnf_parser equiv <grammar-a> <grammar-b> [--max-len N] [--samples N] [--seed N]
+
+Compares the languages of two grammars on every string up to --max-len terminals (default 6)
+and on --samples random longer sentences of each (default 100), printing a shortest
+distinguishing string if they differ. Exits with code 1 if the languages differ.";
+
+const DIFF_HELP: &str = "Usage: bnf_parser diff <old-grammar> <new-grammar>
+
+Compares two versions of a grammar: the symbols that look renamed, the rules added and removed
+or with a changed semantic action for each variable, and changes in derived properties: whether
+the grammar is LL(1), whether its language is empty, finite or infinite, and the nullable, FIRST
+and FOLLOW sets of each variable.
+A symbol is taken as renamed when renaming it makes more rules of the two grammars the same.
+Exits with code 1 if the grammars differ.";
+
+const AMBIGUITY_HELP: &str = "Usage: bnf_parser ambiguity <grammar> [--max-len N]
+
+Searches every sentence of up to --max-len terminals (default 6) for one with two
+distinct parse trees, and prints both trees side by side.";
+
+const LL_HELP: &str = "Usage: bnf_parser ll <grammar> [--max-k N]
+
+Finds the smallest k up to --max-k (default 3) for which the grammar's LL(k) table, built
+from its FIRST_k and FOLLOW_k sets, has no conflicts, and prints the table. This is strong
+LL(k), which is the same as LL(k) for k = 1.
+Exits with code 1 and prints the remaining conflicts if there is no such k.";
+
+const CODEGEN_HELP: &str = "Usage: bnf_parser codegen <grammar>
+
+Prints the source of a standalone Rust module with a recursive-descent parser for an LL(1)
+grammar: a `Terminal` enum, a `tokenize` function splitting input like `parse` does, a node
+type for each variable with a variant per rule, and `parse`, which returns the tree of the
+start symbol or a `ParseError` with the terminals it expected. Semantic actions are ignored.
+The library function `bnf_parser::codegen::generate_parser` does the same from a build script.
+Exits with code 3 if the grammar isn't LL(1).";
+
+const CLASSIFY_HELP: &str = "Usage: bnf_parser classify <grammar> [--max-len N]
+
+Checks whether a grammar is regular (right- or left-linear), LL(1), SLR(1), LALR(1), LR(1),
+unambiguous and context-free, printing the evidence for each answer: the rule that isn't linear,
+the conflicting table entries, or an ambiguous sentence. Ambiguity is only searched for up to
+--max-len terminals (default 6), and not at all for an LR(1) grammar, which can't be ambiguous.
+Finding no ambiguous sentence doesn't show there is none, so then unambiguous is unknown and
+the grammar is only unambiguous up to --max-len.
+Ends with the most specific class the grammar is known to be in.";
+
+const AUTOMATON_HELP: &str = "Usage: bnf_parser automaton <grammar> [--stage nfa|dfa|minimal] [--output table|dot]
+
+Converts a right- or left-linear grammar to an NFA, then to a DFA with the subset construction,
+then to a minimal DFA with Hopcroft's algorithm, and prints the automaton of the chosen stage
+(default minimal) as a transition table or a Graphviz dot graph. In the table `->` marks the
+start state, `*` accepting states and `-` a missing transition.
+Exits with code 3 if the grammar is not right- or left-linear.";
+
+const INTERSECT_HELP: &str = "Usage: bnf_parser intersect <grammar> (--regex <pattern> | --dfa <file>) [--output text|bnf|json]
+
+Prints a grammar for the sentences of a grammar that a regular expression matches or a DFA accepts,
+built with the Bar-Hillel product construction. A regex is matched against the text of the
+terminals written one after the other, so `.` and classes stand for single characters of them.
+A DFA file has a `start <state>` line, an `accept <states>` line and transitions like
+`q0 a -> q1` whose symbols are terminals of the grammar.
+Exits with code 1 if the intersection is empty.";
+
+const PDA_HELP: &str = "Usage: bnf_parser pda <grammar-or-pda> [--max-steps N] [<sentence>...]
+
+Converts a grammar to a single-state pushdown automaton that accepts by empty stack, or reads
+a hand-written PDA from a file ending in .pda, and prints it. Given a sentence (ε for the empty
+one), runs the PDA on it instead and prints the configurations (state, remaining input, stack)
+of a shortest accepting run, exploring at most --max-steps configurations (default 10000).
+A PDA file has a `start <state>` line, an optional `stack <symbols>` line (top first),
+`accept <states>` or `accept empty`, and transitions like `q0 a Z -> q0 A Z` with ε for nothing.
+Exits with code 1 if no accepting run is found.";
+
+const REPL_HELP: &str = "Usage: bnf_parser repl [<grammar>]
+
+Starts an interactive session on a grammar, empty unless a grammar file is given.
+Rules typed in are added to the grammar, and commands like `:first <E>`, `:parse id + id`,
+`:cnf` and `:generate 5` run against it. Type :help in the session for every command.";
+
+/// Format a grammar is printed in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Bnf,
+    Json,
+}
+impl FromStr for OutputFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "bnf" => Ok(OutputFormat::Bnf),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Format a grammar file is written in. A regex stands for its right-linear grammar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GrammarFormat {
+    Bnf,
+    Json,
+    Regex,
+}
+impl FromStr for GrammarFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bnf" => Ok(GrammarFormat::Bnf),
+            "json" => Ok(GrammarFormat::Json),
+            "regex" => Ok(GrammarFormat::Regex),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Format parse trees are printed in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TreeFormat {
+    Ascii,
+    Dot,
+    Json,
+}
+impl FromStr for TreeFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ascii" => Ok(TreeFormat::Ascii),
+            "dot" => Ok(TreeFormat::Dot),
+            "json" => Ok(TreeFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Parser the parse command uses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParserKind {
+    Earley,
+    Ll1,
+    Lr(LrKind),
+}
+impl FromStr for ParserKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "earley" => Ok(ParserKind::Earley),
+            "ll1" => Ok(ParserKind::Ll1),
+            "slr" => Ok(ParserKind::Lr(LrKind::Slr)),
+            "lalr" => Ok(ParserKind::Lr(LrKind::Lalr)),
+            "lr1" => Ok(ParserKind::Lr(LrKind::Lr1)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Normal form the normalize command converts a grammar to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalForm {
+    Chomsky,
+    Reduced,
+}
+impl FromStr for NormalForm {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cnf" => Ok(NormalForm::Chomsky),
+            "reduced" => Ok(NormalForm::Reduced),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Which automaton the automaton command prints.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutomatonStage {
+    Nfa,
+    Dfa,
+    Minimal,
+}
+impl FromStr for AutomatonStage {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nfa" => Ok(AutomatonStage::Nfa),
+            "dfa" => Ok(AutomatonStage::Dfa),
+            "minimal" => Ok(AutomatonStage::Minimal),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Format an automaton is printed in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutomatonFormat {
+    Table,
+    Dot,
+}
+impl FromStr for AutomatonFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(AutomatonFormat::Table),
+            "dot" => Ok(AutomatonFormat::Dot),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A regular language to intersect a grammar with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegularLanguage {
+    Regex(String),
+    DfaFile(String),
+}
+
+/// Command run by the bnf_parser binary. Grammar paths of `-` are read from stdin.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Prints help text.
+    Help(&'static str),
+    /// Prints a single grammar.
+    Show { grammar_path: String, output_format: OutputFormat },
+    /// Reports problems with a grammar.
+    Check { grammar_path: String },
+    /// Reports likely mistakes in a grammar, with lints allowed or turned into errors.
+    Lint { grammar_path: String, allow: Vec<LintCode>, deny: Vec<LintCode> },
+    /// Converts a grammar to a normal form.
+    Normalize { grammar_path: String, form: NormalForm, output_format: OutputFormat },
+    /// Prints the nullable, FIRST and FOLLOW sets of a grammar's variables.
+    Sets { grammar_path: String, variable: Option<String>, k: usize },
+    /// Parses a sentence and prints its parse trees.
+    Parse {
+        grammar_path: String,
+        sentence: String,
+        input_path: Option<String>,
+        parser: ParserKind,
+        tree_format: TreeFormat,
+        trees: usize,
+    },
+    /// Prints the leftmost or rightmost derivation of a sentence.
+    Derive { grammar_path: String, sentence: String, rightmost: bool, color: bool },
+    /// Evaluates the semantic actions of a grammar on a sentence.
+    Eval { grammar_path: String, sentence: String, attribute: String },
+    /// Prints random sentences of a grammar.
+    Generate { grammar_path: String, count: usize, max_depth: usize, max_len: usize, seed: Option<u64> },
+    /// Converts a grammar between file formats.
+    Convert { grammar_path: String, from: Option<GrammarFormat>, to: GrammarFormat },
+    /// Compares the languages of two grammars up to a bounded length.
+    Equiv { first_path: String, second_path: String, max_len: usize, samples: usize, seed: Option<u64> },
+    /// Compares the rules and derived properties of two versions of a grammar.
+    Diff { old_path: String, new_path: String },
+    /// Searches a grammar for a sentence with two parse trees up to a bounded length.
+    Ambiguity { grammar_path: String, max_len: usize },
+    /// Converts a regular grammar to a finite automaton.
+    Automaton { grammar_path: String, stage: AutomatonStage, output_format: AutomatonFormat },
+    /// Intersects a grammar with a regular language given as a regex or a DFA file.
+    Intersect { grammar_path: String, regular: RegularLanguage, output_format: OutputFormat },
+    /// Prints a pushdown automaton or runs it on a sentence.
+    Pda { path: String, sentence: Option<String>, max_steps: usize },
+    /// Finds the smallest k up to a limit for which a grammar is LL(k).
+    Ll { grammar_path: String, max_k: usize },
+    /// Reports the most specific class of grammars a grammar is in.
+    Classify { grammar_path: String, max_len: usize },
+    /// Prints the source of a recursive-descent parser for an LL(1) grammar.
+    Codegen { grammar_path: String },
+    /// Starts an interactive session on a grammar.
+    Repl { grammar_path: Option<String> },
+}
+
+/// Configuration struct for the bnf_parser binary.
 pub struct Config {
-    pub bnf_grammar_filepath: String,
+    pub command: Command,
 }
 impl Config {
-    pub fn build(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 2 {
-            return Err("Usage: bnf_lexer <bnf-grammar-filepath>");
+    pub fn build(args: &[String]) -> Result<Config, CliError> {
+        let Some(command) = args.get(1) else {
+            return Err(usage(HELP));
+        };
+        let args = &args[2..];
+
+        let help = command_help(command).ok_or_else(|| {
+            CliError::Usage(format!("Unknown command '{}'. Run `bnf_parser --help` for a list of commands.", command))
+        })?;
+        if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+            return Ok(Config { command: Command::Help(help) });
         }
 
-        let bnf_grammar_filepath = args[1].clone();
+        let command = match command.as_str() {
+            "help" | "--help" | "-h" => {
+                let help = match args.first() {
+                    Some(command) => command_help(command).ok_or_else(|| usage(HELP))?,
+                    None => HELP,
+                };
+                Command::Help(help)
+            }
+            "show" => {
+                let args = Arguments::parse(args, &["--output"], &[], help)?;
+                Command::Show {
+                    grammar_path: args.single_positional()?,
+                    output_format: args.value("--output", OutputFormat::Text)?,
+                }
+            }
+            "check" => {
+                let args = Arguments::parse(args, &[], &[], help)?;
+                Command::Check { grammar_path: args.single_positional()? }
+            }
+            "lint" => {
+                let args = Arguments::parse(args, &["--allow", "--deny"], &[], help)?;
+                Command::Lint {
+                    grammar_path: args.single_positional()?,
+                    allow: args.list("--allow")?,
+                    deny: args.list("--deny")?,
+                }
+            }
+            "normalize" => {
+                let args = Arguments::parse(args, &["--form", "--output"], &[], help)?;
+                Command::Normalize {
+                    grammar_path: args.single_positional()?,
+                    form: args.value("--form", NormalForm::Chomsky)?,
+                    output_format: args.value("--output", OutputFormat::Bnf)?,
+                }
+            }
+            "sets" => {
+                let args = Arguments::parse(args, &["--variable", "--k"], &[], help)?;
+                let k = args.value("--k", 1)?;
+                if k == 0 {
+                    return Err(usage(help));
+                }
+                Command::Sets {
+                    grammar_path: args.single_positional()?,
+                    variable: args.options.get("--variable").cloned(),
+                    k,
+                }
+            }
+            "parse" => {
+                let args = Arguments::parse(args, &["--parser", "--tree", "--trees", "--input"], &[], help)?;
+                let (grammar_path, words) = args.positionals.split_first().ok_or_else(|| usage(help))?;
+                let input_path = args.options.get("--input").cloned();
+                if input_path.is_some() && !words.is_empty() {
+                    return Err(CliError::Usage(
+                        "Give the sentence either as arguments or with --input, not both".to_string(),
+                    ));
+                }
+                if grammar_path == "-" && input_path.as_deref() == Some("-") {
+                    return Err(CliError::Usage(
+                        "Only one of the grammar and the sentence can be read from stdin".to_string(),
+                    ));
+                }
+                let parser = args.value("--parser", ParserKind::Earley)?;
+                let trees = args.value("--trees", 1)?;
+                if parser != ParserKind::Earley && trees != 1 {
+                    return Err(CliError::Usage("Only the earley parser can print several parse trees".to_string()));
+                }
+                Command::Parse {
+                    grammar_path: grammar_path.clone(),
+                    sentence: words.join(" "),
+                    input_path,
+                    parser,
+                    tree_format: args.value("--tree", TreeFormat::Ascii)?,
+                    trees,
+                }
+            }
+            "derive" => {
+                let args = Arguments::parse(args, &[], &["--rightmost", "--color"], help)?;
+                let (grammar_path, words) = args.positionals.split_first().ok_or_else(|| usage(help))?;
+                Command::Derive {
+                    grammar_path: grammar_path.clone(),
+                    sentence: words.join(" "),
+                    rightmost: args.flags.contains("--rightmost"),
+                    color: args.flags.contains("--color"),
+                }
+            }
+            "eval" => {
+                let args = Arguments::parse(args, &["--attribute"], &[], help)?;
+                let (grammar_path, words) = args.positionals.split_first().ok_or_else(|| usage(help))?;
+                Command::Eval {
+                    grammar_path: grammar_path.clone(),
+                    sentence: words.join(" "),
+                    attribute: args.value("--attribute", semantics::VALUE.to_string())?,
+                }
+            }
+            "generate" => {
+                let args = Arguments::parse(args, &["--count", "--max-depth", "--max-len", "--seed"], &[], help)?;
+                Command::Generate {
+                    grammar_path: args.single_positional()?,
+                    count: args.value("--count", 10)?,
+                    max_depth: args.value("--max-depth", 12)?,
+                    max_len: args.value("--max-len", 30)?,
+                    seed: args.optional_value("--seed")?,
+                }
+            }
+            "convert" => {
+                let args = Arguments::parse(args, &["--from", "--to"], &[], help)?;
+                let Some(to) = args.optional_value("--to")? else {
+                    return Err(usage(help));
+                };
+                Command::Convert {
+                    grammar_path: args.single_positional()?,
+                    from: args.optional_value("--from")?,
+                    to,
+                }
+            }
+            "equiv" => {
+                let args = Arguments::parse(args, &["--max-len", "--samples", "--seed"], &[], help)?;
+                let [first_path, second_path] = args.positionals.as_slice() else {
+                    return Err(usage(help));
+                };
+                Command::Equiv {
+                    first_path: first_path.clone(),
+                    second_path: second_path.clone(),
+                    max_len: args.value("--max-len", 6)?,
+                    samples: args.value("--samples", 100)?,
+                    seed: args.optional_value("--seed")?,
+                }
+            }
+            "diff" => {
+                let args = Arguments::parse(args, &[], &[], help)?;
+                let [old_path, new_path] = args.positionals.as_slice() else {
+                    return Err(usage(help));
+                };
+                Command::Diff { old_path: old_path.clone(), new_path: new_path.clone() }
+            }
+            "ambiguity" => {
+                let args = Arguments::parse(args, &["--max-len"], &[], help)?;
+                Command::Ambiguity {
+                    grammar_path: args.single_positional()?,
+                    max_len: args.value("--max-len", 6)?,
+                }
+            }
+            "ll" => {
+                let args = Arguments::parse(args, &["--max-k"], &[], help)?;
+                let max_k = args.value("--max-k", 3)?;
+                if max_k == 0 {
+                    return Err(usage(help));
+                }
+                Command::Ll { grammar_path: args.single_positional()?, max_k }
+            }
+            "classify" => {
+                let args = Arguments::parse(args, &["--max-len"], &[], help)?;
+                Command::Classify {
+                    grammar_path: args.single_positional()?,
+                    max_len: args.value("--max-len", 6)?,
+                }
+            }
+            "codegen" => {
+                let args = Arguments::parse(args, &[], &[], help)?;
+                Command::Codegen { grammar_path: args.single_positional()? }
+            }
+            "automaton" => {
+                let args = Arguments::parse(args, &["--stage", "--output"], &[], help)?;
+                Command::Automaton {
+                    grammar_path: args.single_positional()?,
+                    stage: args.value("--stage", AutomatonStage::Minimal)?,
+                    output_format: args.value("--output", AutomatonFormat::Table)?,
+                }
+            }
+            "intersect" => {
+                let args = Arguments::parse(args, &["--regex", "--dfa", "--output"], &[], help)?;
+                let regular = match (args.options.get("--regex"), args.options.get("--dfa")) {
+                    (Some(pattern), None) => RegularLanguage::Regex(pattern.clone()),
+                    (None, Some(path)) => RegularLanguage::DfaFile(path.clone()),
+                    _ => return Err(usage(help)),
+                };
+                Command::Intersect {
+                    grammar_path: args.single_positional()?,
+                    regular,
+                    output_format: args.value("--output", OutputFormat::Bnf)?,
+                }
+            }
+            "pda" => {
+                let args = Arguments::parse(args, &["--max-steps"], &[], help)?;
+                let (path, words) = args.positionals.split_first().ok_or_else(|| usage(help))?;
+                Command::Pda {
+                    path: path.clone(),
+                    sentence: (!words.is_empty()).then(|| words.join(" ")),
+                    max_steps: args.value("--max-steps", 10000)?,
+                }
+            }
+            "repl" => {
+                let args = Arguments::parse(args, &[], &[], help)?;
+                if args.positionals.len() > 1 {
+                    return Err(usage(help));
+                }
+                Command::Repl { grammar_path: args.positionals.first().cloned() }
+            }
+            _ => unreachable!("command_help only knows the commands above"),
+        };
 
-        Ok(Config { bnf_grammar_filepath })
+        Ok(Config { command })
     }
 }
 
-/// Runs the bnf_lexer binary.
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    println!("bnf_grammar_filepath: {}", config.bnf_grammar_filepath);
+/// Returns the help text of a command, or None if there is no such command.
+fn command_help(command: &str) -> Option<&'static str> {
+    match command {
+        "help" | "--help" | "-h" => Some(HELP),
+        "show" => Some(SHOW_HELP),
+        "check" => Some(CHECK_HELP),
+        "lint" => Some(LINT_HELP),
+        "normalize" => Some(NORMALIZE_HELP),
+        "sets" => Some(SETS_HELP),
+        "parse" => Some(PARSE_HELP),
+        "derive" => Some(DERIVE_HELP),
+        "eval" => Some(EVAL_HELP),
+        "generate" => Some(GENERATE_HELP),
+        "convert" => Some(CONVERT_HELP),
+        "equiv" => Some(EQUIV_HELP),
+        "diff" => Some(DIFF_HELP),
+        "ambiguity" => Some(AMBIGUITY_HELP),
+        "automaton" => Some(AUTOMATON_HELP),
+        "intersect" => Some(INTERSECT_HELP),
+        "pda" => Some(PDA_HELP),
+        "ll" => Some(LL_HELP),
+        "classify" => Some(CLASSIFY_HELP),
+        "codegen" => Some(CODEGEN_HELP),
+        "repl" => Some(REPL_HELP),
+        _ => None,
+    }
+}
+
+/// Returns a usage error showing a command's help text.
+fn usage(help: &str) -> CliError {
+    CliError::Usage(help.to_string())
+}
+
+/// Arguments of a command, split into positionals, `--option value` pairs and `--flag`s.
+struct Arguments {
+    positionals: Vec<String>,
+    options: HashMap<String, String>,
+    flags: HashSet<String>,
+    help: &'static str,
+}
+impl Arguments {
+    fn parse(
+        args: &[String],
+        value_options: &[&str],
+        flag_options: &[&str],
+        help: &'static str,
+    ) -> Result<Arguments, CliError> {
+        let mut arguments = Arguments {
+            positionals: Vec::new(),
+            options: HashMap::new(),
+            flags: HashSet::new(),
+            help,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                // everything after -- is positional, even if it looks like an option
+                arguments.positionals.extend(args.by_ref().cloned());
+            } else if value_options.contains(&arg.as_str()) {
+                let value = args
+                    .next()
+                    .ok_or_else(|| CliError::Usage(format!("{} expects a value\n\n{}", arg, help)))?;
+                arguments.options.insert(arg.clone(), value.clone());
+            } else if flag_options.contains(&arg.as_str()) {
+                arguments.flags.insert(arg.clone());
+            } else if arg.starts_with("--") {
+                return Err(CliError::Usage(format!("Unknown option {}\n\n{}", arg, help)));
+            } else {
+                arguments.positionals.push(arg.clone());
+            }
+        }
+
+        Ok(arguments)
+    }
+
+    /// Returns the only positional argument.
+    fn single_positional(&self) -> Result<String, CliError> {
+        match self.positionals.as_slice() {
+            [positional] => Ok(positional.clone()),
+            _ => Err(usage(self.help)),
+        }
+    }
 
-    let bnf_grammar = read_bnf_file(&config.bnf_grammar_filepath)?;
+    /// Parses an option's value, or returns None if the option wasn't given.
+    fn optional_value<T: FromStr>(&self, option: &str) -> Result<Option<T>, CliError> {
+        self.options
+            .get(option)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| CliError::Usage(format!("Invalid value '{}' for {}\n\n{}", value, option, self.help)))
+            })
+            .transpose()
+    }
 
-    let grammar = cfg::build_grammar(&bnf_grammar);
+    /// Parses an option's value, or returns the default if the option wasn't given.
+    fn value<T: FromStr>(&self, option: &str, default: T) -> Result<T, CliError> {
+        Ok(self.optional_value(option)?.unwrap_or(default))
+    }
 
-    // prints grammar to the screen
-    println!("{}", grammar);
+    /// Parses an option's comma separated values, or returns an empty list if the option wasn't given.
+    fn list<T: FromStr>(&self, option: &str) -> Result<Vec<T>, CliError> {
+        let Some(values) = self.options.get(option) else {
+            return Ok(Vec::new());
+        };
+        values
+            .split(',')
+            .map(|value| {
+                value
+                    .trim()
+                    .parse()
+                    .map_err(|_| CliError::Usage(format!("Invalid value '{}' for {}\n\n{}", value, option, self.help)))
+            })
+            .collect()
+    }
+}
 
-    Ok(())
+/// Runs the bnf_parser binary.
+pub fn run(config: Config) -> Result<(), CliError> {
+    match config.command {
+        Command::Help(help) => {
+            println!("{}", help);
+            Ok(())
+        }
+        Command::Show { grammar_path, output_format } => commands::show(&grammar_path, output_format),
+        Command::Check { grammar_path } => commands::check(&grammar_path),
+        Command::Lint { grammar_path, allow, deny } => commands::lint(&grammar_path, &allow, &deny),
+        Command::Normalize { grammar_path, form, output_format } => {
+            commands::normalize(&grammar_path, form, output_format)
+        }
+        Command::Sets { grammar_path, variable, k } => commands::sets(&grammar_path, variable.as_deref(), k),
+        Command::Parse { grammar_path, sentence, input_path, parser, tree_format, trees } => {
+            let sentence = match input_path {
+                Some(input_path) => commands::read_input(&input_path)?,
+                None => sentence,
+            };
+            commands::parse(&grammar_path, &sentence, parser, tree_format, trees)
+        }
+        Command::Derive { grammar_path, sentence, rightmost, color } => {
+            commands::derive(&grammar_path, &sentence, rightmost, color)
+        }
+        Command::Eval { grammar_path, sentence, attribute } => commands::eval(&grammar_path, &sentence, &attribute),
+        Command::Generate { grammar_path, count, max_depth, max_len, seed } => {
+            commands::generate(&grammar_path, count, max_depth, max_len, seed)
+        }
+        Command::Convert { grammar_path, from, to } => commands::convert(&grammar_path, from, to),
+        Command::Equiv { first_path, second_path, max_len, samples, seed } => {
+            commands::equiv(&first_path, &second_path, max_len, samples, seed)
+        }
+        Command::Diff { old_path, new_path } => commands::diff(&old_path, &new_path),
+        Command::Ambiguity { grammar_path, max_len } => commands::ambiguity(&grammar_path, max_len),
+        Command::Automaton { grammar_path, stage, output_format } => {
+            commands::automaton(&grammar_path, stage, output_format)
+        }
+        Command::Intersect { grammar_path, regular, output_format } => {
+            commands::intersect(&grammar_path, &regular, output_format)
+        }
+        Command::Pda { path, sentence, max_steps } => commands::pda(&path, sentence.as_deref(), max_steps),
+        Command::Ll { grammar_path, max_k } => commands::ll(&grammar_path, max_k),
+        Command::Classify { grammar_path, max_len } => commands::classify(&grammar_path, max_len),
+        Command::Codegen { grammar_path } => commands::codegen(&grammar_path),
+        Command::Repl { grammar_path } => repl::run(grammar_path.as_deref()),
+    }
 }
 
-/// Reads a BNF grammar file and returns the contents as a String.
-pub fn read_bnf_file(filename: &str) -> Result<String, Box<dyn Error>> {
-    fs::read_to_string(filename).map_err(|e| e.into())
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(args: &str) -> Result<Command, CliError> {
+        let args: Vec<String> = args.split_whitespace().map(|s| s.to_string()).collect();
+        Config::build(&args).map(|config| config.command)
+    }
+
+    #[test]
+    fn test_build_commands() {
+        assert_eq!(
+            build("bnf_parser show g.bnf --output json").unwrap(),
+            Command::Show { grammar_path: "g.bnf".to_string(), output_format: OutputFormat::Json }
+        );
+        assert_eq!(
+            build("bnf_parser parse - --tree dot id + id").unwrap(),
+            Command::Parse {
+                grammar_path: "-".to_string(),
+                sentence: "id + id".to_string(),
+                input_path: None,
+                parser: ParserKind::Earley,
+                tree_format: TreeFormat::Dot,
+                trees: 1,
+            }
+        );
+        assert_eq!(
+            build("bnf_parser parse g.bnf --parser lalr id").unwrap(),
+            Command::Parse {
+                grammar_path: "g.bnf".to_string(),
+                sentence: "id".to_string(),
+                input_path: None,
+                parser: ParserKind::Lr(LrKind::Lalr),
+                tree_format: TreeFormat::Ascii,
+                trees: 1,
+            }
+        );
+        assert_eq!(
+            build("bnf_parser automaton g.bnf --stage dfa --output dot").unwrap(),
+            Command::Automaton {
+                grammar_path: "g.bnf".to_string(),
+                stage: AutomatonStage::Dfa,
+                output_format: AutomatonFormat::Dot,
+            }
+        );
+        assert_eq!(
+            build("bnf_parser convert a(b|c)* --from regex --to bnf").unwrap(),
+            Command::Convert {
+                grammar_path: "a(b|c)*".to_string(),
+                from: Some(GrammarFormat::Regex),
+                to: GrammarFormat::Bnf,
+            }
+        );
+        assert_eq!(
+            build("bnf_parser pda anbn.pda --max-steps 50 a b").unwrap(),
+            Command::Pda { path: "anbn.pda".to_string(), sentence: Some("a b".to_string()), max_steps: 50 }
+        );
+        assert_eq!(
+            build("bnf_parser intersect g.bnf --dfa even.dfa").unwrap(),
+            Command::Intersect {
+                grammar_path: "g.bnf".to_string(),
+                regular: RegularLanguage::DfaFile("even.dfa".to_string()),
+                output_format: OutputFormat::Bnf,
+            }
+        );
+        assert_eq!(
+            build("bnf_parser sets g.bnf --k 2").unwrap(),
+            Command::Sets { grammar_path: "g.bnf".to_string(), variable: None, k: 2 }
+        );
+        assert_eq!(build("bnf_parser ll g.bnf").unwrap(), Command::Ll { grammar_path: "g.bnf".to_string(), max_k: 3 });
+        assert_eq!(
+            build("bnf_parser classify g.bnf --max-len 4").unwrap(),
+            Command::Classify { grammar_path: "g.bnf".to_string(), max_len: 4 }
+        );
+        assert_eq!(
+            build("bnf_parser lint g.bnf --allow L003,left-recursion --deny L001").unwrap(),
+            Command::Lint {
+                grammar_path: "g.bnf".to_string(),
+                allow: vec![LintCode::DuplicateAlternative, LintCode::LeftRecursion],
+                deny: vec![LintCode::UndefinedVariable],
+            }
+        );
+        assert_eq!(
+            build("bnf_parser diff old.bnf new.bnf").unwrap(),
+            Command::Diff { old_path: "old.bnf".to_string(), new_path: "new.bnf".to_string() }
+        );
+        assert_eq!(
+            build("bnf_parser eval calc.bnf 1 + 2").unwrap(),
+            Command::Eval {
+                grammar_path: "calc.bnf".to_string(),
+                sentence: "1 + 2".to_string(),
+                attribute: "value".to_string(),
+            }
+        );
+        assert_eq!(build("bnf_parser codegen g.bnf").unwrap(), Command::Codegen { grammar_path: "g.bnf".to_string() });
+        assert_eq!(build("bnf_parser check --help").unwrap(), Command::Help(CHECK_HELP));
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        assert_eq!(build("bnf_parser").unwrap_err().exit_code(), EXIT_USAGE);
+        assert_eq!(build("bnf_parser frobnicate g.bnf").unwrap_err().exit_code(), EXIT_USAGE);
+        assert_eq!(build("bnf_parser intersect g.bnf --regex a --dfa b.dfa").unwrap_err().exit_code(), EXIT_USAGE);
+        assert_eq!(build("bnf_parser show g.bnf --output yaml").unwrap_err().exit_code(), EXIT_USAGE);
+        assert_eq!(build("bnf_parser show a.bnf b.bnf").unwrap_err().exit_code(), EXIT_USAGE);
+        assert_eq!(build("bnf_parser parse - --input -").unwrap_err().exit_code(), EXIT_USAGE);
+        assert_eq!(build("bnf_parser parse g.bnf --parser lr0 id").unwrap_err().exit_code(), EXIT_USAGE);
+        assert_eq!(build("bnf_parser parse g.bnf --parser ll1 --trees 2 id").unwrap_err().exit_code(), EXIT_USAGE);
+        assert_eq!(build("bnf_parser generate g.bnf --count").unwrap_err().exit_code(), EXIT_USAGE);
+        assert_eq!(build("bnf_parser diff old.bnf").unwrap_err().exit_code(), EXIT_USAGE);
+        assert_eq!(build("bnf_parser lint g.bnf --allow L999").unwrap_err().exit_code(), EXIT_USAGE);
+        assert_eq!(build("bnf_parser sets g.bnf --k 0").unwrap_err().exit_code(), EXIT_USAGE);
+        assert_eq!(build("bnf_parser ll g.bnf --max-k 0").unwrap_err().exit_code(), EXIT_USAGE);
+        assert_eq!(build("bnf_parser eval").unwrap_err().exit_code(), EXIT_USAGE);
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::context_free_grammar::{format_rule, split_action, split_alternatives, ContextFreeGrammar, EPSILON};
+use crate::normal_form;
+use crate::sets;
+
+/// Comment directive that allows lints on the next rule line, as in `# bnf-lint: allow L001, L006`.
+pub const ALLOW_DIRECTIVE: &str = "bnf-lint: allow";
+
+/// The kinds of problems the linter looks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintCode {
+    /// A variable is used on a right hand side but has no rules.
+    UndefinedVariable,
+    /// A variable has rules but no other variable's rule uses it, and it isn't the start symbol.
+    UnusedVariable,
+    /// The same alternative is written more than once for a variable.
+    DuplicateAlternative,
+    /// A terminal on a right hand side looks like a variable with a typo, like `<NP` or `NP` when there is a `<NP>`.
+    MisspelledVariable,
+    /// A rule can't be reached from the start symbol.
+    UnreachableRule,
+    /// A variable can derive a string starting with itself, which predictive parsers loop on.
+    LeftRecursion,
+}
+impl LintCode {
+    pub const ALL: [LintCode; 6] = [
+        LintCode::UndefinedVariable,
+        LintCode::UnusedVariable,
+        LintCode::DuplicateAlternative,
+        LintCode::MisspelledVariable,
+        LintCode::UnreachableRule,
+        LintCode::LeftRecursion,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            LintCode::UndefinedVariable => "L001",
+            LintCode::UnusedVariable => "L002",
+            LintCode::DuplicateAlternative => "L003",
+            LintCode::MisspelledVariable => "L004",
+            LintCode::UnreachableRule => "L005",
+            LintCode::LeftRecursion => "L006",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LintCode::UndefinedVariable => "undefined-variable",
+            LintCode::UnusedVariable => "unused-variable",
+            LintCode::DuplicateAlternative => "duplicate-alternative",
+            LintCode::MisspelledVariable => "misspelled-variable",
+            LintCode::UnreachableRule => "unreachable-rule",
+            LintCode::LeftRecursion => "left-recursion",
+        }
+    }
+}
+impl FromStr for LintCode {
+    type Err = ();
+
+    /// Parses a lint from its code, like `L001`, or its name, like `undefined-variable`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LintCode::ALL.into_iter().find(|code| code.code().eq_ignore_ascii_case(s) || code.name() == s).ok_or(())
+    }
+}
+
+/// A problem found by the linter, on a line of the grammar's source if it has one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    pub code: LintCode,
+    pub line: Option<usize>,
+    pub message: String,
+}
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        write!(f, "[{}] {}", self.code.code(), self.message)
+    }
+}
+
+/// Runs every lint on a grammar and the source it was built from, sorted by line.
+/// Lints allowed by a directive comment on the line before are left out.
+pub fn lint(grammar: &ContextFreeGrammar, source: &str) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    undefined_and_unused_variables(grammar, &mut lints);
+    duplicate_alternatives(source, &mut lints);
+    misspelled_variables(grammar, &mut lints);
+    unreachable_rules(grammar, &mut lints);
+    left_recursion(grammar, &mut lints);
+
+    let allowed = allowed_lints(source);
+    lints.retain(|lint| lint.line.is_none_or(|line| !allowed.contains(&(line, lint.code))));
+    lints.sort_by_key(|lint| (lint.line, lint.code.code()));
+    lints
+}
+
+/// Returns the line of the first rule of a variable, or of the first rule using it if it has none.
+fn variable_line(grammar: &ContextFreeGrammar, variable: &str) -> Option<usize> {
+    let rule = grammar.rules_for(variable).next().or_else(|| {
+        grammar.production_rules().iter().find(|rule| rule.rhs.iter().any(|symbol| symbol == variable))
+    });
+    rule.and_then(|rule| rule.span).map(|span| span.line)
+}
+
+fn undefined_and_unused_variables(grammar: &ContextFreeGrammar, lints: &mut Vec<Lint>) {
+    for variable in grammar.variables() {
+        let defined = grammar.rules_for(variable).next().is_some();
+        let used = grammar.production_rules().iter().any(|rule| rule.lhs != *variable && rule.rhs.contains(variable));
+        let message = if !defined {
+            (LintCode::UndefinedVariable, format!("{} is used but has no production rules", variable))
+        } else if !used && variable != grammar.start_symbol() {
+            (LintCode::UnusedVariable, format!("{} is never used by another variable's rules", variable))
+        } else {
+            continue;
+        };
+        lints.push(Lint { code: message.0, line: variable_line(grammar, variable), message: message.1 });
+    }
+}
+
+/// Finds alternatives written twice for the same variable. The grammar keeps only one copy of each rule,
+/// so the source is read again, line by line.
+fn duplicate_alternatives(source: &str, lints: &mut Vec<Lint>) {
+    let mut seen: HashMap<(&str, Vec<&str>), usize> = HashMap::new();
+    for (line_index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('#') || line.starts_with('@') {
+            continue;
+        }
+        let Some((lhs, rhs)) = line.split_once("::=") else {
+            continue;
+        };
+
+        for alternative in split_alternatives(rhs) {
+            // an alternative with an action that can't be split is reported by build_grammar
+            let alternative = split_action(alternative).map_or(alternative, |(symbols, _)| symbols);
+            let symbols: Vec<&str> = alternative.split_whitespace().filter(|s| *s != EPSILON).collect();
+            let line = line_index + 1;
+            let rhs = if symbols.is_empty() { EPSILON.to_string() } else { symbols.join(" ") };
+            let rule = format!("{} ::= {}", lhs.trim(), rhs);
+            match seen.get(&(lhs.trim(), symbols.clone())) {
+                Some(&first) => lints.push(Lint {
+                    code: LintCode::DuplicateAlternative,
+                    line: Some(line),
+                    message: format!("{} is already an alternative on line {}", rule, first),
+                }),
+                None => {
+                    seen.insert((lhs.trim(), symbols), line);
+                }
+            }
+        }
+    }
+}
+
+fn misspelled_variables(grammar: &ContextFreeGrammar, lints: &mut Vec<Lint>) {
+    for terminal in grammar.terminals() {
+        let name = terminal.trim_start_matches('<').trim_end_matches('>');
+        let is_name = name.starts_with(|c: char| c.is_alphabetic())
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '\'');
+        if !is_name {
+            continue;
+        }
+
+        let variable = format!("<{}>", name);
+        let message = if terminal.starts_with('<') != terminal.ends_with('>') {
+            format!("terminal {} looks like the variable {} with a missing bracket", terminal, variable)
+        } else if name == terminal && grammar.is_variable(&variable) {
+            format!("terminal {} looks like the variable {} without its brackets", terminal, variable)
+        } else {
+            continue;
+        };
+        let rule = grammar.production_rules().iter().find(|rule| rule.rhs.contains(terminal));
+        let line = rule.and_then(|rule| rule.span).map(|span| span.line);
+        lints.push(Lint { code: LintCode::MisspelledVariable, line, message });
+    }
+}
+
+fn unreachable_rules(grammar: &ContextFreeGrammar, lints: &mut Vec<Lint>) {
+    let reachable = normal_form::reachable_variables(grammar);
+    let used: HashSet<&str> = grammar
+        .production_rules()
+        .iter()
+        .flat_map(|rule| rule.rhs.iter().filter(move |symbol| **symbol != rule.lhs))
+        .map(|symbol| symbol.as_str())
+        .collect();
+
+    for rule in grammar.production_rules() {
+        // the rules of unused variables are already reported with them
+        if reachable.contains(rule.lhs.as_str()) || !used.contains(rule.lhs.as_str()) {
+            continue;
+        }
+        lints.push(Lint {
+            code: LintCode::UnreachableRule,
+            line: rule.span.map(|span| span.line),
+            message: format!("{} can't be reached from the start symbol {}", format_rule(rule), grammar.start_symbol()),
+        });
+    }
+}
+
+/// Finds variables that derive strings starting with themselves, through the variables that can start
+/// each of their rules after nullable ones.
+fn left_recursion(grammar: &ContextFreeGrammar, lints: &mut Vec<Lint>) {
+    let nullable = sets::nullable_variables(grammar);
+
+    // edges from each variable to the variables its rules can start with, and the rule of each edge
+    let mut edges: HashMap<&str, Vec<(&str, Option<usize>)>> = HashMap::new();
+    for rule in grammar.production_rules() {
+        for symbol in &rule.rhs {
+            if !grammar.is_variable(symbol) {
+                break;
+            }
+            edges.entry(rule.lhs.as_str()).or_default().push((symbol.as_str(), rule.span.map(|span| span.line)));
+            if !nullable.contains(symbol.as_str()) {
+                break;
+            }
+        }
+    }
+
+    for variable in grammar.variables() {
+        // shortest path back to the variable, remembering how each variable was first reached
+        let mut parents: HashMap<&str, (&str, Option<usize>)> = HashMap::new();
+        let mut queue = VecDeque::from([variable.as_str()]);
+        let mut cycle_line = None;
+        'search: while let Some(current) = queue.pop_front() {
+            for &(next, line) in edges.get(current).into_iter().flatten() {
+                if next == variable {
+                    parents.insert(next, (current, line));
+                    cycle_line = Some(line);
+                    break 'search;
+                }
+                if !parents.contains_key(next) {
+                    parents.insert(next, (current, line));
+                    queue.push_back(next);
+                }
+            }
+        }
+        let Some(mut line) = cycle_line else {
+            continue;
+        };
+
+        let mut path = vec![variable.as_str()];
+        let mut current = parents[variable.as_str()].0;
+        while current != variable {
+            path.push(current);
+            line = parents[current].1;
+            current = parents[current].0;
+        }
+        path.push(variable);
+        path.reverse();
+
+        let message = if path.len() == 2 {
+            format!("{} is directly left recursive", variable)
+        } else {
+            format!("{} is left recursive through {}", variable, path.join(" -> "))
+        };
+        lints.push(Lint { code: LintCode::LeftRecursion, line, message });
+    }
+}
+
+/// Returns the lints allowed on each line by a directive comment on the line before it,
+/// skipping blank lines and other comments in between.
+fn allowed_lints(source: &str) -> HashSet<(usize, LintCode)> {
+    let mut allowed = HashSet::new();
+    let mut pending: Vec<LintCode> = Vec::new();
+    for (line_index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix('#') {
+            if let Some(codes) = comment.trim().strip_prefix(ALLOW_DIRECTIVE) {
+                pending.extend(codes.split([',', ' ']).filter_map(|code| code.trim().parse::<LintCode>().ok()));
+            }
+        } else if !line.is_empty() {
+            allowed.extend(pending.drain(..).map(|code| (line_index + 1, code)));
+        }
+    }
+    allowed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+
+    fn lints(source: &str) -> Vec<String> {
+        lint(&build_grammar(source).unwrap(), source).iter().map(|lint| lint.to_string()).collect()
+    }
+
+    #[test]
+    fn test_lints() {
+        let source = "<S> ::= <NP> <VP> | <NP <VP>
+<NP> ::= the <N> | a <N>
+<VP> ::= runs | <VP> quickly | <Adv> <VP>
+<Adv> ::= ε
+<NP> ::= the <N>
+<U> ::= NP
+<R> ::= <U> | <R> x";
+        assert_eq!(
+            lints(source),
+            [
+                "line 1: [L004] terminal <NP looks like the variable <NP> with a missing bracket",
+                "line 2: [L001] <N> is used but has no production rules",
+                "line 3: [L006] <VP> is directly left recursive",
+                "line 5: [L003] <NP> ::= the <N> is already an alternative on line 2",
+                "line 6: [L004] terminal NP looks like the variable <NP> without its brackets",
+                "line 6: [L005] <U> ::= NP can't be reached from the start symbol <S>",
+                "line 7: [L002] <R> is never used by another variable's rules",
+                "line 7: [L006] <R> is directly left recursive",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indirect_left_recursion() {
+        let source = "<A> ::= <N> <B> a\n<B> ::= <A> b | c\n<N> ::= ε | n";
+        assert_eq!(
+            lints(source),
+            [
+                "line 1: [L006] <A> is left recursive through <A> -> <B> -> <A>",
+                "line 2: [L006] <B> is left recursive through <B> -> <A> -> <B>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_allow_directive() {
+        let source = "<E> ::= <E> + <T> | <T>\n# bnf-lint: allow L001\n\n<T> ::= id | <F>";
+        assert_eq!(lints(source), ["line 1: [L006] <E> is directly left recursive"]);
+
+        let source = format!("# {} left-recursion\n{}", ALLOW_DIRECTIVE, source);
+        assert!(lints(&source).is_empty());
+        assert_eq!("L003".parse::<LintCode>(), Ok(LintCode::DuplicateAlternative));
+        assert_eq!("unused-variable".parse::<LintCode>(), Ok(LintCode::UnusedVariable));
+    }
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::context_free_grammar::{format_rule, ContextFreeGrammar, ProductionRule};
+use crate::sets::{GrammarSets, LookaheadSets};
+
+/// A predictive parse table: for each variable and lookahead, the rules a parser could expand it with.
+/// A lookahead is a sequence of terminals, ending with `$` if it reaches the end of the input.
+pub struct LlTable<'g> {
+    pub entries: IndexMap<&'g str, IndexMap<Vec<&'g str>, Vec<&'g ProductionRule>>>,
+}
+impl<'g> LlTable<'g> {
+    /// Returns the entries with more than one rule, which keep the grammar from being predictive.
+    pub fn conflicts(&self) -> Vec<LlConflict<'g>> {
+        let mut conflicts = Vec::new();
+        for (variable, row) in &self.entries {
+            for (lookahead, rules) in row {
+                if rules.len() > 1 {
+                    conflicts.push(LlConflict { variable, lookahead: lookahead.clone(), rules: rules.clone() });
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Returns the rule to expand a variable with on a lookahead, if there is exactly one.
+    pub fn rule(&self, variable: &str, lookahead: &[&str]) -> Option<&'g ProductionRule> {
+        match self.entries.get(variable)?.get(lookahead)?.as_slice() {
+            [rule] => Some(rule),
+            _ => None,
+        }
+    }
+}
+
+/// Rules of the same variable that a predictive parser can't choose between on a lookahead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LlConflict<'g> {
+    pub variable: &'g str,
+    pub lookahead: Vec<&'g str>,
+    pub rules: Vec<&'g ProductionRule>,
+}
+impl fmt::Display for LlConflict<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rules: Vec<String> = self.rules.iter().map(|rule| format_rule(rule)).collect();
+        write!(f, "{} on {}: {}", self.variable, self.lookahead.join(" "), rules.join(" or "))
+    }
+}
+
+/// Builds the LL(1) table of a grammar. A rule `<A> ::= α` goes in the entries of FIRST(α),
+/// and also in those of FOLLOW(<A>) if α can derive the empty string.
+pub fn ll1_table(grammar: &ContextFreeGrammar) -> LlTable<'_> {
+    let sets = GrammarSets::new(grammar);
+
+    let mut entries: IndexMap<&str, IndexMap<Vec<&str>, Vec<&ProductionRule>>> =
+        grammar.variables().iter().map(|variable| (variable.as_str(), IndexMap::new())).collect();
+    for rule in grammar.production_rules() {
+        let (first, nullable) = sets.first_of_sequence(&rule.rhs);
+        let mut lookaheads = first;
+        if nullable {
+            lookaheads.extend(sets.follow[rule.lhs.as_str()].iter().copied());
+        }
+
+        let row = entries.get_mut(rule.lhs.as_str()).expect("every lhs is a variable");
+        for lookahead in lookaheads {
+            row.entry(vec![lookahead]).or_default().push(rule);
+        }
+    }
+
+    LlTable { entries }
+}
+
+/// Builds the strong LL(k) table of a grammar, which puts a rule `<A> ::= α` in the entries of
+/// FIRST_k(α FOLLOW_k(<A>)). For k = 1 this is the LL(1) table; for larger k, a grammar without
+/// conflicts is strong LL(k), and so LL(k).
+pub fn llk_table(grammar: &ContextFreeGrammar, k: usize) -> LlTable<'_> {
+    let sets = LookaheadSets::new(grammar, k);
+
+    let mut entries: IndexMap<&str, IndexMap<Vec<&str>, Vec<&ProductionRule>>> =
+        grammar.variables().iter().map(|variable| (variable.as_str(), IndexMap::new())).collect();
+    for rule in grammar.production_rules() {
+        let row = entries.get_mut(rule.lhs.as_str()).expect("every lhs is a variable");
+        for lookahead in sets.follow_of_sequence(&rule.rhs, &rule.lhs) {
+            row.entry(lookahead).or_default().push(rule);
+        }
+    }
+
+    LlTable { entries }
+}
+
+/// Returns the smallest k up to `max_k` for which the grammar's LL(k) table has no conflicts,
+/// or the conflicts that remain with `max_k` tokens of lookahead.
+pub fn minimum_k(grammar: &ContextFreeGrammar, max_k: usize) -> Result<usize, Vec<LlConflict<'_>>> {
+    let mut conflicts = Vec::new();
+    for k in 1..=max_k {
+        conflicts = llk_table(grammar, k).conflicts();
+        if conflicts.is_empty() {
+            return Ok(k);
+        }
+    }
+    Err(conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+
+    #[test]
+    fn test_ll1_table() {
+        let grammar =
+            build_grammar("<E> ::= <T> <E'>\n<E'> ::= + <T> <E'> | ε\n<T> ::= id | ( <E> )").unwrap();
+        let table = ll1_table(&grammar);
+        assert!(table.conflicts().is_empty());
+        assert_eq!(table.rule("<E'>", &["$"]), Some(&ProductionRule::new("<E'>", &[])));
+        assert_eq!(table.rule("<E'>", &["+"]), Some(&ProductionRule::new("<E'>", &["+", "<T>", "<E'>"])));
+        assert_eq!(table.rule("<T>", &["+"]), None);
+    }
+
+    #[test]
+    fn test_ll1_conflicts() {
+        let grammar = build_grammar("<E> ::= <E> + id | id").unwrap();
+        let conflicts = ll1_table(&grammar).conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].to_string(), "<E> on id: <E> ::= <E> + id or <E> ::= id");
+    }
+
+    #[test]
+    fn test_llk_table() {
+        let grammar = build_grammar("<S> ::= a <B> | a a c\n<B> ::= b | a d").unwrap();
+        assert_eq!(minimum_k(&grammar, 3), Ok(3));
+        let table = llk_table(&grammar, 3);
+        assert_eq!(table.rule("<S>", &["a", "b", "$"]), Some(&ProductionRule::new("<S>", &["a", "<B>"])));
+        assert_eq!(table.rule("<S>", &["a", "a", "c"]), Some(&ProductionRule::new("<S>", &["a", "a", "c"])));
+        assert_eq!(table.rule("<B>", &["a", "d", "$"]), Some(&ProductionRule::new("<B>", &["a", "d"])));
+
+        let conflicts = minimum_k(&grammar, 2).unwrap_err();
+        assert_eq!(conflicts[0].to_string(), "<S> on a a: <S> ::= a <B> or <S> ::= a a c");
+
+        // the LL(1) table is the LL(k) table with k = 1
+        let grammar = build_grammar("<E> ::= <T> <E'>\n<E'> ::= + <T> <E'> | ε\n<T> ::= id | ( <E> )").unwrap();
+        assert_eq!(llk_table(&grammar, 1).entries, ll1_table(&grammar).entries);
+        // left recursion needs unbounded lookahead
+        assert!(minimum_k(&build_grammar("<E> ::= <E> + id | id").unwrap(), 4).is_err());
+    }
+}
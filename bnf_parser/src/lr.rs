@@ -0,0 +1,340 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::context_free_grammar::{format_rule, ContextFreeGrammar, ProductionRule, SymbolId};
+use crate::normal_form::FreshNames;
+use crate::sets::{GrammarSets, END_MARKER};
+
+/// Which lookahead an LR(1) family parser decides its reductions with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LrKind {
+    /// LR(0) states, reducing on the FOLLOW set of the rule's lhs.
+    Slr,
+    /// LR(1) states with the same items merged, reducing on their merged lookaheads.
+    Lalr,
+    /// Canonical LR(1) states, reducing on their exact lookaheads.
+    Lr1,
+}
+impl fmt::Display for LrKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LrKind::Slr => write!(f, "SLR(1)"),
+            LrKind::Lalr => write!(f, "LALR(1)"),
+            LrKind::Lr1 => write!(f, "LR(1)"),
+        }
+    }
+}
+
+/// An entry of an LR action table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Shifts the lookahead and moves to a state.
+    Shift(usize),
+    /// Reduces by a rule, given as its index in the augmented grammar.
+    Reduce(usize),
+    Accept,
+}
+
+/// An LR parse table for a grammar augmented with a new start rule `<S'> ::= <S>`, the first rule.
+pub struct LrTable {
+    pub kind: LrKind,
+    pub grammar: ContextFreeGrammar,
+    /// The actions of each state by lookahead terminal, or `$` for the end of the input.
+    pub actions: Vec<IndexMap<String, Vec<Action>>>,
+    /// The state to go to after reducing to each variable, by state.
+    pub gotos: Vec<IndexMap<String, usize>>,
+}
+impl LrTable {
+    /// Builds the parse table of a grammar.
+    pub fn new(grammar: &ContextFreeGrammar, kind: LrKind) -> LrTable {
+        let grammar = augment(grammar);
+        let canonical = canonical_states(&grammar);
+        let states = match kind {
+            LrKind::Slr | LrKind::Lalr => merge_cores(&canonical),
+            LrKind::Lr1 => canonical,
+        };
+
+        let sets = GrammarSets::new(&grammar);
+        let terminal_name = |lookahead: usize| lookahead_name(&grammar, lookahead).to_string();
+        let mut actions = Vec::new();
+        let mut gotos = Vec::new();
+        for state in &states {
+            let mut state_actions: IndexMap<String, Vec<Action>> = IndexMap::new();
+            let mut add = |lookahead: String, action: Action| {
+                let entry = state_actions.entry(lookahead).or_default();
+                if !entry.contains(&action) {
+                    entry.push(action);
+                }
+            };
+
+            let mut state_gotos = IndexMap::new();
+            for (&symbol, &target) in &state.transitions {
+                if grammar.is_variable_id(symbol) {
+                    state_gotos.insert(grammar.symbol(symbol).to_string(), target);
+                } else {
+                    add(grammar.symbol(symbol).to_string(), Action::Shift(target));
+                }
+            }
+
+            for ((rule, dot), lookaheads) in &state.items {
+                let indexed = &grammar.indexed_rules()[*rule];
+                if *dot < indexed.rhs.len() {
+                    continue;
+                }
+                if *rule == 0 {
+                    add(END_MARKER.to_string(), Action::Accept);
+                    continue;
+                }
+                match kind {
+                    LrKind::Slr => {
+                        for follow in &sets.follow[grammar.symbol(indexed.lhs)] {
+                            add(follow.to_string(), Action::Reduce(*rule));
+                        }
+                    }
+                    LrKind::Lalr | LrKind::Lr1 => {
+                        for &lookahead in lookaheads {
+                            add(terminal_name(lookahead), Action::Reduce(*rule));
+                        }
+                    }
+                }
+            }
+
+            actions.push(state_actions);
+            gotos.push(state_gotos);
+        }
+
+        LrTable { kind, grammar, actions, gotos }
+    }
+
+    /// Returns the rule with an index in the augmented grammar.
+    pub fn rule(&self, index: usize) -> &ProductionRule {
+        &self.grammar.production_rules()[index]
+    }
+
+    /// Returns the entries with more than one action, which keep the grammar from being in this LR class.
+    pub fn conflicts(&self) -> Vec<LrConflict> {
+        let mut conflicts = Vec::new();
+        for (state, state_actions) in self.actions.iter().enumerate() {
+            for (lookahead, actions) in state_actions {
+                if actions.len() < 2 {
+                    continue;
+                }
+                let choices = actions
+                    .iter()
+                    .map(|action| match action {
+                        Action::Shift(target) => format!("shift to state {}", target),
+                        Action::Reduce(rule) => format!("reduce {}", format_rule(self.rule(*rule))),
+                        Action::Accept => "accept".to_string(),
+                    })
+                    .collect();
+                let shifts = actions.iter().any(|action| matches!(action, Action::Shift(_)));
+                conflicts.push(LrConflict {
+                    state,
+                    lookahead: lookahead.clone(),
+                    kind: if shifts { "shift/reduce" } else { "reduce/reduce" },
+                    choices,
+                });
+            }
+        }
+        conflicts
+    }
+}
+
+/// Actions an LR parser can't choose between in a state on a lookahead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LrConflict {
+    pub state: usize,
+    pub lookahead: String,
+    /// `shift/reduce` or `reduce/reduce`.
+    pub kind: &'static str,
+    /// Each competing action, described like `reduce <E> ::= <E> + <E>`.
+    pub choices: Vec<String>,
+}
+impl fmt::Display for LrConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} conflict in state {} on {}: {}", self.kind, self.state, self.lookahead, self.choices.join(" or "))
+    }
+}
+
+/// Returns the grammar with a new start variable whose only rule, the first, derives the old start symbol.
+fn augment(grammar: &ContextFreeGrammar) -> ContextFreeGrammar {
+    let start = grammar.start_symbol();
+    let augmented_start = FreshNames::new(grammar).fresh(start.trim_start_matches('<').trim_end_matches('>'));
+
+    let rules = std::iter::once(ProductionRule::new(&augmented_start, &[start]))
+        .chain(grammar.production_rules().iter().cloned());
+    grammar.with_rules(&augmented_start, rules)
+}
+
+/// A state of an LR automaton: its items by (rule, dot) with their lookaheads, and its transitions.
+#[derive(Debug, Clone)]
+struct State {
+    items: BTreeMap<(usize, usize), BTreeSet<usize>>,
+    transitions: BTreeMap<SymbolId, usize>,
+}
+
+/// An LR(1) item: a rule, the position of the dot in its rhs and a lookahead, where lookahead 0 is `$`
+/// and lookahead `1 + t` is the terminal `terminals()[t]`.
+type Item = (usize, usize, usize);
+
+/// Builds the canonical collection of LR(1) states of an augmented grammar.
+fn canonical_states(grammar: &ContextFreeGrammar) -> Vec<State> {
+    let sets = GrammarSets::new(grammar);
+
+    // FIRST of what follows the symbol after each dot, as lookaheads, and whether it is nullable
+    let mut after_dot: HashMap<(usize, usize), (BTreeSet<usize>, bool)> = HashMap::new();
+    for (index, rule) in grammar.indexed_rules().iter().enumerate() {
+        for dot in 0..rule.rhs.len() {
+            let rest: Vec<&str> = rule.rhs[dot + 1..].iter().map(|&symbol| grammar.symbol(symbol)).collect();
+            let (first, nullable) = sets.first_of_sequence(&rest);
+            let first = first.iter().map(|terminal| lookahead_index(grammar, terminal)).collect();
+            after_dot.insert((index, dot), (first, nullable));
+        }
+    }
+
+    let closure = |kernel: &BTreeSet<Item>| -> BTreeSet<Item> {
+        let mut items = kernel.clone();
+        let mut worklist: Vec<Item> = kernel.iter().copied().collect();
+        while let Some((rule, dot, lookahead)) = worklist.pop() {
+            let Some(&symbol) = grammar.indexed_rules()[rule].rhs.get(dot) else {
+                continue;
+            };
+            if !grammar.is_variable_id(symbol) {
+                continue;
+            }
+
+            let (first, nullable) = &after_dot[&(rule, dot)];
+            let lookaheads = first.iter().copied().chain(nullable.then_some(lookahead));
+            for lookahead in lookaheads {
+                for &expanded in grammar.rule_indices_for(symbol) {
+                    if items.insert((expanded, 0, lookahead)) {
+                        worklist.push((expanded, 0, lookahead));
+                    }
+                }
+            }
+        }
+        items
+    };
+
+    let start_kernel = BTreeSet::from([(0, 0, 0)]);
+    let mut kernels: Vec<BTreeSet<Item>> = vec![start_kernel.clone()];
+    let mut ids: HashMap<BTreeSet<Item>, usize> = HashMap::from([(start_kernel, 0)]);
+    let mut states = Vec::new();
+
+    let mut index = 0;
+    while index < kernels.len() {
+        let items = closure(&kernels[index]);
+
+        let mut moves: BTreeMap<SymbolId, BTreeSet<Item>> = BTreeMap::new();
+        for &(rule, dot, lookahead) in &items {
+            if let Some(&symbol) = grammar.indexed_rules()[rule].rhs.get(dot) {
+                moves.entry(symbol).or_default().insert((rule, dot + 1, lookahead));
+            }
+        }
+        let mut transitions = BTreeMap::new();
+        for (symbol, kernel) in moves {
+            let target = *ids.entry(kernel.clone()).or_insert_with(|| {
+                kernels.push(kernel);
+                kernels.len() - 1
+            });
+            transitions.insert(symbol, target);
+        }
+
+        let mut grouped: BTreeMap<(usize, usize), BTreeSet<usize>> = BTreeMap::new();
+        for (rule, dot, lookahead) in items {
+            grouped.entry((rule, dot)).or_default().insert(lookahead);
+        }
+        states.push(State { items: grouped, transitions });
+        index += 1;
+    }
+
+    states
+}
+
+/// Merges the LR(1) states with the same items, ignoring lookaheads, into LALR(1) states.
+/// Without their lookaheads these are the LR(0) states.
+fn merge_cores(states: &[State]) -> Vec<State> {
+    let mut merged_ids: HashMap<Vec<(usize, usize)>, usize> = HashMap::new();
+    let mut merged_of = Vec::new();
+    let mut merged: Vec<State> = Vec::new();
+
+    for state in states {
+        let core: Vec<(usize, usize)> = state.items.keys().copied().collect();
+        let id = *merged_ids.entry(core).or_insert_with(|| {
+            merged.push(State { items: BTreeMap::new(), transitions: BTreeMap::new() });
+            merged.len() - 1
+        });
+        merged_of.push(id);
+        for (item, lookaheads) in &state.items {
+            merged[id].items.entry(*item).or_default().extend(lookaheads);
+        }
+    }
+
+    for (state, &id) in states.iter().zip(&merged_of) {
+        for (&symbol, &target) in &state.transitions {
+            merged[id].transitions.insert(symbol, merged_of[target]);
+        }
+    }
+
+    merged
+}
+
+fn lookahead_index(grammar: &ContextFreeGrammar, terminal: &str) -> usize {
+    if terminal == END_MARKER {
+        0
+    } else {
+        1 + grammar.terminals().get_index_of(terminal).expect("lookaheads are terminals")
+    }
+}
+
+fn lookahead_name(grammar: &ContextFreeGrammar, lookahead: usize) -> &str {
+    if lookahead == 0 {
+        END_MARKER
+    } else {
+        &grammar.terminals()[lookahead - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+
+    fn conflicts(bnf: &str, kind: LrKind) -> Vec<String> {
+        let grammar = build_grammar(bnf).unwrap();
+        LrTable::new(&grammar, kind).conflicts().iter().map(|conflict| conflict.to_string()).collect()
+    }
+
+    #[test]
+    fn test_slr_table() {
+        let grammar = build_grammar("<E> ::= <E> + <T> | <T>\n<T> ::= id | ( <E> )").unwrap();
+        let table = LrTable::new(&grammar, LrKind::Slr);
+        assert!(table.conflicts().is_empty());
+        assert_eq!(table.rule(0), &ProductionRule::new("<E'>", &["<E>"]));
+        assert_eq!(table.actions.len(), 9);
+        assert_eq!(table.actions[0]["id"], [Action::Shift(3)]);
+        assert_eq!(table.gotos[0]["<E>"], 1);
+        assert_eq!(table.actions[1]["$"], [Action::Accept]);
+    }
+
+    #[test]
+    fn test_ambiguous_grammar_conflicts() {
+        let conflicts = conflicts("<E> ::= <E> + <E> | id", LrKind::Lr1);
+        assert_eq!(conflicts, ["shift/reduce conflict in state 4 on +: shift to state 3 or reduce <E> ::= <E> + <E>"]);
+    }
+
+    #[test]
+    fn test_lr_classes() {
+        // LALR(1) but not SLR(1)
+        let lalr = "<S> ::= <L> = <R> | <R>\n<L> ::= * <R> | id\n<R> ::= <L>";
+        assert_eq!(conflicts(lalr, LrKind::Slr).len(), 1);
+        assert!(conflicts(lalr, LrKind::Lalr).is_empty());
+
+        // LR(1) but not LALR(1)
+        let lr1 = "<S> ::= a <A> d | b <B> d | a <B> e | b <A> e\n<A> ::= c\n<B> ::= c";
+        assert_eq!(conflicts(lr1, LrKind::Lalr).len(), 2);
+        assert!(conflicts(lr1, LrKind::Lr1).is_empty());
+    }
+}
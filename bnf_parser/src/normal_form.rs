@@ -0,0 +1,312 @@
+use std::collections::HashSet;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::context_free_grammar::{remove_start_on_rhs, ContextFreeGrammar, GrammarError, ProductionRule};
+use crate::sets::nullable_variables;
+
+/// Computes the set of variables that can derive some string of terminals.
+pub fn generating_variables(grammar: &ContextFreeGrammar) -> HashSet<&str> {
+    let mut generating: HashSet<&str> = HashSet::new();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for rule in grammar.production_rules() {
+            if !generating.contains(rule.lhs.as_str())
+                && rule.rhs.iter().all(|symbol| !grammar.is_variable(symbol) || generating.contains(symbol.as_str()))
+            {
+                generating.insert(rule.lhs.as_str());
+                changed = true;
+            }
+        }
+    }
+
+    generating
+}
+
+/// Computes the set of variables that appear in some sentential form derived from the start symbol.
+pub fn reachable_variables(grammar: &ContextFreeGrammar) -> IndexSet<&str> {
+    let mut reachable: IndexSet<&str> = IndexSet::new();
+    reachable.insert(grammar.start_symbol());
+
+    let mut index = 0;
+    while let Some(&variable) = reachable.get_index(index) {
+        for rule in grammar.rules_for(variable) {
+            for symbol in &rule.rhs {
+                if grammar.is_variable(symbol) {
+                    reachable.insert(symbol.as_str());
+                }
+            }
+        }
+        index += 1;
+    }
+
+    reachable
+}
+
+/// Returns an error for the first variable reachable from the start symbol that has no rules,
+/// which a parser could never finish parsing.
+pub fn check_reachable_rules(grammar: &ContextFreeGrammar) -> Result<(), GrammarError> {
+    match reachable_variables(grammar).iter().find(|variable| grammar.rules_for(variable).next().is_none()) {
+        Some(variable) => Err(GrammarError::new(&format!("{} has no rules", variable), None)),
+        None => Ok(()),
+    }
+}
+
+/// Removes useless rules: first those using variables that can't derive a string of terminals,
+/// then those of variables that can't be reached from the start symbol.
+pub fn reduce(grammar: &ContextFreeGrammar) -> ContextFreeGrammar {
+    let generating = generating_variables(grammar);
+    let productive = grammar.with_rules(
+        grammar.start_symbol(),
+        grammar
+            .production_rules()
+            .iter()
+            .filter(|rule| {
+                generating.contains(rule.lhs.as_str())
+                    && rule.rhs.iter().all(|symbol| !grammar.is_variable(symbol) || generating.contains(symbol.as_str()))
+            })
+            .cloned(),
+    );
+
+    let reachable = reachable_variables(&productive);
+    productive.with_rules(
+        productive.start_symbol(),
+        productive
+            .production_rules()
+            .iter()
+            .filter(|rule| reachable.contains(rule.lhs.as_str()))
+            .cloned(),
+    )
+}
+
+/// Converts a grammar to Chomsky normal form, where every rule is `<A> ::= <B> <C>` or `<A> ::= a`,
+/// plus `<S> ::= ε` for the start symbol if the language contains the empty string.
+/// Applies the START, TERM, BIN, DEL and UNIT steps in that order and then removes useless rules.
+pub fn to_chomsky_normal_form(grammar: &ContextFreeGrammar) -> ContextFreeGrammar {
+    // START: the start symbol may not appear on a right hand side
+    let mut start = grammar.with_rules(grammar.start_symbol(), grammar.production_rules().iter().cloned());
+    remove_start_on_rhs(&mut start);
+    let start_symbol = start.start_symbol().to_string();
+
+    let mut names = FreshNames::new(&start);
+    let rules = replace_terminals(&start, &mut names);
+    let rules = binarize(rules, &mut names);
+
+    let binarized = start.with_rules(&start_symbol, rules);
+    let rules = remove_epsilon_rules(&binarized);
+
+    let without_epsilon = start.with_rules(&start_symbol, rules);
+    let rules = remove_unit_rules(&without_epsilon);
+
+    reduce(&start.with_rules(&start_symbol, rules))
+}
+
+/// Generates variable names that don't clash with any symbol already in the grammar.
+pub struct FreshNames {
+    taken: HashSet<String>,
+}
+impl FreshNames {
+    pub fn new(grammar: &ContextFreeGrammar) -> FreshNames {
+        FreshNames {
+            taken: grammar.variables().iter().chain(grammar.terminals()).cloned().collect(),
+        }
+    }
+
+    /// Returns `<base>`, adding primes until the name is unused.
+    pub fn fresh(&mut self, base: &str) -> String {
+        let mut name = format!("<{}>", base);
+        while self.taken.contains(&name) {
+            name.insert(name.len() - 1, '\'');
+        }
+        self.taken.insert(name.clone());
+        name
+    }
+}
+
+/// TERM: replaces terminals in right hand sides of two or more symbols with a new variable per terminal.
+fn replace_terminals(grammar: &ContextFreeGrammar, names: &mut FreshNames) -> Vec<ProductionRule> {
+    let mut terminal_variables: IndexMap<String, String> = IndexMap::new();
+    let mut rules = Vec::new();
+
+    for rule in grammar.production_rules() {
+        if rule.rhs.len() < 2 {
+            rules.push(rule.clone());
+            continue;
+        }
+
+        let rhs: Vec<String> = rule
+            .rhs
+            .iter()
+            .map(|symbol| {
+                if grammar.is_variable(symbol) {
+                    symbol.clone()
+                } else {
+                    // token classes are already written like <name>
+                    let base = symbol.trim_matches(|c| c == '<' || c == '>');
+                    terminal_variables.entry(symbol.clone()).or_insert_with(|| names.fresh(base)).clone()
+                }
+            })
+            .collect();
+        rules.push(ProductionRule { rhs, action: None, ..rule.clone() });
+    }
+
+    for (terminal, variable) in terminal_variables {
+        rules.push(ProductionRule::new(&variable, &[&terminal]));
+    }
+
+    rules
+}
+
+/// BIN: splits right hand sides longer than two symbols into a chain of new variables.
+fn binarize(rules: Vec<ProductionRule>, names: &mut FreshNames) -> Vec<ProductionRule> {
+    let mut binarized = Vec::new();
+
+    for rule in rules {
+        if rule.rhs.len() <= 2 {
+            binarized.push(rule);
+            continue;
+        }
+
+        let base = rule.lhs.trim_matches(|c| c == '<' || c == '>').to_string();
+        let mut lhs = rule.lhs.clone();
+        for (index, symbol) in rule.rhs[..rule.rhs.len() - 2].iter().enumerate() {
+            let next = names.fresh(&format!("{}_{}", base, index + 1));
+            binarized.push(ProductionRule::new(&lhs, &[symbol, &next]));
+            lhs = next;
+        }
+        let last_two = &rule.rhs[rule.rhs.len() - 2..];
+        binarized.push(ProductionRule::new(&lhs, &[&last_two[0], &last_two[1]]));
+    }
+
+    binarized
+}
+
+/// DEL: removes epsilon rules by adding a copy of every rule with each combination of
+/// nullable variables left out. Only the start symbol keeps an epsilon rule.
+fn remove_epsilon_rules(grammar: &ContextFreeGrammar) -> Vec<ProductionRule> {
+    let nullable = nullable_variables(grammar);
+    let mut rules: IndexSet<ProductionRule> = IndexSet::new();
+
+    for rule in grammar.production_rules() {
+        let mut variants: Vec<Vec<String>> = vec![Vec::new()];
+        for symbol in &rule.rhs {
+            let mut next = Vec::new();
+            for variant in &variants {
+                let mut with_symbol = variant.clone();
+                with_symbol.push(symbol.clone());
+                next.push(with_symbol);
+                if nullable.contains(symbol.as_str()) {
+                    next.push(variant.clone());
+                }
+            }
+            variants = next;
+        }
+
+        for rhs in variants {
+            if !rhs.is_empty() {
+                rules.insert(ProductionRule { lhs: rule.lhs.clone(), rhs, span: None, action: None });
+            }
+        }
+    }
+
+    if nullable.contains(grammar.start_symbol()) {
+        rules.insert(ProductionRule::new(grammar.start_symbol(), &[]));
+    }
+
+    rules.into_iter().collect()
+}
+
+/// UNIT: replaces rules `<A> ::= <B>` with the non-unit rules of every variable `<A>` reaches through unit rules.
+fn remove_unit_rules(grammar: &ContextFreeGrammar) -> Vec<ProductionRule> {
+    let is_unit = |rule: &ProductionRule| rule.rhs.len() == 1 && grammar.is_variable(&rule.rhs[0]);
+    let mut rules: IndexSet<ProductionRule> = IndexSet::new();
+
+    for variable in grammar.variables() {
+        let mut reached: IndexSet<&str> = IndexSet::new();
+        reached.insert(variable.as_str());
+
+        let mut index = 0;
+        while let Some(&current) = reached.get_index(index) {
+            for rule in grammar.rules_for(current) {
+                if is_unit(rule) {
+                    reached.insert(rule.rhs[0].as_str());
+                } else {
+                    rules.insert(ProductionRule {
+                        lhs: variable.clone(),
+                        rhs: rule.rhs.clone(),
+                        span: None,
+                        action: rule.action.clone(),
+                    });
+                }
+            }
+            index += 1;
+        }
+    }
+
+    rules.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+    use crate::equivalence::{check_equivalence, Equivalence};
+    use crate::generator::Rng;
+
+    fn is_in_cnf(grammar: &ContextFreeGrammar) -> bool {
+        grammar.production_rules().iter().all(|rule| match rule.rhs.as_slice() {
+            [] => rule.lhs == grammar.start_symbol(),
+            [terminal] => !grammar.is_variable(terminal),
+            [first, second] => {
+                grammar.is_variable(first)
+                    && grammar.is_variable(second)
+                    && first != grammar.start_symbol()
+                    && second != grammar.start_symbol()
+            }
+            _ => false,
+        })
+    }
+
+    #[test]
+    fn test_reduce() {
+        let grammar = build_grammar("<S> ::= a <A> | b\n<A> ::= <A> a\n<B> ::= b").unwrap();
+
+        let expected = build_grammar("<S> ::= b").unwrap();
+
+        assert_eq!(reduce(&grammar), expected);
+    }
+
+    #[test]
+    fn test_chomsky_normal_form() {
+        let grammar = build_grammar(
+            "<S> ::= <A> <S> <A> | a <B>
+             <A> ::= <B> | <S>
+             <B> ::= b | ε",
+        )
+        .unwrap();
+
+        let cnf = to_chomsky_normal_form(&grammar);
+
+        assert!(is_in_cnf(&cnf));
+        assert!(matches!(
+            check_equivalence(&grammar, &cnf, 5, 50, Rng::new(4)),
+            Equivalence::Agree { .. }
+        ));
+    }
+
+    #[test]
+    fn test_chomsky_normal_form_keeps_empty_string() {
+        let grammar = build_grammar("<S> ::= ( <S> ) <S> | ε").unwrap();
+
+        let cnf = to_chomsky_normal_form(&grammar);
+
+        assert!(is_in_cnf(&cnf));
+        assert!(cnf.production_rules().contains(&ProductionRule::new(cnf.start_symbol(), &[])));
+        assert!(matches!(
+            check_equivalence(&grammar, &cnf, 6, 50, Rng::new(4)),
+            Equivalence::Agree { .. }
+        ));
+    }
+}
@@ -0,0 +1,261 @@
+use serde::Serialize;
+
+use crate::context_free_grammar::{Span, EPSILON};
+use crate::lexer::Token;
+
+/// A parse tree (derivation tree) of a sentence.
+/// Variables use the `<...>` naming convention, terminals are the input words they matched
+/// along with where they were found in the input. Terminals of token classes also keep the text they matched.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ParseTree {
+    Variable {
+        name: String,
+        children: Vec<ParseTree>,
+    },
+    Terminal {
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        span: Option<Span>,
+    },
+    /// Where a parser recovering from a syntax error expected something else, holding the tokens and
+    /// partial subtrees it gave up on, if any.
+    Error {
+        expected: Vec<String>,
+        children: Vec<ParseTree>,
+    },
+}
+impl ParseTree {
+    /// Returns a terminal node for a token.
+    pub fn token(token: &Token) -> ParseTree {
+        ParseTree::Terminal {
+            name: token.terminal.clone(),
+            text: (token.text != token.terminal).then(|| token.text.clone()),
+            span: Some(token.span),
+        }
+    }
+
+    /// Returns the variable or terminal at the root of the tree, or `error` for an error node.
+    pub fn name(&self) -> &str {
+        match self {
+            ParseTree::Variable { name, .. } | ParseTree::Terminal { name, .. } => name,
+            ParseTree::Error { .. } => "error",
+        }
+    }
+
+    /// Returns the children of a variable or error node.
+    fn children(&self) -> Option<&[ParseTree]> {
+        match self {
+            ParseTree::Variable { children, .. } | ParseTree::Error { children, .. } => Some(children),
+            ParseTree::Terminal { .. } => None,
+        }
+    }
+
+    /// Returns the text a node is drawn with, adding the matched text to terminals of token classes
+    /// and what was expected to error nodes.
+    fn label(&self) -> String {
+        match self {
+            ParseTree::Terminal { name, text: Some(text), .. } => format!("{} \"{}\"", name, text),
+            ParseTree::Error { expected, .. } => format!("error: expected {}", expected.join(" or ")),
+            _ => self.name().to_string(),
+        }
+    }
+
+    /// Sets the spans of the tree's terminals from the tokens they were parsed from, in order.
+    /// Terminals matched by a token class also get the text they matched.
+    pub fn attach_spans(&mut self, tokens: &[Token]) {
+        let mut tokens = tokens.iter();
+        self.attach_spans_from(&mut tokens);
+    }
+
+    fn attach_spans_from<'t>(&mut self, tokens: &mut impl Iterator<Item = &'t Token>) {
+        match self {
+            ParseTree::Variable { children, .. } | ParseTree::Error { children, .. } => {
+                for child in children {
+                    child.attach_spans_from(tokens);
+                }
+            }
+            ParseTree::Terminal { name, text, span } => {
+                let token = tokens.next();
+                *span = token.map(|token| token.span);
+                *text = token.filter(|token| token.text != *name).map(|token| token.text.clone());
+            }
+        }
+    }
+
+    /// Renders the tree with box-drawing characters, one node per line.
+    pub fn to_ascii(&self) -> String {
+        let mut output = format!("{}\n", self.label());
+        if let Some(children) = self.children() {
+            render_children(self, children, "", &mut output);
+        }
+        output
+    }
+
+    /// Renders the tree as a Graphviz dot graph. Variables are drawn as ellipses, terminals as boxes and errors as
+    /// red octagons.
+    pub fn to_dot(&self) -> String {
+        let mut output = String::from("digraph parse_tree {\n    ordering=out;\n");
+        let mut next_id = 0;
+        render_dot(self, &mut next_id, &mut output);
+        output.push_str("}\n");
+        output
+    }
+
+    /// Renders the tree as pretty-printed JSON, including the input spans of terminals.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Adds a node and its subtree to a dot graph, returning the node's id.
+fn render_dot(tree: &ParseTree, next_id: &mut usize, output: &mut String) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+
+    match tree {
+        ParseTree::Terminal { .. } => {
+            output.push_str(&format!("    n{} [label=\"{}\", shape=box];\n", id, escape_dot(&tree.label())));
+        }
+        ParseTree::Variable { name, children } => {
+            output.push_str(&format!("    n{} [label=\"{}\", shape=ellipse];\n", id, escape_dot(name)));
+
+            if children.is_empty() {
+                let epsilon_id = *next_id;
+                *next_id += 1;
+                output.push_str(&format!("    n{} [label=\"{}\", shape=plaintext];\n", epsilon_id, EPSILON));
+                output.push_str(&format!("    n{} -> n{};\n", id, epsilon_id));
+            }
+            for child in children {
+                let child_id = render_dot(child, next_id, output);
+                output.push_str(&format!("    n{} -> n{};\n", id, child_id));
+            }
+        }
+        ParseTree::Error { children, .. } => {
+            let label = escape_dot(&tree.label());
+            output.push_str(&format!("    n{} [label=\"{}\", shape=octagon, color=red];\n", id, label));
+            for child in children {
+                let child_id = render_dot(child, next_id, output);
+                output.push_str(&format!("    n{} -> n{};\n", id, child_id));
+            }
+        }
+    }
+
+    id
+}
+
+/// Escapes a label for use inside a double quoted dot string.
+pub fn escape_dot(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Renders the children of a node below it, indented by `prefix`.
+fn render_children(parent: &ParseTree, children: &[ParseTree], prefix: &str, output: &mut String) {
+    if children.is_empty() && matches!(parent, ParseTree::Variable { .. }) {
+        output.push_str(&format!("{}└── {}\n", prefix, EPSILON));
+        return;
+    }
+
+    for (index, child) in children.iter().enumerate() {
+        let last = index == children.len() - 1;
+        let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+
+        output.push_str(&format!("{}{}{}\n", prefix, branch, child.label()));
+        if let Some(children) = child.children() {
+            render_children(child, children, &format!("{}{}", prefix, indent), output);
+        }
+    }
+}
+
+/// Lays out several rendered blocks of text next to each other in columns.
+pub fn side_by_side(blocks: &[String], gap: usize) -> String {
+    let columns: Vec<Vec<&str>> = blocks.iter().map(|block| block.lines().collect()).collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .map(|lines| lines.iter().map(|line| line.chars().count()).max().unwrap_or(0))
+        .collect();
+    let height = columns.iter().map(|lines| lines.len()).max().unwrap_or(0);
+
+    let mut output = String::new();
+    for row in 0..height {
+        let mut line = String::new();
+        for (column, lines) in columns.iter().enumerate() {
+            let cell = lines.get(row).copied().unwrap_or("");
+            line.push_str(cell);
+            if column < columns.len() - 1 {
+                let padding = widths[column] - cell.chars().count() + gap;
+                line.push_str(&" ".repeat(padding));
+            }
+        }
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(name: &str, children: Vec<ParseTree>) -> ParseTree {
+        ParseTree::Variable { name: name.to_string(), children }
+    }
+
+    fn terminal(name: &str) -> ParseTree {
+        ParseTree::Terminal { name: name.to_string(), text: None, span: None }
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        let tree = variable(
+            "<E>",
+            vec![variable("<E>", vec![terminal("id")]), terminal("+"), variable("<T>", vec![])],
+        );
+
+        let expected = "<E>\n├── <E>\n│   └── id\n├── +\n└── <T>\n    └── ε\n";
+
+        assert_eq!(tree.to_ascii(), expected);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let tree = variable("<S>", vec![terminal("\"a\""), variable("<T>", vec![])]);
+
+        let expected = "digraph parse_tree {
+    ordering=out;
+    n0 [label=\"<S>\", shape=ellipse];
+    n1 [label=\"\\\"a\\\"\", shape=box];
+    n0 -> n1;
+    n2 [label=\"<T>\", shape=ellipse];
+    n3 [label=\"ε\", shape=plaintext];
+    n2 -> n3;
+    n0 -> n2;
+}
+";
+
+        assert_eq!(tree.to_dot(), expected);
+    }
+
+    #[test]
+    fn test_to_json_with_spans() {
+        let mut tree = variable("<S>", vec![terminal("a"), variable("<T>", vec![terminal("b")])]);
+        tree.attach_spans(&crate::lexer::split_words("a  b"));
+
+        let json: serde_json::Value = serde_json::from_str(&tree.to_json().unwrap()).unwrap();
+
+        assert_eq!(json["kind"], "variable");
+        assert_eq!(json["children"][0]["kind"], "terminal");
+        assert_eq!(json["children"][1]["children"][0]["name"], "b");
+        assert_eq!(json["children"][1]["children"][0]["span"]["start"], 3);
+    }
+
+    #[test]
+    fn test_side_by_side() {
+        let blocks = vec!["a\nbbb".to_string(), "cc\nd\ne".to_string()];
+
+        assert_eq!(side_by_side(&blocks, 2), "a    cc\nbbb  d\n     e\n");
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::{HashSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+
+use indexmap::IndexSet;
+
+use crate::context_free_grammar::{ContextFreeGrammar, EPSILON};
+
+/// How a pushdown automaton accepts its input once all of it has been read.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Acceptance {
+    EmptyStack,
+    FinalStates(IndexSet<String>),
+}
+
+/// A transition `from input pop -> to push`. Without an input symbol the transition reads nothing,
+/// and without a pop symbol it doesn't look at the stack. The first symbol pushed ends up on top.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub from: String,
+    pub input: Option<String>,
+    pub pop: Option<String>,
+    pub to: String,
+    pub push: Vec<String>,
+}
+
+/// A nondeterministic pushdown automaton.
+///
+/// PDA files list the start state, the initial stack (top first), how the machine accepts
+/// and one transition per line, with `ε` for reading, popping or pushing nothing:
+///
+/// ```text
+/// # a^n b^n
+/// start q0
+/// stack Z
+/// accept q2          # or `accept empty` to accept by empty stack
+/// q0 a Z -> q0 A Z
+/// q0 a A -> q0 A A
+/// q0 b A -> q1 ε
+/// q1 b A -> q1 ε
+/// q1 ε Z -> q2 Z
+/// q0 ε Z -> q2 Z
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pda {
+    pub start_state: String,
+    pub initial_stack: Vec<String>,
+    pub acceptance: Acceptance,
+    pub transitions: Vec<Transition>,
+}
+
+/// A configuration of a PDA: its state, how much of the input it has read and its stack, top last.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    pub state: String,
+    pub position: usize,
+    pub stack: Vec<String>,
+}
+impl Configuration {
+    /// Formats the configuration as `(state, remaining input, stack)` with the top of the stack first.
+    pub fn format(&self, input: &[String]) -> String {
+        let or_epsilon = |symbols: Vec<&str>| if symbols.is_empty() { EPSILON.to_string() } else { symbols.join(" ") };
+        let remaining = or_epsilon(input[self.position..].iter().map(|s| s.as_str()).collect());
+        let stack = or_epsilon(self.stack.iter().rev().map(|s| s.as_str()).collect());
+        format!("({}, {}, {})", self.state, remaining, stack)
+    }
+}
+
+/// Result of running a PDA on an input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Run {
+    /// The configurations of a shortest accepting run, from the initial one to the accepting one.
+    Accepted(Vec<Configuration>),
+    /// Every reachable configuration was explored without accepting.
+    Rejected { explored: usize },
+    /// The search stopped after exploring the maximum number of configurations.
+    GaveUp { explored: usize },
+}
+
+/// Error in a PDA file, with the line it was found on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdaError {
+    pub message: String,
+    pub line: usize,
+}
+impl fmt::Display for PdaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+impl Error for PdaError {}
+
+impl Pda {
+    /// Builds the single-state PDA of a grammar that accepts by empty stack. Its stack starts with the
+    /// start symbol, a variable on top is replaced by the rhs of one of its rules and a terminal on top
+    /// is popped when it matches the next input symbol, so accepting runs follow leftmost derivations.
+    pub fn from_grammar(grammar: &ContextFreeGrammar) -> Pda {
+        let transition = |input: Option<&str>, pop: &str, push: &[String]| Transition {
+            from: "q".to_string(),
+            input: input.map(|input| input.to_string()),
+            pop: Some(pop.to_string()),
+            to: "q".to_string(),
+            push: push.to_vec(),
+        };
+
+        let mut transitions: Vec<Transition> =
+            grammar.production_rules().iter().map(|rule| transition(None, &rule.lhs, &rule.rhs)).collect();
+        transitions.extend(grammar.terminals().iter().map(|terminal| transition(Some(terminal), terminal, &[])));
+
+        Pda {
+            start_state: "q".to_string(),
+            initial_stack: vec![grammar.start_symbol().to_string()],
+            acceptance: Acceptance::EmptyStack,
+            transitions,
+        }
+    }
+
+    /// Reads a PDA in the file format described on `Pda`.
+    pub fn parse(text: &str) -> Result<Pda, PdaError> {
+        let mut start_state = None;
+        let mut initial_stack = Vec::new();
+        let mut acceptance = None;
+        let mut transitions = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: &str| PdaError { message: message.to_string(), line: index + 1 };
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            match words.as_slice() {
+                [] => {}
+                ["start", state] => start_state = Some(state.to_string()),
+                ["start", ..] => return Err(error("expected a single state after 'start'")),
+                ["stack", symbols @ ..] => initial_stack = symbols_of(symbols),
+                ["accept", "empty"] => acceptance = Some(Acceptance::EmptyStack),
+                ["accept", states @ ..] => {
+                    acceptance = Some(Acceptance::FinalStates(states.iter().map(|s| s.to_string()).collect()))
+                }
+                _ => {
+                    let Some(arrow) = words.iter().position(|word| *word == "->") else {
+                        return Err(error("expected a transition like 'q0 a Z -> q1 A Z'"));
+                    };
+                    let [from, input, pop] = words[..arrow] else {
+                        return Err(error("expected a state, an input symbol and a stack symbol before '->'"));
+                    };
+                    let Some((to, push)) = words[arrow + 1..].split_first() else {
+                        return Err(error("expected a state after '->'"));
+                    };
+                    let symbol = |symbol: &str| (symbol != EPSILON).then(|| symbol.to_string());
+                    transitions.push(Transition {
+                        from: from.to_string(),
+                        input: symbol(input),
+                        pop: symbol(pop),
+                        to: to.to_string(),
+                        push: symbols_of(push),
+                    });
+                }
+            }
+        }
+
+        let missing = |message: &str| PdaError { message: message.to_string(), line: text.lines().count().max(1) };
+        Ok(Pda {
+            start_state: start_state.ok_or_else(|| missing("missing 'start <state>' line"))?,
+            initial_stack,
+            acceptance: acceptance.ok_or_else(|| missing("missing 'accept <states>' or 'accept empty' line"))?,
+            transitions,
+        })
+    }
+
+    /// Writes the PDA in the file format read by `Pda::parse`.
+    pub fn to_text(&self) -> String {
+        let or_epsilon = |symbols: &[String]| if symbols.is_empty() { EPSILON.to_string() } else { symbols.join(" ") };
+
+        let mut output = format!("start {}\n", self.start_state);
+        if !self.initial_stack.is_empty() {
+            output.push_str(&format!("stack {}\n", self.initial_stack.join(" ")));
+        }
+        match &self.acceptance {
+            Acceptance::EmptyStack => output.push_str("accept empty\n"),
+            Acceptance::FinalStates(states) => {
+                output.push_str(&format!("accept {}\n", states.iter().cloned().collect::<Vec<String>>().join(" ")))
+            }
+        }
+        for transition in &self.transitions {
+            output.push_str(&format!(
+                "{} {} {} -> {} {}\n",
+                transition.from,
+                transition.input.as_deref().unwrap_or(EPSILON),
+                transition.pop.as_deref().unwrap_or(EPSILON),
+                transition.to,
+                or_epsilon(&transition.push)
+            ));
+        }
+
+        output
+    }
+
+    /// Searches breadth first for an accepting run on the input, exploring at most `max_steps` configurations.
+    pub fn run(&self, input: &[String], max_steps: usize) -> Run {
+        let initial = Configuration {
+            state: self.start_state.clone(),
+            position: 0,
+            stack: self.initial_stack.iter().rev().cloned().collect(),
+        };
+
+        // every configuration found so far, with the index of the one it was reached from
+        let mut found: Vec<(Configuration, Option<usize>)> = vec![(initial.clone(), None)];
+        let mut seen: HashSet<Configuration> = HashSet::from([initial]);
+        let mut queue = VecDeque::from([0]);
+        let mut explored = 0;
+
+        while let Some(index) = queue.pop_front() {
+            if explored == max_steps {
+                return Run::GaveUp { explored };
+            }
+            explored += 1;
+
+            let configuration = found[index].0.clone();
+            if self.accepts(&configuration, input) {
+                let mut run = vec![configuration];
+                let mut parent = found[index].1;
+                while let Some(index) = parent {
+                    run.push(found[index].0.clone());
+                    parent = found[index].1;
+                }
+                run.reverse();
+                return Run::Accepted(run);
+            }
+
+            for next in self.successors(&configuration, input) {
+                if seen.insert(next.clone()) {
+                    found.push((next, Some(index)));
+                    queue.push_back(found.len() - 1);
+                }
+            }
+        }
+
+        Run::Rejected { explored }
+    }
+
+    fn accepts(&self, configuration: &Configuration, input: &[String]) -> bool {
+        configuration.position == input.len()
+            && match &self.acceptance {
+                Acceptance::EmptyStack => configuration.stack.is_empty(),
+                Acceptance::FinalStates(states) => states.contains(&configuration.state),
+            }
+    }
+
+    /// Returns the configurations one transition away from a configuration.
+    fn successors(&self, configuration: &Configuration, input: &[String]) -> Vec<Configuration> {
+        let next_input = input.get(configuration.position);
+        let top = configuration.stack.last();
+
+        self.transitions
+            .iter()
+            .filter(|transition| transition.from == configuration.state)
+            .filter(|transition| transition.input.is_none() || transition.input.as_ref() == next_input)
+            .filter(|transition| transition.pop.is_none() || transition.pop.as_ref() == top)
+            .map(|transition| {
+                let mut stack = configuration.stack.clone();
+                if transition.pop.is_some() {
+                    stack.pop();
+                }
+                stack.extend(transition.push.iter().rev().cloned());
+                Configuration {
+                    state: transition.to.clone(),
+                    position: configuration.position + usize::from(transition.input.is_some()),
+                    stack,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Returns the symbols of a list of words, where a lone `ε` stands for no symbols.
+fn symbols_of(words: &[&str]) -> Vec<String> {
+    words.iter().filter(|word| **word != EPSILON).map(|word| word.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+
+    const AN_BN: &str = "# a^n b^n
+start q0
+stack Z
+accept q2
+q0 a Z -> q0 A Z
+q0 a A -> q0 A A
+q0 b A -> q1 ε
+q1 b A -> q1 ε
+q1 ε Z -> q2 Z
+q0 ε Z -> q2 Z
+";
+
+    fn sentence(words: &str) -> Vec<String> {
+        words.split_whitespace().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_from_grammar() {
+        let grammar = build_grammar("<E> ::= <T> + <E> | <T>\n<T> ::= id | ( <E> )").unwrap();
+        let pda = Pda::from_grammar(&grammar);
+        assert_eq!(
+            pda.to_text(),
+            "start q\nstack <E>\naccept empty\nq ε <E> -> q <T> + <E>\nq ε <E> -> q <T>\n\
+             q ε <T> -> q id\nq ε <T> -> q ( <E> )\nq + + -> q ε\nq id id -> q ε\nq ( ( -> q ε\nq ) ) -> q ε\n"
+        );
+
+        let input = sentence("id + id");
+        let Run::Accepted(run) = pda.run(&input, 1000) else {
+            panic!("id + id is in the language");
+        };
+        let trace: Vec<String> = run.iter().map(|configuration| configuration.format(&input)).collect();
+        assert_eq!(
+            trace,
+            [
+                "(q, id + id, <E>)",
+                "(q, id + id, <T> + <E>)",
+                "(q, id + id, id + <E>)",
+                "(q, + id, + <E>)",
+                "(q, id, <E>)",
+                "(q, id, <T>)",
+                "(q, id, id)",
+                "(q, ε, ε)",
+            ]
+        );
+
+        assert!(matches!(pda.run(&sentence("id +"), 1000), Run::Rejected { .. }));
+    }
+
+    #[test]
+    fn test_run_from_file() {
+        let pda = Pda::parse(AN_BN).unwrap();
+        assert_eq!(Pda::parse(&pda.to_text()).unwrap(), pda);
+
+        assert!(matches!(pda.run(&sentence(""), 100), Run::Accepted(_)));
+        assert!(matches!(pda.run(&sentence("a a b b"), 100), Run::Accepted(_)));
+        assert!(matches!(pda.run(&sentence("a a b"), 100), Run::Rejected { .. }));
+        assert!(matches!(pda.run(&sentence("b a"), 100), Run::Rejected { .. }));
+
+        // left recursion pushes forever, so the search has to give up
+        let grammar = build_grammar("<E> ::= <E> + id | id").unwrap();
+        let pda = Pda::from_grammar(&grammar);
+        assert!(matches!(pda.run(&sentence("id +"), 100), Run::GaveUp { explored: 100 }));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = Pda::parse("start q0\naccept q1\nq0 a -> q1").unwrap_err();
+        assert_eq!(error.to_string(), "line 3: expected a state, an input symbol and a stack symbol before '->'");
+        assert_eq!(Pda::parse("start q0\nq0 a Z -> q0").unwrap_err().line, 2);
+        assert!(Pda::parse("start q0 q1\naccept empty").is_err());
+    }
+}
@@ -0,0 +1,485 @@
+use std::fmt;
+
+use crate::commands::counted;
+use crate::context_free_grammar::{ContextFreeGrammar, GrammarError, Span};
+use crate::lexer::Token;
+use crate::ll::{ll1_table, LlTable};
+use crate::lr::{Action, LrKind, LrTable};
+use crate::normal_form::check_reachable_rules;
+use crate::parse_tree::ParseTree;
+use crate::sets::{GrammarSets, END_MARKER};
+
+/// How a parser got past a syntax error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recovery {
+    /// Deleted the unexpected token, as the one after it could be parsed.
+    Deleted,
+    /// Inserted the missing terminal.
+    Inserted(String),
+    /// Skipped tokens until one the variable being parsed can start with.
+    Skipped(usize),
+    /// Skipped tokens until one in the synchronization set of a variable, and took the input
+    /// since the variable began, if any, as an error in its place.
+    Synchronized { skipped: usize, variable: String },
+}
+impl fmt::Display for Recovery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Recovery::Deleted => write!(f, "deleted it"),
+            Recovery::Inserted(terminal) => write!(f, "inserted {}", terminal),
+            Recovery::Skipped(skipped) => write!(f, "skipped {}", counted(*skipped, "token")),
+            Recovery::Synchronized { skipped: 0, variable } => write!(f, "recovered as {}", variable),
+            Recovery::Synchronized { skipped, variable } => {
+                write!(f, "skipped {} and recovered as {}", counted(*skipped, "token"), variable)
+            }
+        }
+    }
+}
+
+/// A syntax error found by a recovering parser: the token it was found at, or None at the end of the input,
+/// the terminals that could have come there and how the parser went on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub token: Option<Token>,
+    pub expected: Vec<String>,
+    pub recovery: Recovery,
+}
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let expected: Vec<&str> = self.expected.iter().map(|terminal| describe(terminal)).collect();
+        match &self.token {
+            Some(Token { text, span: Span { line, start, end }, .. }) => write!(
+                f,
+                "line {}, bytes {}..{}: expected {} but found '{}', {}",
+                line,
+                start,
+                end,
+                expected.join(" or "),
+                text,
+                self.recovery
+            ),
+            None => write!(f, "at the end of the input: expected {}, {}", expected.join(" or "), self.recovery),
+        }
+    }
+}
+
+/// The result of parsing with error recovery: a tree of the whole input, with error nodes where
+/// syntax errors were found, and the errors in the order they were found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialParse {
+    pub tree: ParseTree,
+    pub errors: Vec<SyntaxError>,
+}
+
+fn describe(terminal: &str) -> &str {
+    if terminal == END_MARKER {
+        "the end of the input"
+    } else {
+        terminal
+    }
+}
+
+/// Orders the terminals a parser expected as they are in the grammar, with `$` last.
+fn sorted_expected<'t>(grammar: &ContextFreeGrammar, terminals: impl Iterator<Item = &'t str>) -> Vec<String> {
+    let mut expected: Vec<String> = terminals.map(|terminal| terminal.to_string()).collect();
+    expected.sort_by_key(|terminal| grammar.terminals().get_index_of(terminal).unwrap_or(usize::MAX));
+    expected
+}
+
+/// Parses tokens with a predictive parser for an LL(1) grammar. Fails if the grammar's table has conflicts
+/// or a variable the parser could reach has no rules.
+///
+/// On a syntax error in a variable `<A>` the parser deletes the unexpected token if the next one can
+/// start `<A>` (phrase-level recovery), and otherwise skips tokens until one that can start `<A>` or is
+/// in FOLLOW(<A>) (panic mode). Missing terminals are inserted, unless deleting one token gets to them.
+pub fn parse_ll1(grammar: &ContextFreeGrammar, tokens: &[Token]) -> Result<PartialParse, GrammarError> {
+    check_reachable_rules(grammar)?;
+    let table = ll1_table(grammar);
+    let conflicts = table.conflicts();
+    if !conflicts.is_empty() {
+        return Err(GrammarError::conflicts("LL(1)", &conflicts));
+    }
+
+    let sets = GrammarSets::new(grammar);
+    let mut parser = LlParser { grammar, table, sets, tokens, position: 0, errors: Vec::new() };
+    let start = grammar.start_symbol();
+    let mut tree = parser.variable(start);
+    // tokens left over are skipped up to one that can start another sentence, which is parsed into the
+    // error node to find any errors in it
+    while parser.position < tokens.len() {
+        let first = &parser.sets.first[start];
+        let mut skipped = 0;
+        while parser.peek(skipped) != END_MARKER && !first.contains(parser.peek(skipped)) {
+            skipped += 1;
+        }
+        let error = parser.errors.len();
+        parser.error(vec![END_MARKER.to_string()], Recovery::Skipped(skipped));
+        let mut children = parser.take(skipped);
+        if parser.position < tokens.len() {
+            let before = parser.position;
+            let sentence = parser.variable(start);
+            if parser.position == before {
+                // a sentence parsed without consuming anything gets nowhere, so the token is skipped instead
+                parser.errors.truncate(error + 1);
+                parser.errors[error].recovery = Recovery::Skipped(skipped + 1);
+                children.extend(parser.take(1));
+            } else {
+                children.push(sentence);
+            }
+        }
+        append(&mut tree, ParseTree::Error { expected: vec![END_MARKER.to_string()], children });
+    }
+
+    Ok(PartialParse { tree, errors: parser.errors })
+}
+
+struct LlParser<'g, 't> {
+    grammar: &'g ContextFreeGrammar,
+    table: LlTable<'g>,
+    sets: GrammarSets<'g>,
+    tokens: &'t [Token],
+    position: usize,
+    errors: Vec<SyntaxError>,
+}
+impl LlParser<'_, '_> {
+    /// Returns the terminal of the token `offset` tokens ahead, or `$` past the end of the input.
+    fn peek(&self, offset: usize) -> &str {
+        self.tokens.get(self.position + offset).map_or(END_MARKER, |token| token.terminal.as_str())
+    }
+
+    fn error(&mut self, expected: Vec<String>, recovery: Recovery) {
+        let token = self.tokens.get(self.position).cloned();
+        self.errors.push(SyntaxError { token, expected, recovery });
+    }
+
+    /// Takes the next `count` tokens as terminal nodes.
+    fn take(&mut self, count: usize) -> Vec<ParseTree> {
+        let taken = self.tokens[self.position..self.position + count].iter().map(ParseTree::token).collect();
+        self.position += count;
+        taken
+    }
+
+    fn variable(&mut self, variable: &str) -> ParseTree {
+        let mut children = Vec::new();
+        loop {
+            if let Some(rule) = self.table.rule(variable, &[self.peek(0)]) {
+                for symbol in &rule.rhs {
+                    if self.grammar.is_variable(symbol) {
+                        let child = self.variable(symbol);
+                        children.push(child);
+                    } else {
+                        self.terminal(symbol, &mut children);
+                    }
+                }
+                return ParseTree::Variable { name: variable.to_string(), children };
+            }
+
+            let row = self.table.entries.get(variable).into_iter().flatten();
+            let lookaheads = row.map(|(lookahead, _)| lookahead[0]);
+            let expected = sorted_expected(self.grammar, lookaheads);
+            if self.peek(0) != END_MARKER && self.table.rule(variable, &[self.peek(1)]).is_some() {
+                self.error(expected.clone(), Recovery::Deleted);
+                children.push(ParseTree::Error { expected, children: self.take(1) });
+                continue;
+            }
+
+            let follow = &self.sets.follow[variable];
+            let mut skipped = 0;
+            while self.peek(skipped) != END_MARKER
+                && !expected.iter().any(|terminal| terminal == self.peek(skipped))
+                && !follow.contains(self.peek(skipped))
+            {
+                skipped += 1;
+            }
+            let resumes = self.table.rule(variable, &[self.peek(skipped)]).is_some();
+            let recovery = if resumes {
+                Recovery::Skipped(skipped)
+            } else {
+                Recovery::Synchronized { skipped, variable: variable.to_string() }
+            };
+            self.error(expected.clone(), recovery);
+            let error = ParseTree::Error { expected, children: self.take(skipped) };
+            if !resumes {
+                // nothing was parsed for the variable yet, so the error stands in for it
+                return error;
+            }
+            children.push(error);
+        }
+    }
+
+    fn terminal(&mut self, terminal: &str, children: &mut Vec<ParseTree>) {
+        if self.peek(0) == terminal {
+            children.extend(self.take(1));
+        } else if self.peek(0) != END_MARKER && self.peek(1) == terminal {
+            self.error(vec![terminal.to_string()], Recovery::Deleted);
+            children.push(ParseTree::Error { expected: vec![terminal.to_string()], children: self.take(1) });
+            children.extend(self.take(1));
+        } else {
+            self.error(vec![terminal.to_string()], Recovery::Inserted(terminal.to_string()));
+            children.push(ParseTree::Error { expected: vec![terminal.to_string()], children: Vec::new() });
+        }
+    }
+}
+
+/// Parses tokens with a shift-reduce parser using an LR table of a grammar. Fails if the table has conflicts
+/// or a variable the parser could reach has no rules.
+///
+/// On a syntax error the parser deletes the unexpected token if the next one has an action in the current
+/// state (phrase-level recovery). Otherwise it goes into panic mode: it skips the fewest tokens, then pops
+/// the fewest states, for the top state to have a goto on some `<A>` to a state with an action for the next
+/// token, which must be in FOLLOW(<A>). What was popped and skipped becomes an error node parsed as `<A>`.
+pub fn parse_lr(grammar: &ContextFreeGrammar, kind: LrKind, tokens: &[Token]) -> Result<PartialParse, GrammarError> {
+    check_reachable_rules(grammar)?;
+    let table = LrTable::new(grammar, kind);
+    let conflicts = table.conflicts();
+    if !conflicts.is_empty() {
+        return Err(GrammarError::conflicts(kind, &conflicts));
+    }
+    let sets = GrammarSets::new(&table.grammar);
+    let terminal = |position: usize| tokens.get(position).map_or(END_MARKER, |token| token.terminal.as_str());
+
+    // each state on the stack comes with the nodes parsed into it: a single node, after any error nodes
+    // of tokens deleted just before it
+    let mut stack: Vec<(usize, Vec<ParseTree>)> = vec![(0, Vec::new())];
+    let mut deleted: Vec<ParseTree> = Vec::new();
+    let mut errors = Vec::new();
+    let mut position = 0;
+    // where the last panic mode recovery left off, which it may not come back to without consuming input
+    let mut recovered_at = None;
+    loop {
+        let state = stack.last().expect("the stack always has the start state").0;
+        match table.actions[state].get(terminal(position)).map(|actions| actions[0]) {
+            Some(Action::Shift(target)) => {
+                let mut nodes = std::mem::take(&mut deleted);
+                nodes.push(ParseTree::token(&tokens[position]));
+                stack.push((target, nodes));
+                position += 1;
+            }
+            Some(Action::Reduce(rule)) => {
+                let rule = table.rule(rule);
+                let children = stack.drain(stack.len() - rule.rhs.len()..).flat_map(|(_, nodes)| nodes).collect();
+                let top = stack.last().expect("the start state is never reduced").0;
+                let node = ParseTree::Variable { name: rule.lhs.clone(), children };
+                stack.push((table.gotos[top][&rule.lhs], vec![node]));
+            }
+            Some(Action::Accept) => {
+                let (_, mut nodes) = stack.pop().expect("accepting states are entered with the start symbol");
+                let mut tree = nodes.pop().expect("the start symbol was parsed into a node");
+                for node in deleted {
+                    append(&mut tree, node);
+                }
+                return Ok(PartialParse { tree, errors });
+            }
+            None => {
+                let expected = sorted_expected(&table.grammar, table.actions[state].keys().map(String::as_str));
+                let repeated = recovered_at == Some(position);
+                if !repeated && position < tokens.len() && table.actions[state].contains_key(terminal(position + 1)) {
+                    let token = Some(tokens[position].clone());
+                    errors.push(SyntaxError { token, expected: expected.clone(), recovery: Recovery::Deleted });
+                    deleted.push(ParseTree::Error { expected, children: vec![ParseTree::token(&tokens[position])] });
+                    position += 1;
+                    continue;
+                }
+
+                let synchronization = |skipped: usize, popped: usize| {
+                    let lookahead = terminal(position + skipped);
+                    let (state, _) = stack[stack.len() - 1 - popped];
+                    table.gotos[state].iter().find(|(variable, target)| {
+                        sets.follow[variable.as_str()].contains(lookahead)
+                            && table.actions[**target].contains_key(lookahead)
+                    })
+                };
+                // coming back to the same error, it must skip a token, or at the end of the input give up
+                // on everything parsed and take it as the start symbol, which accepts
+                let (skipped, popped, variable, target) = if repeated && position == tokens.len() {
+                    let start = &table.rule(0).rhs[0];
+                    (0, stack.len() - 1, start, table.gotos[0][start])
+                } else {
+                    let candidates = (usize::from(repeated)..=tokens.len() - position)
+                        .flat_map(|skipped| (0..stack.len()).map(move |popped| (skipped, popped)));
+                    candidates
+                        .filter_map(|(skipped, popped)| {
+                            let (variable, &target) = synchronization(skipped, popped)?;
+                            Some((skipped, popped, variable, target))
+                        })
+                        .next()
+                        .expect("the start state has a goto on the start symbol to the accepting state")
+                };
+
+                // an error where the last recovery left off, with nothing shifted since, is part of the same
+                // error, which this recovery takes over from
+                match errors.last_mut() {
+                    Some(error) if repeated => {
+                        let before = match error.recovery {
+                            Recovery::Synchronized { skipped, .. } => skipped,
+                            _ => 0,
+                        };
+                        let skipped = before + skipped;
+                        error.recovery = Recovery::Synchronized { skipped, variable: variable.clone() };
+                    }
+                    _ => {
+                        let token = tokens.get(position).cloned();
+                        let recovery = Recovery::Synchronized { skipped, variable: variable.clone() };
+                        errors.push(SyntaxError { token, expected: expected.clone(), recovery });
+                    }
+                }
+                let mut children: Vec<ParseTree> =
+                    stack.drain(stack.len() - popped..).flat_map(|(_, nodes)| nodes).collect();
+                children.append(&mut deleted);
+                children.extend(tokens[position..position + skipped].iter().map(ParseTree::token));
+                stack.push((target, vec![ParseTree::Error { expected, children }]));
+                position += skipped;
+                recovered_at = Some(position);
+            }
+        }
+    }
+}
+
+/// Adds a node after the last child of a variable or error node.
+fn append(tree: &mut ParseTree, node: ParseTree) {
+    match tree {
+        ParseTree::Variable { children, .. } | ParseTree::Error { children, .. } => children.push(node),
+        ParseTree::Terminal { .. } => unreachable!("parsers return a tree of the start symbol"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+    use crate::earley;
+    use crate::lexer::{split_words, terminals};
+
+    const LL1: &str = "<E> ::= <T> <E'>\n<E'> ::= + <T> <E'> | ε\n<T> ::= id | ( <E> )";
+    const LR: &str = "<E> ::= <E> + <T> | <T>\n<T> ::= id | ( <E> )";
+
+    fn errors(parse: &PartialParse) -> Vec<String> {
+        parse.errors.iter().map(|error| error.to_string()).collect()
+    }
+
+    #[test]
+    fn test_sentences_parse_without_errors() {
+        let tokens = split_words("( id + id ) + id");
+        for (bnf, parse) in [
+            (LL1, parse_ll1(&build_grammar(LL1).unwrap(), &tokens).unwrap()),
+            (LR, parse_lr(&build_grammar(LR).unwrap(), LrKind::Slr, &tokens).unwrap()),
+        ] {
+            let mut expected = earley::parse(&build_grammar(bnf).unwrap(), &terminals(&tokens), 1).remove(0);
+            expected.attach_spans(&tokens);
+            assert_eq!(parse, PartialParse { tree: expected, errors: Vec::new() });
+        }
+    }
+
+    #[test]
+    fn test_ll1_recovery() {
+        let grammar = build_grammar(LL1).unwrap();
+
+        let parse = parse_ll1(&grammar, &split_words("( + id ) id )")).unwrap();
+        assert_eq!(
+            errors(&parse),
+            [
+                "line 1, bytes 2..3: expected id or ( but found '+', deleted it",
+                "line 1, bytes 9..11: expected + or ) or the end of the input but found 'id', deleted it",
+                "line 1, bytes 12..13: expected the end of the input but found ')', skipped 1 token",
+            ]
+        );
+        let ParseTree::Variable { children, .. } = &parse.tree else { panic!("expected a tree of <E>") };
+        assert_eq!(children.iter().map(ParseTree::name).collect::<Vec<_>>(), ["<T>", "<E'>", "error"]);
+        assert_eq!(children[1].to_ascii(), "<E'>\n└── error: expected + or ) or $\n    └── id\n");
+
+        // a missing variable becomes an error node and a missing terminal is inserted
+        let parse = parse_ll1(&grammar, &split_words("( id + + ) id")).unwrap();
+        assert_eq!(
+            errors(&parse),
+            [
+                "line 1, bytes 7..8: expected id or ( but found '+', recovered as <T>",
+                "line 1, bytes 9..10: expected id or ( but found ')', deleted it",
+                "at the end of the input: expected ), inserted )",
+            ]
+        );
+
+        let parse = parse_ll1(&grammar, &split_words(") ) id + id (")).unwrap();
+        assert_eq!(
+            errors(&parse),
+            [
+                "line 1, bytes 0..1: expected id or ( but found ')', recovered as <E>",
+                "line 1, bytes 0..1: expected the end of the input but found ')', skipped 2 tokens",
+                "line 1, bytes 12..13: expected + or ) or the end of the input but found '(', deleted it",
+            ]
+        );
+        assert_eq!(parse.tree.name(), "error");
+
+        assert!(parse_ll1(&build_grammar("<E> ::= <E> + id | id").unwrap(), &[]).is_err());
+        let undefined = build_grammar("<S> ::= a <X> | b").unwrap();
+        assert_eq!(parse_ll1(&undefined, &split_words("b")).unwrap_err().message, "<X> has no rules");
+    }
+
+    #[test]
+    fn test_ll1_recovery_skips_tokens_following_a_nullable_start_symbol() {
+        let grammar = build_grammar("<S> ::= ( <S> ) <S> | ε").unwrap();
+
+        let parse = parse_ll1(&grammar, &split_words(")")).unwrap();
+        assert_eq!(errors(&parse), ["line 1, bytes 0..1: expected the end of the input but found ')', skipped 1 token"]);
+
+        let parse = parse_ll1(&grammar, &split_words("( ) ) ( )")).unwrap();
+        assert_eq!(errors(&parse), ["line 1, bytes 4..5: expected the end of the input but found ')', skipped 1 token"]);
+        let ParseTree::Variable { children, .. } = &parse.tree else { panic!("expected a tree of <S>") };
+        let ParseTree::Error { children: leftover, .. } = children.last().unwrap() else { panic!("expected an error") };
+        assert_eq!(leftover.iter().map(ParseTree::name).collect::<Vec<_>>(), [")", "<S>"]);
+    }
+
+    #[test]
+    fn test_lr_recovery() {
+        let grammar = build_grammar(LR).unwrap();
+
+        let parse = parse_lr(&grammar, LrKind::Lr1, &split_words("( + id ) id )")).unwrap();
+        assert_eq!(
+            errors(&parse),
+            [
+                "line 1, bytes 2..3: expected id or ( but found '+', deleted it",
+                "line 1, bytes 9..11: expected + or the end of the input but found 'id', \
+                 skipped 1 token and recovered as <E>",
+            ]
+        );
+        assert_eq!(
+            parse.tree.to_ascii(),
+            "<E>
+└── <T>
+    ├── (
+    ├── error: expected + or $
+    │   ├── <E>
+    │   │   └── <T>
+    │   │       ├── error: expected id or (
+    │   │       │   └── +
+    │   │       └── id
+    │   ├── )
+    │   └── id
+    └── )
+"
+        );
+
+        // SLR(1) reduces before finding the error, which it recovers from in several steps
+        for kind in [LrKind::Slr, LrKind::Lalr, LrKind::Lr1] {
+            let parse = parse_lr(&grammar, kind, &split_words(") ) id + id (")).unwrap();
+            let first = &parse.errors[0];
+            assert_eq!(first.token.as_ref().map(|token| token.span.start), Some(0));
+            assert_eq!(first.expected, ["id", "("]);
+            assert_eq!(first.recovery, Recovery::Synchronized { skipped: 3, variable: "<E>".to_string() });
+            assert_eq!(parse.errors[1].token.as_ref().map(|token| token.span.start), Some(12));
+            assert_eq!(parse.errors[1].recovery, Recovery::Deleted);
+        }
+
+        for kind in [LrKind::Lalr, LrKind::Lr1] {
+            let parse = parse_lr(&grammar, kind, &split_words("( ( (")).unwrap();
+            assert_eq!(errors(&parse), ["at the end of the input: expected id or (, recovered as <E>"]);
+        }
+        let parse = parse_lr(&grammar, LrKind::Lr1, &split_words("( ( (")).unwrap();
+        let children = split_words("( ( (").iter().map(ParseTree::token).collect();
+        assert_eq!(parse.tree, ParseTree::Error { expected: vec!["id".to_string(), "(".to_string()], children });
+
+        let error = parse_lr(&build_grammar("<E> ::= <E> + <E> | id").unwrap(), LrKind::Lr1, &[]).unwrap_err();
+        assert!(error.message.starts_with("the grammar is not LR(1):\n    shift/reduce conflict"));
+        let undefined = build_grammar("<S> ::= a <X> | b").unwrap();
+        assert_eq!(parse_lr(&undefined, LrKind::Slr, &split_words("b")).unwrap_err().message, "<X> has no rules");
+    }
+}
@@ -0,0 +1,557 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+/// A regular expression over characters, as written between slashes in `@token` declarations.
+/// Supports literals, `.`, escapes like `\d`, classes like `[a-z_]` and `[^"]`, groups,
+/// alternation `|` and the quantifiers `*`, `+`, `?`, `{m}`, `{m,}` and `{m,n}`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Regex {
+    /// Matches the empty string.
+    Empty,
+    Literal(char),
+    /// Matches any character except a newline.
+    Any,
+    /// Matches a character in (or, if negated, not in) one of the inclusive ranges.
+    Class { negated: bool, ranges: Vec<(char, char)> },
+    Concat(Vec<Regex>),
+    Alternation(Vec<Regex>),
+    /// Matches `inner` at least `min` and at most `max` times, without an upper bound if `max` is None.
+    Repeat { inner: Box<Regex>, min: u32, max: Option<u32> },
+}
+impl Regex {
+    /// Parses a regular expression.
+    pub fn parse(pattern: &str) -> Result<Regex, RegexError> {
+        let mut parser = Parser { chars: pattern.chars().collect(), position: 0 };
+        let regex = parser.alternation()?;
+        match parser.peek() {
+            None => Ok(regex),
+            Some(')') => Err(parser.error("unmatched ')'")),
+            Some(c) => Err(parser.error(&format!("unexpected '{}'", c))),
+        }
+    }
+
+    /// Returns a regex matching exactly the text.
+    pub fn literal(text: &str) -> Regex {
+        Regex::concatenation(text.chars().map(Regex::Literal).collect())
+    }
+
+    /// Returns the concatenation of some regexes, flattening nested concatenations,
+    /// dropping empty parts and writing `x x*` and `x* x` as `x+`.
+    pub fn concatenation(parts: Vec<Regex>) -> Regex {
+        let mut flattened: Vec<Regex> = Vec::new();
+        for part in parts {
+            let parts = match part {
+                Regex::Concat(parts) => parts,
+                Regex::Empty => Vec::new(),
+                part => vec![part],
+            };
+            for part in parts {
+                match (flattened.pop(), part) {
+                    (Some(Regex::Repeat { inner, min: 0, max: None }), part) if *inner == part => {
+                        flattened.push(Regex::Repeat { inner, min: 1, max: None });
+                    }
+                    (Some(previous), Regex::Repeat { inner, min: 0, max: None }) if *inner == previous => {
+                        flattened.push(Regex::Repeat { inner, min: 1, max: None });
+                    }
+                    (previous, part) => {
+                        flattened.extend(previous);
+                        flattened.push(part);
+                    }
+                }
+            }
+        }
+
+        match flattened.len() {
+            0 => Regex::Empty,
+            1 => flattened.remove(0),
+            _ => Regex::Concat(flattened),
+        }
+    }
+
+    /// Returns the alternation of some regexes, flattening nested alternations and dropping duplicates.
+    /// An empty alternative is written as `?`, or turns `x+` into `x*`.
+    pub fn alternatives(alternatives: Vec<Regex>) -> Regex {
+        let mut flattened: Vec<Regex> = Vec::new();
+        let mut empty = false;
+        for alternative in alternatives {
+            let alternatives = match alternative {
+                Regex::Alternation(alternatives) => alternatives,
+                Regex::Repeat { inner, min: 0, max: Some(1) } => {
+                    empty = true;
+                    vec![*inner]
+                }
+                alternative => vec![alternative],
+            };
+            for alternative in alternatives {
+                if alternative == Regex::Empty {
+                    empty = true;
+                } else if !flattened.contains(&alternative) {
+                    flattened.push(alternative);
+                }
+            }
+        }
+
+        let regex = match flattened.len() {
+            0 => return Regex::Empty,
+            1 => flattened.remove(0),
+            _ => Regex::Alternation(flattened),
+        };
+        match regex {
+            Regex::Repeat { inner, min: 0 | 1, max: None } if empty => Regex::Repeat { inner, min: 0, max: None },
+            regex @ Regex::Repeat { min: 0, .. } => regex,
+            regex if empty => Regex::Repeat { inner: Box::new(regex), min: 0, max: Some(1) },
+            regex => regex,
+        }
+    }
+
+    /// Returns a regex matching any number of repetitions of this one.
+    pub fn star(self) -> Regex {
+        match self {
+            Regex::Empty => Regex::Empty,
+            Regex::Repeat { inner, min: 0 | 1, max: None | Some(1) } => Regex::Repeat { inner, min: 0, max: None },
+            regex => Regex::Repeat { inner: Box::new(regex), min: 0, max: None },
+        }
+    }
+
+    /// Returns true if the character matches this single-character regex.
+    pub fn matches_char(&self, c: char) -> bool {
+        match self {
+            Regex::Literal(literal) => *literal == c,
+            Regex::Any => c != '\n',
+            Regex::Class { negated, ranges } => {
+                ranges.iter().any(|&(low, high)| low <= c && c <= high) != *negated
+            }
+            _ => false,
+        }
+    }
+}
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format_regex(self, Precedence::Alternation))
+    }
+}
+
+/// How tightly an operator binds, used to only add the parentheses that are needed when printing.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    Alternation,
+    Concat,
+    Repeat,
+}
+
+fn format_regex(regex: &Regex, context: Precedence) -> String {
+    let (output, precedence) = match regex {
+        Regex::Empty => ("()".to_string(), Precedence::Repeat),
+        Regex::Literal(c) => (escape_char(*c, "\\.[]{}()|*+?^$/"), Precedence::Repeat),
+        Regex::Any => (".".to_string(), Precedence::Repeat),
+        Regex::Class { negated, ranges } => {
+            let mut output = String::from(if *negated { "[^" } else { "[" });
+            for &(low, high) in ranges {
+                output.push_str(&escape_char(low, "\\]^-"));
+                if low != high {
+                    output.push('-');
+                    output.push_str(&escape_char(high, "\\]^-"));
+                }
+            }
+            output.push(']');
+            (output, Precedence::Repeat)
+        }
+        Regex::Concat(parts) => (
+            parts.iter().map(|part| format_regex(part, Precedence::Concat)).collect(),
+            Precedence::Concat,
+        ),
+        Regex::Alternation(alternatives) => (
+            alternatives
+                .iter()
+                .map(|alternative| format_regex(alternative, Precedence::Alternation))
+                .collect::<Vec<String>>()
+                .join("|"),
+            Precedence::Alternation,
+        ),
+        Regex::Repeat { inner, min, max } => {
+            let quantifier = match (min, max) {
+                (0, None) => "*".to_string(),
+                (1, None) => "+".to_string(),
+                (0, Some(1)) => "?".to_string(),
+                (min, None) => format!("{{{},}}", min),
+                (min, Some(max)) if min == max => format!("{{{}}}", min),
+                (min, Some(max)) => format!("{{{},{}}}", min, max),
+            };
+            (format!("{}{}", format_regex(inner, Precedence::Repeat), quantifier), Precedence::Repeat)
+        }
+    };
+
+    if precedence < context {
+        format!("({})", output)
+    } else {
+        output
+    }
+}
+
+/// Writes a character, escaping it if it is one of the special characters or not printable.
+fn escape_char(c: char, special: &str) -> String {
+    match c {
+        '\n' => "\\n".to_string(),
+        '\t' => "\\t".to_string(),
+        '\r' => "\\r".to_string(),
+        c if special.contains(c) => format!("\\{}", c),
+        c => c.to_string(),
+    }
+}
+
+/// Error found while parsing a regular expression, with the character position it was found at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegexError {
+    pub message: String,
+    pub position: usize,
+}
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+impl Error for RegexError {}
+
+/// Recursive descent parser for regular expressions.
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position += 1;
+        c
+    }
+
+    fn error(&self, message: &str) -> RegexError {
+        RegexError { message: message.to_string(), position: self.position }
+    }
+
+    fn alternation(&mut self) -> Result<Regex, RegexError> {
+        let mut alternatives = vec![self.concat()?];
+        while self.peek() == Some('|') {
+            self.position += 1;
+            alternatives.push(self.concat()?);
+        }
+
+        Ok(if alternatives.len() == 1 { alternatives.remove(0) } else { Regex::Alternation(alternatives) })
+    }
+
+    fn concat(&mut self) -> Result<Regex, RegexError> {
+        let mut parts = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            parts.push(self.repeat()?);
+        }
+
+        Ok(match parts.len() {
+            0 => Regex::Empty,
+            1 => parts.remove(0),
+            _ => Regex::Concat(parts),
+        })
+    }
+
+    fn repeat(&mut self) -> Result<Regex, RegexError> {
+        let mut regex = self.atom()?;
+
+        loop {
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => {
+                    self.position += 1;
+                    self.bounds()?
+                }
+                _ => return Ok(regex),
+            };
+            // skip the quantifier or the closing brace
+            self.position += 1;
+            regex = Regex::Repeat { inner: Box::new(regex), min, max };
+        }
+    }
+
+    /// Parses the `m}`, `m,}` or `m,n}` of a `{...}` quantifier, leaving the closing brace to the caller.
+    fn bounds(&mut self) -> Result<(u32, Option<u32>), RegexError> {
+        let min = self.number()?.ok_or_else(|| self.error("expected a number after '{'"))?;
+        let max = if self.peek() == Some(',') {
+            self.position += 1;
+            self.number()?
+        } else {
+            Some(min)
+        };
+
+        if self.peek() != Some('}') {
+            return Err(self.error("expected '}'"));
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error("the upper bound of a repetition is below its lower bound"));
+        }
+        Ok((min, max))
+    }
+
+    fn number(&mut self) -> Result<Option<u32>, RegexError> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Ok(None);
+        }
+
+        let digits: String = self.chars[start..self.position].iter().collect();
+        digits.parse().map(Some).map_err(|_| self.error("repetition count is too large"))
+    }
+
+    fn atom(&mut self) -> Result<Regex, RegexError> {
+        match self.next() {
+            Some('(') => {
+                let regex = self.alternation()?;
+                if self.next() != Some(')') {
+                    return Err(self.error("expected ')'"));
+                }
+                Ok(regex)
+            }
+            Some('[') => self.class(),
+            Some('.') => Ok(Regex::Any),
+            Some('\\') => self.escape(),
+            Some(c @ ('*' | '+' | '?' | '{')) => {
+                self.position -= 1;
+                Err(self.error(&format!("'{}' has nothing to repeat", c)))
+            }
+            Some(c) => Ok(Regex::Literal(c)),
+            None => Err(self.error("unexpected end of pattern")),
+        }
+    }
+
+    /// Parses the character after a backslash.
+    fn escape(&mut self) -> Result<Regex, RegexError> {
+        let class = |negated, ranges: &[(char, char)]| Regex::Class { negated, ranges: ranges.to_vec() };
+        let digit = [('0', '9')];
+        let word = [('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+        let space = [('\t', '\n'), ('\r', '\r'), (' ', ' ')];
+
+        Ok(match self.next() {
+            Some('d') => class(false, &digit),
+            Some('D') => class(true, &digit),
+            Some('w') => class(false, &word),
+            Some('W') => class(true, &word),
+            Some('s') => class(false, &space),
+            Some('S') => class(true, &space),
+            Some(c) => Regex::Literal(unescape(c)),
+            None => return Err(self.error("pattern ends with a backslash")),
+        })
+    }
+
+    /// Parses a character class after its opening `[`.
+    fn class(&mut self) -> Result<Regex, RegexError> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.position += 1;
+        }
+
+        let mut ranges = Vec::new();
+        loop {
+            let low = match self.next() {
+                Some(']') if !ranges.is_empty() => break,
+                Some('\\') => unescape(self.next().ok_or_else(|| self.error("unterminated character class"))?),
+                Some(c) => c,
+                None => return Err(self.error("unterminated character class")),
+            };
+
+            let high = if self.peek() == Some('-') && self.chars.get(self.position + 1).is_some_and(|&c| c != ']') {
+                self.position += 1;
+                match self.next() {
+                    Some('\\') => unescape(self.next().ok_or_else(|| self.error("unterminated character class"))?),
+                    Some(c) => c,
+                    None => return Err(self.error("unterminated character class")),
+                }
+            } else {
+                low
+            };
+
+            if high < low {
+                return Err(self.error(&format!("invalid range {}-{}", low, high)));
+            }
+            ranges.push((low, high));
+        }
+
+        Ok(Regex::Class { negated, ranges })
+    }
+}
+
+/// Returns the character an escape like `\n` stands for.
+fn unescape(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+/// A regular expression compiled to a Thompson NFA, for finding the longest match at the start of a string.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    states: Vec<MatcherState>,
+    start: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum MatcherState {
+    /// Consumes one character matching the single-character regex, then moves to the next state.
+    Char(Regex, usize),
+    /// Moves to any of the states without consuming input.
+    Split(Vec<usize>),
+    Accept,
+}
+
+impl Matcher {
+    pub fn new(regex: &Regex) -> Matcher {
+        let mut matcher = Matcher { states: vec![MatcherState::Accept], start: 0 };
+        matcher.start = matcher.compile(regex, 0);
+        matcher
+    }
+
+    /// Adds states matching `regex` and then continuing at `next`, returning the first of them.
+    fn compile(&mut self, regex: &Regex, next: usize) -> usize {
+        match regex {
+            Regex::Empty => next,
+            Regex::Literal(_) | Regex::Any | Regex::Class { .. } => self.add(MatcherState::Char(regex.clone(), next)),
+            Regex::Concat(parts) => parts.iter().rev().fold(next, |next, part| self.compile(part, next)),
+            Regex::Alternation(alternatives) => {
+                let starts = alternatives.iter().map(|alternative| self.compile(alternative, next)).collect();
+                self.add(MatcherState::Split(starts))
+            }
+            Regex::Repeat { inner, min, max } => {
+                let mut start = match max {
+                    None => {
+                        // loop state that either matches inner again or leaves
+                        let loop_state = self.add(MatcherState::Split(Vec::new()));
+                        let inner_start = self.compile(inner, loop_state);
+                        self.states[loop_state] = MatcherState::Split(vec![inner_start, next]);
+                        loop_state
+                    }
+                    Some(max) => {
+                        let mut start = next;
+                        for _ in *min..*max {
+                            let inner_start = self.compile(inner, start);
+                            start = self.add(MatcherState::Split(vec![inner_start, next]));
+                        }
+                        start
+                    }
+                };
+                for _ in 0..*min {
+                    start = self.compile(inner, start);
+                }
+                start
+            }
+        }
+    }
+
+    /// Returns the states of the NFA, which starts at `start()` and accepts in the `Accept` state.
+    pub fn states(&self) -> &[MatcherState] {
+        &self.states
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    fn add(&mut self, state: MatcherState) -> usize {
+        self.states.push(state);
+        self.states.len() - 1
+    }
+
+    /// Adds a state and every state reachable from it without consuming input.
+    fn add_closure(&self, state: usize, set: &mut Vec<usize>, seen: &mut HashSet<usize>) {
+        if !seen.insert(state) {
+            return;
+        }
+        set.push(state);
+        if let MatcherState::Split(targets) = &self.states[state] {
+            for &target in targets {
+                self.add_closure(target, set, seen);
+            }
+        }
+    }
+
+    /// Returns the length in bytes of the longest prefix of `text` the regex matches, if any prefix matches.
+    pub fn longest_match(&self, text: &str) -> Option<usize> {
+        let mut current = Vec::new();
+        self.add_closure(self.start, &mut current, &mut HashSet::new());
+
+        let accepts = |states: &[usize]| states.iter().any(|&state| matches!(self.states[state], MatcherState::Accept));
+        let mut longest = accepts(&current).then_some(0);
+
+        for (offset, c) in text.char_indices() {
+            let mut next = Vec::new();
+            let mut seen = HashSet::new();
+            for &state in &current {
+                if let MatcherState::Char(atom, target) = &self.states[state] {
+                    if atom.matches_char(c) {
+                        self.add_closure(*target, &mut next, &mut seen);
+                    }
+                }
+            }
+
+            if next.is_empty() {
+                break;
+            }
+            if accepts(&next) {
+                longest = Some(offset + c.len_utf8());
+            }
+            current = next;
+        }
+
+        longest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn longest(pattern: &str, text: &str) -> Option<usize> {
+        Matcher::new(&Regex::parse(pattern).unwrap()).longest_match(text)
+    }
+
+    #[test]
+    fn test_longest_match() {
+        assert_eq!(longest("[0-9]+", "123abc"), Some(3));
+        assert_eq!(longest("[0-9]+", "abc"), None);
+        assert_eq!(longest("[a-z_]\\w*", "foo_1 + 2"), Some(5));
+        assert_eq!(longest("a|ab|abc", "abd"), Some(2));
+        assert_eq!(longest("(ab)*", "ababa"), Some(4));
+        assert_eq!(longest("a{2,3}", "aaaa"), Some(3));
+        assert_eq!(longest("a{2,}", "a"), None);
+        assert_eq!(longest("\"[^\"]*\"", "\"hi\" there"), Some(4));
+        assert_eq!(longest("-?\\d+(\\.\\d+)?", "-3.25x"), Some(5));
+        assert_eq!(longest("(a*)*", "aab"), Some(2));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Regex::parse("(ab").unwrap_err().message, "expected ')'");
+        assert_eq!(Regex::parse("ab)").unwrap_err().message, "unmatched ')'");
+        assert_eq!(Regex::parse("*a").unwrap_err().position, 0);
+        assert!(Regex::parse("[a-").is_err());
+        assert!(Regex::parse("a{3,2}").is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for pattern in ["[0-9]+", "a(b|c)*d", "(ab)?c{2,3}", "[^\"\\\\]", "\\.\\*x|y", "(a|)b"] {
+            let regex = Regex::parse(pattern).unwrap();
+            assert_eq!(Regex::parse(&regex.to_string()).unwrap(), regex, "{}", pattern);
+        }
+    }
+}
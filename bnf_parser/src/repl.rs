@@ -0,0 +1,323 @@
+use std::io::{self, BufRead, Write};
+
+use crate::commands::{self, counted, format_sentence, format_set, format_table};
+use crate::context_free_grammar::{build_grammar, ContextFreeGrammar, ProductionRule};
+use crate::earley;
+use crate::error::CliError;
+use crate::generator::Rng;
+use crate::lexer;
+use crate::normal_form;
+use crate::sets::GrammarSets;
+
+const REPL_HELP: &str = "Enter a rule like `<E> ::= <E> + <T> | <T>` to add it to the grammar. Commands:
+    :load <file>            Replace the grammar with one read from a file
+    :show                   Print the grammar as BNF
+    :start <V>              Make a variable the start symbol
+    :delete <V> ::= <rhs>   Delete rules (every rule of <V> if no rhs is given)
+    :first <symbols>        Print the FIRST set of a sequence of symbols
+    :follow <V>             Print the FOLLOW set of a variable
+    :sets                   Print the nullable, FIRST and FOLLOW sets of every variable
+    :parse <sentence>       Parse a sentence and print its parse tree
+    :cnf                    Print the grammar in Chomsky normal form
+    :reduce                 Print the grammar without useless rules
+    :generate [N]           Print N random sentences (default 5)
+    :help                   Print this help
+    :quit                   Leave the repl";
+
+/// Depth and length limits used by `:generate`.
+const GENERATE_MAX_DEPTH: usize = 12;
+const GENERATE_MAX_LEN: usize = 30;
+
+/// What the repl does after running a line.
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    /// Prints the output and waits for the next line.
+    Output(String),
+    /// Leaves the repl.
+    Quit,
+}
+
+/// Interactive session working on a grammar that is edited one line at a time.
+pub struct Repl {
+    grammar: Option<ContextFreeGrammar>,
+}
+impl Repl {
+    pub fn new(grammar: Option<ContextFreeGrammar>) -> Repl {
+        Repl { grammar }
+    }
+
+    /// Runs a single line, either a rule to add or a `:command`.
+    pub fn execute(&mut self, line: &str) -> Result<Reply, CliError> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(Reply::Output(String::new()));
+        }
+        let Some(command_line) = line.strip_prefix(':') else {
+            return self.add_rules(line).map(Reply::Output);
+        };
+
+        let (command, argument) = match command_line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (command_line, ""),
+        };
+
+        let output = match command {
+            "quit" | "q" | "exit" => return Ok(Reply::Quit),
+            "help" | "h" => REPL_HELP.to_string(),
+            "load" => {
+                let grammar = commands::read_grammar(argument, None)?;
+                let output = format!(
+                    "Loaded {} rules, start symbol {}",
+                    grammar.production_rules().len(),
+                    grammar.start_symbol()
+                );
+                self.grammar = Some(grammar);
+                output
+            }
+            "show" => self.grammar()?.to_bnf().trim_end().to_string(),
+            "start" => self.set_start_symbol(argument)?,
+            "delete" => self.delete_rules(argument)?,
+            "first" => self.first(argument)?,
+            "follow" => self.follow(argument)?,
+            "sets" => self.sets()?,
+            "parse" => self.parse(argument)?,
+            "cnf" => normal_form::to_chomsky_normal_form(self.grammar()?).to_bnf().trim_end().to_string(),
+            "reduce" => normal_form::reduce(self.grammar()?).to_bnf().trim_end().to_string(),
+            "generate" => self.generate(argument)?,
+            _ => {
+                return Err(CliError::Usage(format!(
+                    "Unknown command :{}, type :help for a list of commands",
+                    command
+                )))
+            }
+        };
+
+        Ok(Reply::Output(output))
+    }
+
+    /// Returns the live grammar, or an error if nothing has been loaded or added yet.
+    fn grammar(&self) -> Result<&ContextFreeGrammar, CliError> {
+        self.grammar.as_ref().ok_or_else(|| {
+            CliError::Usage("No grammar yet, add a rule or use :load <file>".to_string())
+        })
+    }
+
+    /// Adds the rules of a BNF line. The first rule ever added sets the start symbol.
+    fn add_rules(&mut self, line: &str) -> Result<String, CliError> {
+        let rules = parse_rules(line)?;
+
+        let mut added = 0;
+        match &mut self.grammar {
+            Some(grammar) => {
+                for rule in rules {
+                    if grammar.add_rule(rule) {
+                        added += 1;
+                    }
+                }
+            }
+            None => {
+                added = rules.len();
+                self.grammar = Some(ContextFreeGrammar::from_rules(&rules[0].lhs.clone(), rules));
+            }
+        }
+
+        Ok(format!("Added {}", counted(added, "rule")))
+    }
+
+    /// Deletes the rules of a BNF line, or every rule of a variable.
+    fn delete_rules(&mut self, argument: &str) -> Result<String, CliError> {
+        let grammar = self.grammar.as_mut().ok_or_else(|| {
+            CliError::Usage("No grammar yet, add a rule or use :load <file>".to_string())
+        })?;
+
+        let rules = if argument.contains("::=") {
+            parse_rules(argument)?
+        } else if grammar.is_variable(argument) {
+            grammar.rules_for(argument).cloned().collect()
+        } else {
+            return Err(CliError::Usage("Usage: :delete <V> ::= <rhs> or :delete <V>".to_string()));
+        };
+
+        let mut deleted = 0;
+        for rule in &rules {
+            if grammar.remove_rule(rule) {
+                deleted += 1;
+            }
+        }
+
+        Ok(format!("Deleted {}", counted(deleted, "rule")))
+    }
+
+    fn set_start_symbol(&mut self, variable: &str) -> Result<String, CliError> {
+        let grammar = self.grammar.as_mut().ok_or_else(|| {
+            CliError::Usage("No grammar yet, add a rule or use :load <file>".to_string())
+        })?;
+        if !grammar.is_variable(variable) {
+            return Err(CliError::Usage(format!("{} is not a variable of the grammar", variable)));
+        }
+
+        grammar.set_start_symbol(variable);
+        Ok(format!("Start symbol is now {}", variable))
+    }
+
+    /// Prints the FIRST set of a sequence of symbols, noting if the sequence can derive epsilon.
+    fn first(&self, argument: &str) -> Result<String, CliError> {
+        let grammar = self.grammar()?;
+        let symbols: Vec<&str> = argument.split_whitespace().collect();
+        if let Some(unknown) = symbols
+            .iter()
+            .find(|symbol| !grammar.is_variable(symbol) && !grammar.terminals().contains(**symbol))
+        {
+            return Err(CliError::Usage(format!("{} is not a symbol of the grammar", unknown)));
+        }
+
+        let (first, nullable) = GrammarSets::new(grammar).first_of_sequence(&symbols);
+        let nullable = if nullable { " (nullable)" } else { "" };
+        Ok(format!("FIRST({}) = {}{}", argument, format_set(&first), nullable))
+    }
+
+    fn follow(&self, variable: &str) -> Result<String, CliError> {
+        let grammar = self.grammar()?;
+        if !grammar.is_variable(variable) {
+            return Err(CliError::Usage(format!("{} is not a variable of the grammar", variable)));
+        }
+
+        let sets = GrammarSets::new(grammar);
+        Ok(format!("FOLLOW({}) = {}", variable, format_set(&sets.follow[variable])))
+    }
+
+    fn sets(&self) -> Result<String, CliError> {
+        let grammar = self.grammar()?;
+        let sets = GrammarSets::new(grammar);
+
+        let rows: Vec<[String; 4]> = grammar
+            .variables()
+            .iter()
+            .map(|variable| {
+                let variable = variable.as_str();
+                [
+                    variable.to_string(),
+                    if sets.nullable.contains(variable) { "yes" } else { "no" }.to_string(),
+                    format_set(&sets.first[variable]),
+                    format_set(&sets.follow[variable]),
+                ]
+            })
+            .collect();
+
+        let header = ["Variable", "Nullable", "FIRST", "FOLLOW"].map(|s| s.to_string());
+        Ok(format_table(&header, &rows).trim_end().to_string())
+    }
+
+    fn parse(&self, sentence: &str) -> Result<String, CliError> {
+        let grammar = self.grammar()?;
+
+        let tokens = lexer::tokenize(grammar, sentence)?;
+        let terminals = lexer::terminals(&tokens);
+        let Some(mut tree) = earley::parse(grammar, &terminals, 1).pop() else {
+            return Err(CliError::Rejected(format!(
+                "\"{}\" is not in the language of the grammar",
+                format_sentence(&terminals)
+            )));
+        };
+
+        tree.attach_spans(&tokens);
+        Ok(tree.to_ascii().trim_end().to_string())
+    }
+
+    fn generate(&self, argument: &str) -> Result<String, CliError> {
+        let grammar = self.grammar()?;
+        let count = match argument {
+            "" => 5,
+            count => count
+                .parse()
+                .map_err(|_| CliError::Usage(format!("Invalid count '{}' for :generate", count)))?,
+        };
+
+        let sentences =
+            commands::generate_sentences(grammar, count, GENERATE_MAX_DEPTH, GENERATE_MAX_LEN, Rng::from_time())?;
+        Ok(sentences.iter().map(|sentence| format_sentence(sentence)).collect::<Vec<String>>().join("\n"))
+    }
+}
+
+/// Parses the rules of a single BNF line, dropping spans since they point into the line.
+fn parse_rules(line: &str) -> Result<Vec<ProductionRule>, CliError> {
+    let grammar = build_grammar(line)?;
+    Ok(grammar
+        .production_rules()
+        .iter()
+        .map(|rule| ProductionRule { span: None, ..rule.clone() })
+        .collect())
+}
+
+/// Runs the repl on stdin until `:quit` or the end of the input.
+pub fn run(grammar_path: Option<&str>) -> Result<(), CliError> {
+    let grammar = grammar_path.map(|path| commands::read_grammar(path, None)).transpose()?;
+    let mut repl = Repl::new(grammar);
+
+    println!("bnf_parser repl, type :help for a list of commands");
+    let mut stdin = io::stdin().lock();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            // end of input
+            println!();
+            return Ok(());
+        }
+
+        match repl.execute(&line) {
+            Ok(Reply::Output(output)) if output.is_empty() => {}
+            Ok(Reply::Output(output)) => println!("{}", output),
+            Ok(Reply::Quit) => return Ok(()),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(repl: &mut Repl, line: &str) -> String {
+        match repl.execute(line).unwrap() {
+            Reply::Output(output) => output,
+            Reply::Quit => panic!("unexpected quit"),
+        }
+    }
+
+    #[test]
+    fn test_edit_session() {
+        let mut repl = Repl::new(None);
+
+        assert!(repl.execute(":first <E>").is_err());
+        assert_eq!(output(&mut repl, "<E> ::= <E> + <T> | <T>"), "Added 2 rules");
+        assert_eq!(output(&mut repl, "<T> ::= id | ( <E> )"), "Added 2 rules");
+        assert_eq!(output(&mut repl, ":first <E>"), "FIRST(<E>) = { id, ( }");
+        assert_eq!(output(&mut repl, ":follow <T>"), "FOLLOW(<T>) = { $, +, ) }");
+        assert!(output(&mut repl, ":parse id + id").starts_with("<E>\n├── <E>"));
+
+        assert_eq!(output(&mut repl, ":delete <E> ::= <E> + <T>"), "Deleted 1 rule");
+        assert_eq!(repl.execute(":parse id + id").unwrap_err().exit_code(), crate::error::EXIT_REJECTED);
+        assert_eq!(output(&mut repl, ":show"), "<E> ::= <T>\n<T> ::= id | ( <E> )");
+
+        assert_eq!(output(&mut repl, ":delete <T>"), "Deleted 2 rules");
+        assert_eq!(output(&mut repl, ":show"), "<E> ::= <T>");
+    }
+
+    #[test]
+    fn test_commands() {
+        let grammar = build_grammar("<S> ::= a <S> b | ε").unwrap();
+        let mut repl = Repl::new(Some(grammar));
+
+        assert_eq!(
+            output(&mut repl, ":cnf"),
+            "<S'> ::= ε | <a> <S_1>\n<S> ::= <a> <S_1>\n<S_1> ::= <S> <b> | b\n<a> ::= a\n<b> ::= b"
+        );
+        assert_eq!(output(&mut repl, ":generate 3").lines().count(), 3);
+        assert_eq!(output(&mut repl, ":first <S> b"), "FIRST(<S> b) = { a, b }");
+        assert!(repl.execute(":frobnicate").is_err());
+        assert_eq!(repl.execute(":quit").unwrap(), Reply::Quit);
+    }
+}
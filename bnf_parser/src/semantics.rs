@@ -0,0 +1,467 @@
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::context_free_grammar::{format_rule, ContextFreeGrammar, ProductionRule};
+use crate::parse_tree::ParseTree;
+
+/// Attribute a bare `$$` or `$1` stands for.
+pub const VALUE: &str = "value";
+
+/// The value of an attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => write!(f, "{}", *number as i64),
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Text(text) => write!(f, "{}", text),
+            Value::Bool(bool) => write!(f, "{}", bool),
+        }
+    }
+}
+
+/// The attributes of a node of a parse tree, by name.
+pub type Attributes = IndexMap<String, Value>;
+
+/// A node a semantic action refers to: `$$` for the rule's lhs or `$1`, `$2`, ... for the symbols of its rhs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Node {
+    Lhs,
+    Child(usize),
+}
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Node::Lhs => write!(f, "$$"),
+            Node::Child(index) => write!(f, "${}", index),
+        }
+    }
+}
+
+/// An expression of the action language.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Attribute(Node, String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+/// A semantic action: assignments `node.attribute = expression` separated by `;`, run in order.
+///
+/// Assigning to `$$` sets a synthesized attribute of the lhs, which the parent reads as `$i.attribute`.
+/// Assigning to `$i` sets an inherited attribute of a variable of the rhs, which its own actions read as
+/// `$$.attribute`. A child is evaluated the first time one of its attributes is read, so its inherited
+/// attributes must be assigned before that.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Action {
+    pub statements: Vec<(Node, String, Expr)>,
+}
+impl Action {
+    /// Parses the text of an action, without its braces.
+    pub fn parse(text: &str) -> Result<Action, String> {
+        let mut parser = ActionParser { tokens: tokenize(text)?, position: 0 };
+        let mut statements = Vec::new();
+        while parser.peek().is_some() {
+            let (node, attribute) = match parser.next() {
+                Some(ActionToken::Attribute(node, attribute)) => (node, attribute),
+                _ => return Err("expected an assignment like $$ = $1 + $3".to_string()),
+            };
+            parser.expect("=")?;
+            statements.push((node, attribute, parser.expression()?));
+            if parser.peek().is_some() {
+                parser.expect(";")?;
+            }
+        }
+        Ok(Action { statements })
+    }
+
+    /// Checks that the action only refers to symbols of the rule's rhs and only assigns to variables.
+    pub fn check(&self, rhs_is_variable: &[bool]) -> Result<(), String> {
+        let in_range = |node: Node| match node {
+            Node::Child(index) if index == 0 || index > rhs_is_variable.len() => {
+                Err(format!("{} is out of range, the rule has {} symbols", node, rhs_is_variable.len()))
+            }
+            _ => Ok(()),
+        };
+        for (node, attribute, expr) in &self.statements {
+            in_range(*node)?;
+            if let Node::Child(index) = node {
+                if !rhs_is_variable[index - 1] {
+                    return Err(format!("can't assign to {}.{}, {} is a terminal", node, attribute, node));
+                }
+            }
+            expr.visit_nodes(&mut |node| in_range(node))?;
+        }
+        Ok(())
+    }
+}
+impl Expr {
+    fn visit_nodes(&self, visit: &mut impl FnMut(Node) -> Result<(), String>) -> Result<(), String> {
+        match self {
+            Expr::Literal(_) => Ok(()),
+            Expr::Attribute(node, _) => visit(*node),
+            Expr::Unary(_, operand) => operand.visit_nodes(visit),
+            Expr::Binary(_, left, right) => {
+                left.visit_nodes(visit)?;
+                right.visit_nodes(visit)
+            }
+            Expr::Conditional(condition, then, otherwise) => {
+                condition.visit_nodes(visit)?;
+                then.visit_nodes(visit)?;
+                otherwise.visit_nodes(visit)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ActionToken {
+    Number(f64),
+    Text(String),
+    Name(String),
+    Attribute(Node, String),
+    Operator(&'static str),
+}
+
+const OPERATORS: [&str; 20] =
+    ["==", "!=", "<=", ">=", "&&", "||", "=", "<", ">", "+", "-", "*", "/", "%", "!", "?", ":", ";", "(", ")"];
+
+fn tokenize(text: &str) -> Result<Vec<ActionToken>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix('$') {
+            let (node, after) = if let Some(after) = after.strip_prefix('$') {
+                (Node::Lhs, after)
+            } else {
+                let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                let index = after[..digits].parse().map_err(|_| "expected $$ or $ and a number like $1".to_string())?;
+                (Node::Child(index), &after[digits..])
+            };
+            let (attribute, after) = match after.strip_prefix('.') {
+                Some(after) => {
+                    let length = after.len() - after.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_').len();
+                    if length == 0 {
+                        return Err(format!("expected an attribute name after {}.", node));
+                    }
+                    (after[..length].to_string(), &after[length..])
+                }
+                None => (VALUE.to_string(), after),
+            };
+            tokens.push(ActionToken::Attribute(node, attribute));
+            rest = after;
+        } else if c.is_ascii_digit() {
+            let length = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.').len();
+            let number = rest[..length].parse().map_err(|_| format!("invalid number {}", &rest[..length]))?;
+            tokens.push(ActionToken::Number(number));
+            rest = &rest[length..];
+        } else if c.is_alphabetic() || c == '_' {
+            let length = rest.len() - rest.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_').len();
+            tokens.push(ActionToken::Name(rest[..length].to_string()));
+            rest = &rest[length..];
+        } else if let Some(after) = rest.strip_prefix('"') {
+            let end = after.find('"').ok_or("unterminated string")?;
+            tokens.push(ActionToken::Text(after[..end].to_string()));
+            rest = &after[end + 1..];
+        } else {
+            let operator = OPERATORS.into_iter().find(|operator| rest.starts_with(operator));
+            let operator = operator.ok_or_else(|| format!("unexpected '{}'", c))?;
+            tokens.push(ActionToken::Operator(operator));
+            rest = &rest[operator.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser of the action language, with the usual precedence of operators.
+struct ActionParser {
+    tokens: Vec<ActionToken>,
+    position: usize,
+}
+impl ActionParser {
+    fn peek(&self) -> Option<&ActionToken> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<ActionToken> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, operators: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(ActionToken::Operator(operator)) if operators.contains(operator) => {
+                let operator = *operator;
+                self.position += 1;
+                Some(operator)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, operator: &'static str) -> Result<(), String> {
+        self.eat(&[operator]).map(|_| ()).ok_or_else(|| format!("expected '{}'", operator))
+    }
+
+    fn expression(&mut self) -> Result<Expr, String> {
+        let condition = self.binary(0)?;
+        if self.eat(&["?"]).is_none() {
+            return Ok(condition);
+        }
+        let then = self.expression()?;
+        self.expect(":")?;
+        let otherwise = self.expression()?;
+        Ok(Expr::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise)))
+    }
+
+    /// Parses binary operators from the loosest binding level up.
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        const LEVELS: [&[&str]; 5] = [&["||"], &["&&"], &["==", "!=", "<", "<=", ">", ">="], &["+", "-"], &["*", "/", "%"]];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(operator) = self.eat(LEVELS[level]) {
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if let Some(operator) = self.eat(&["-", "!"]) {
+            return Ok(Expr::Unary(operator, Box::new(self.unary()?)));
+        }
+        match self.next() {
+            Some(ActionToken::Number(number)) => Ok(Expr::Literal(Value::Number(number))),
+            Some(ActionToken::Text(text)) => Ok(Expr::Literal(Value::Text(text))),
+            Some(ActionToken::Name(name)) if name == "true" || name == "false" => {
+                Ok(Expr::Literal(Value::Bool(name == "true")))
+            }
+            Some(ActionToken::Attribute(node, attribute)) => Ok(Expr::Attribute(node, attribute)),
+            Some(ActionToken::Operator("(")) => {
+                let expr = self.expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(token) => Err(format!("unexpected {:?} in expression", token)),
+            None => Err("expected an expression".to_string()),
+        }
+    }
+}
+
+/// Evaluates the semantic actions of a grammar over a parse tree, returning the attributes of its root.
+///
+/// A terminal's `value` is its text, as a number if it is one. A rule without an action copies the
+/// value of its first symbol, if it has one, as if it were `{ $$ = $1 }`.
+pub fn evaluate(grammar: &ContextFreeGrammar, tree: &ParseTree) -> Result<Attributes, String> {
+    evaluate_node(grammar, tree, Attributes::new())
+}
+
+fn evaluate_node(grammar: &ContextFreeGrammar, tree: &ParseTree, inherited: Attributes) -> Result<Attributes, String> {
+    let (name, children) = match tree {
+        ParseTree::Terminal { name, text, .. } => {
+            let text = text.as_deref().unwrap_or(name);
+            let value = text.parse().map_or_else(|_| Value::Text(text.to_string()), Value::Number);
+            return Ok(Attributes::from([(VALUE.to_string(), value)]));
+        }
+        ParseTree::Variable { name, children } => (name, children),
+        ParseTree::Error { expected, .. } => {
+            return Err(format!("the tree has a syntax error, expected {}", expected.join(" or ")));
+        }
+    };
+
+    let rhs: Vec<&str> = children.iter().map(|child| child.name()).collect();
+    let rule = ProductionRule::new(name, &rhs);
+    let rule = grammar.production_rules().get(&rule).ok_or_else(|| format!("the grammar has no rule {}", format_rule(&rule)))?;
+    let action = match &rule.action {
+        Some(text) => Action::parse(text).map_err(|e| format!("in {}: {}", format_rule(rule), e))?,
+        None if rhs.is_empty() => Action { statements: Vec::new() },
+        None => Action {
+            statements: vec![(Node::Lhs, VALUE.to_string(), Expr::Attribute(Node::Child(1), VALUE.to_string()))],
+        },
+    };
+
+    let mut evaluation = Evaluation {
+        grammar,
+        rule,
+        children,
+        attributes: inherited,
+        inherited: vec![Attributes::new(); children.len()],
+        evaluated: vec![None; children.len()],
+    };
+    for (node, attribute, expr) in &action.statements {
+        let value = evaluation.eval(expr)?;
+        match node {
+            Node::Lhs => {
+                evaluation.attributes.insert(attribute.clone(), value);
+            }
+            Node::Child(index) => {
+                if evaluation.evaluated[index - 1].is_some() {
+                    return Err(evaluation.error(format!("{}.{} is assigned after {} was evaluated", node, attribute, node)));
+                }
+                evaluation.inherited[index - 1].insert(attribute.clone(), value);
+            }
+        }
+    }
+
+    Ok(evaluation.attributes)
+}
+
+/// The state of evaluating the action of one node: its own attributes and those of its children so far.
+/// Errors are reported with the rule they happen in, so those from children are passed on unchanged.
+struct Evaluation<'t, 'g> {
+    grammar: &'g ContextFreeGrammar,
+    rule: &'g ProductionRule,
+    children: &'t [ParseTree],
+    attributes: Attributes,
+    inherited: Vec<Attributes>,
+    evaluated: Vec<Option<Attributes>>,
+}
+impl Evaluation<'_, '_> {
+    fn error(&self, message: String) -> String {
+        format!("in {}: {}", format_rule(self.rule), message)
+    }
+
+    fn attribute(&mut self, node: Node, attribute: &str) -> Result<Value, String> {
+        let attributes = match node {
+            Node::Lhs => &self.attributes,
+            Node::Child(index) => {
+                if self.evaluated[index - 1].is_none() {
+                    let inherited = std::mem::take(&mut self.inherited[index - 1]);
+                    self.evaluated[index - 1] = Some(evaluate_node(self.grammar, &self.children[index - 1], inherited)?);
+                }
+                self.evaluated[index - 1].as_ref().expect("evaluated above")
+            }
+        };
+        match attributes.get(attribute) {
+            Some(value) => Ok(value.clone()),
+            None => Err(self.error(format!("{}.{} is not defined", node, attribute))),
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value, String> {
+        match expr {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Attribute(node, attribute) => self.attribute(*node, attribute),
+            Expr::Unary(operator, operand) => match (*operator, self.eval(operand)?) {
+                ("-", Value::Number(number)) => Ok(Value::Number(-number)),
+                ("!", Value::Bool(bool)) => Ok(Value::Bool(!bool)),
+                (operator, value) => Err(self.error(format!("can't apply {} to {}", operator, value))),
+            },
+            Expr::Binary(operator @ ("&&" | "||"), left, right) => {
+                let Value::Bool(left) = self.eval(left)? else {
+                    return Err(self.error(format!("{} needs true or false on its left", operator)));
+                };
+                if left == (*operator == "||") {
+                    return Ok(Value::Bool(left));
+                }
+                match self.eval(right)? {
+                    Value::Bool(right) => Ok(Value::Bool(right)),
+                    _ => Err(self.error(format!("{} needs true or false on its right", operator))),
+                }
+            }
+            Expr::Binary(operator, left, right) => {
+                let (left, right) = (self.eval(left)?, self.eval(right)?);
+                binary(operator, left, right).map_err(|e| self.error(e))
+            }
+            Expr::Conditional(condition, then, otherwise) => match self.eval(condition)? {
+                Value::Bool(true) => self.eval(then),
+                Value::Bool(false) => self.eval(otherwise),
+                value => Err(self.error(format!("the condition of ?: must be true or false, not {}", value))),
+            },
+        }
+    }
+}
+
+fn binary(operator: &str, left: Value, right: Value) -> Result<Value, String> {
+    use Value::{Bool, Number, Text};
+    match (operator, left, right) {
+        ("==", left, right) => Ok(Bool(left == right)),
+        ("!=", left, right) => Ok(Bool(left != right)),
+        ("+", Number(left), Number(right)) => Ok(Number(left + right)),
+        ("+", left @ Text(_), right) | ("+", left, right @ Text(_)) => Ok(Text(format!("{}{}", left, right))),
+        ("-", Number(left), Number(right)) => Ok(Number(left - right)),
+        ("*", Number(left), Number(right)) => Ok(Number(left * right)),
+        ("/" | "%", Number(_), Number(0.0)) => Err("division by zero".to_string()),
+        ("/", Number(left), Number(right)) => Ok(Number(left / right)),
+        ("%", Number(left), Number(right)) => Ok(Number(left % right)),
+        ("<", Number(left), Number(right)) => Ok(Bool(left < right)),
+        ("<=", Number(left), Number(right)) => Ok(Bool(left <= right)),
+        (">", Number(left), Number(right)) => Ok(Bool(left > right)),
+        (">=", Number(left), Number(right)) => Ok(Bool(left >= right)),
+        ("<", Text(left), Text(right)) => Ok(Bool(left < right)),
+        ("<=", Text(left), Text(right)) => Ok(Bool(left <= right)),
+        (">", Text(left), Text(right)) => Ok(Bool(left > right)),
+        (">=", Text(left), Text(right)) => Ok(Bool(left >= right)),
+        (operator, left, right) => Err(format!("can't apply {} to {} and {}", operator, left, right)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+    use crate::earley;
+    use crate::lexer;
+
+    fn eval(bnf: &str, input: &str) -> Result<Attributes, String> {
+        let grammar = build_grammar(bnf).unwrap();
+        let tokens = lexer::tokenize(&grammar, input).unwrap();
+        let mut tree = earley::parse(&grammar, &lexer::terminals(&tokens), 1).remove(0);
+        tree.attach_spans(&tokens);
+        evaluate(&grammar, &tree)
+    }
+
+    const CALCULATOR: &str = "@token <num> = /[0-9]+/
+<E> ::= <E> + <T> { $$ = $1 + $3 } | <E> - <T> { $$ = $1 - $3 } | <T>
+<T> ::= <T> * <F> { $$ = $1 * $3 } | <T> / <F> { $$ = $1 / $3 } | <F>
+<F> ::= <num> | ( <E> ) { $$ = $2 } | - <F> { $$ = -$2 }";
+
+    #[test]
+    fn test_synthesized_attributes() {
+        assert_eq!(eval(CALCULATOR, "2 + 3 * (4 - 1)").unwrap()[VALUE], Value::Number(11.0));
+        assert_eq!(eval(CALCULATOR, "7 / 2").unwrap()[VALUE].to_string(), "3.5");
+        assert_eq!(eval(CALCULATOR, "1 / (2 - 2)").unwrap_err(), "in <T> ::= <T> / <F>: division by zero");
+
+        // several attributes, strings and conditionals
+        let bnf = "<S> ::= <S> <C> { $$.count = $1.count + 1; $$ = $1 + $2 } | <C> { $$.count = 1; $$ = $1 }
+<C> ::= a | b { $$ = $1 == \"b\" ? \"B\" : \"?\" }";
+        let attributes = eval(bnf, "a b a").unwrap();
+        assert_eq!(attributes[VALUE], Value::Text("aBa".to_string()));
+        assert_eq!(attributes["count"], Value::Number(3.0));
+    }
+
+    #[test]
+    fn test_inherited_attributes() {
+        // an LL grammar for subtraction, which is left associative, passing the value so far down the tree
+        let bnf = "@token <num> = /[0-9]+/
+<E> ::= <num> <R> { $2.acc = $1; $$ = $2 }
+<R> ::= - <num> <R> { $3.acc = $$.acc - $2; $$ = $3 } | ε { $$ = $$.acc }";
+        assert_eq!(eval(bnf, "10 - 3 - 2").unwrap()[VALUE], Value::Number(5.0));
+
+        let bnf = "<S> ::= <A> { $$ = $1; $1.x = 1 }\n<A> ::= a";
+        assert_eq!(eval(bnf, "a").unwrap_err(), "in <S> ::= <A>: $1.x is assigned after $1 was evaluated");
+        let bnf = "<S> ::= <A> { $$ = $1 }\n<A> ::= a { $$ = $$.missing }";
+        assert_eq!(eval(bnf, "a").unwrap_err(), "in <A> ::= a: $$.missing is not defined");
+    }
+
+    #[test]
+    fn test_action_errors() {
+        let error = |bnf: &str| build_grammar(bnf).unwrap_err().to_string();
+        assert_eq!(error("<S> ::= a { $$ = $2 }"), "line 1: invalid action: $2 is out of range, the rule has 1 symbols");
+        assert_eq!(error("<S> ::= a { $1.x = 1 }"), "line 1: invalid action: can't assign to $1.x, $1 is a terminal");
+        assert_eq!(error("<S> ::= a { $$ = }"), "line 1: invalid action: expected an expression");
+        assert_eq!(error("<S> ::= a { $$ = 1 } b"), "line 1: a semantic action must end its alternative");
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use indexmap::IndexSet;
+
+use crate::context_free_grammar::{ContextFreeGrammar, SymbolId};
+
+/// Symbol marking the end of the input in FOLLOW sets.
+pub const END_MARKER: &str = "$";
+
+/// Computes the set of variables that can derive the empty string.
+pub fn nullable_variables(grammar: &ContextFreeGrammar) -> HashSet<&str> {
+    nullable_ids(grammar)
+        .iter()
+        .enumerate()
+        .filter(|(_, &nullable)| nullable)
+        .map(|(index, _)| grammar.variables()[index].as_str())
+        .collect()
+}
+
+/// Computes which variables can derive the empty string, by variable id.
+/// Each rule counts the rhs symbols not yet known to be nullable, and its lhs becomes nullable when
+/// the count reaches zero, so every rule is only looked at again when one of its symbols changes.
+pub fn nullable_ids(grammar: &ContextFreeGrammar) -> Vec<bool> {
+    let mut nullable = vec![false; grammar.variables().len()];
+    let mut remaining: Vec<usize> = Vec::new();
+    let mut occurrences: Vec<Vec<usize>> = vec![Vec::new(); grammar.variables().len()];
+    let mut worklist: Vec<SymbolId> = Vec::new();
+
+    for (index, rule) in grammar.indexed_rules().iter().enumerate() {
+        if rule.rhs.iter().any(|&symbol| !grammar.is_variable_id(symbol)) {
+            // a rule with a terminal is never nullable
+            remaining.push(usize::MAX);
+            continue;
+        }
+
+        remaining.push(rule.rhs.len());
+        for symbol in &rule.rhs {
+            occurrences[symbol.index()].push(index);
+        }
+        if rule.rhs.is_empty() && !nullable[rule.lhs.index()] {
+            nullable[rule.lhs.index()] = true;
+            worklist.push(rule.lhs);
+        }
+    }
+
+    while let Some(variable) = worklist.pop() {
+        for &rule in &occurrences[variable.index()] {
+            remaining[rule] -= 1;
+            let lhs = grammar.indexed_rules()[rule].lhs;
+            if remaining[rule] == 0 && !nullable[lhs.index()] {
+                nullable[lhs.index()] = true;
+                worklist.push(lhs);
+            }
+        }
+    }
+
+    nullable
+}
+
+/// Fixed size set of small integers stored as bits.
+#[derive(Debug, Clone, PartialEq)]
+struct BitSet {
+    words: Vec<u64>,
+}
+impl BitSet {
+    fn new(len: usize) -> BitSet {
+        BitSet { words: vec![0; len.div_ceil(64)] }
+    }
+
+    fn insert(&mut self, bit: usize) -> bool {
+        let (word, mask) = (bit / 64, 1 << (bit % 64));
+        let inserted = self.words[word] & mask == 0;
+        self.words[word] |= mask;
+        inserted
+    }
+
+    /// Adds every bit of `other`, returning true if any was new.
+    fn union_with(&mut self, other: &BitSet) -> bool {
+        let mut changed = false;
+        for (word, other_word) in self.words.iter_mut().zip(&other.words) {
+            changed |= *other_word & !*word != 0;
+            *word |= other_word;
+        }
+        changed
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(index, &word)| {
+            (0..64).filter(move |bit| word & (1 << bit) != 0).map(move |bit| index * 64 + bit)
+        })
+    }
+}
+
+/// FIRST and FOLLOW sets of every variable of a grammar.
+/// Whether a variable derives the empty string is kept in `nullable` rather than as epsilon in FIRST.
+/// Sets list `$` first and then terminals in the grammar's order.
+pub struct GrammarSets<'g> {
+    grammar: &'g ContextFreeGrammar,
+    nullable_ids: Vec<bool>,
+    /// FIRST sets by variable id, with bit 0 standing for `$` and bit `1 + t` for the terminal `terminals()[t]`.
+    first_bits: Vec<BitSet>,
+    pub nullable: HashSet<&'g str>,
+    pub first: HashMap<&'g str, IndexSet<&'g str>>,
+    pub follow: HashMap<&'g str, IndexSet<&'g str>>,
+}
+impl<'g> GrammarSets<'g> {
+    pub fn new(grammar: &'g ContextFreeGrammar) -> GrammarSets<'g> {
+        let nullable_ids = nullable_ids(grammar);
+        let first_bits = first_sets(grammar, &nullable_ids);
+        let follow_bits = follow_sets(grammar, &nullable_ids, &first_bits);
+
+        let named = |sets: &[BitSet]| -> HashMap<&'g str, IndexSet<&'g str>> {
+            grammar
+                .variables()
+                .iter()
+                .zip(sets)
+                .map(|(variable, set)| (variable.as_str(), set.iter().map(|bit| terminal_name(grammar, bit)).collect()))
+                .collect()
+        };
+
+        GrammarSets {
+            grammar,
+            nullable: grammar
+                .variables()
+                .iter()
+                .zip(&nullable_ids)
+                .filter(|(_, &nullable)| nullable)
+                .map(|(variable, _)| variable.as_str())
+                .collect(),
+            first: named(&first_bits),
+            follow: named(&follow_bits),
+            nullable_ids,
+            first_bits,
+        }
+    }
+
+    /// Returns the terminals that can begin a string derived from a sequence of symbols,
+    /// and whether the whole sequence can derive the empty string.
+    pub fn first_of_sequence<S: AsRef<str>>(&self, symbols: &[S]) -> (IndexSet<&'g str>, bool) {
+        // a symbol the grammar doesn't know derives nothing, so only the symbols before it count
+        let mut ids = Vec::new();
+        let mut all_known = true;
+        for symbol in symbols {
+            match self.grammar.symbol_id(symbol.as_ref()) {
+                Some(id) => ids.push(id),
+                None => {
+                    all_known = false;
+                    break;
+                }
+            }
+        }
+
+        let (first, nullable) = self.first_of_ids(&ids);
+        (self.names(&first), nullable && all_known)
+    }
+
+    /// Returns the FIRST set of a sequence of interned symbols and whether it can derive the empty string.
+    fn first_of_ids(&self, symbols: &[SymbolId]) -> (BitSet, bool) {
+        let mut first = BitSet::new(self.grammar.terminals().len() + 1);
+
+        for &symbol in symbols {
+            if self.grammar.is_variable_id(symbol) {
+                first.union_with(&self.first_bits[symbol.index()]);
+                if !self.nullable_ids[symbol.index()] {
+                    return (first, false);
+                }
+            } else {
+                first.insert(terminal_bit(self.grammar, symbol));
+                return (first, false);
+            }
+        }
+
+        (first, true)
+    }
+
+    fn names(&self, set: &BitSet) -> IndexSet<&'g str> {
+        set.iter().map(|bit| terminal_name(self.grammar, bit)).collect()
+    }
+}
+
+/// FIRST_k and FOLLOW_k sets of every variable of a grammar: the strings of up to k terminals that can
+/// begin what a variable derives, and that can come after it. A string shorter than k in FIRST_k is all
+/// the variable derives, with the empty string standing for epsilon. Strings in FOLLOW_k have k terminals
+/// unless they end with `$`, the end of the input.
+pub struct LookaheadSets<'g> {
+    grammar: &'g ContextFreeGrammar,
+    pub k: usize,
+    pub first: HashMap<&'g str, BTreeSet<Vec<&'g str>>>,
+    pub follow: HashMap<&'g str, BTreeSet<Vec<&'g str>>>,
+}
+impl<'g> LookaheadSets<'g> {
+    pub fn new(grammar: &'g ContextFreeGrammar, k: usize) -> LookaheadSets<'g> {
+        let mut sets = LookaheadSets {
+            grammar,
+            k,
+            first: grammar.variables().iter().map(|variable| (variable.as_str(), BTreeSet::new())).collect(),
+            follow: grammar.variables().iter().map(|variable| (variable.as_str(), BTreeSet::new())).collect(),
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in grammar.production_rules() {
+                let first = sets.first_of_sequence(&rule.rhs);
+                let lhs_first = sets.first.get_mut(rule.lhs.as_str()).expect("every lhs is a variable");
+                for string in first {
+                    changed |= lhs_first.insert(string);
+                }
+            }
+        }
+
+        if let Some(start) = sets.follow.get_mut(grammar.start_symbol()) {
+            start.insert(vec![END_MARKER]);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in grammar.production_rules() {
+                for (index, symbol) in rule.rhs.iter().enumerate() {
+                    if !grammar.is_variable(symbol) {
+                        continue;
+                    }
+                    let follow = sets.follow_of_sequence(&rule.rhs[index + 1..], &rule.lhs);
+                    let symbol_follow = sets.follow.get_mut(symbol.as_str()).expect("checked to be a variable");
+                    for string in follow {
+                        changed |= symbol_follow.insert(string);
+                    }
+                }
+            }
+        }
+
+        sets
+    }
+
+    /// Returns the strings of up to k terminals that can begin a string derived from a sequence of symbols.
+    pub fn first_of_sequence<S: AsRef<str>>(&self, symbols: &[S]) -> BTreeSet<Vec<&'g str>> {
+        let mut first = BTreeSet::from([Vec::new()]);
+        for symbol in symbols {
+            let symbol_first = match self.grammar.symbol_id(symbol.as_ref()) {
+                Some(id) if self.grammar.is_variable_id(id) => self.first[self.grammar.symbol(id)].clone(),
+                Some(id) => BTreeSet::from([vec![self.grammar.symbol(id)]]),
+                // a symbol the grammar doesn't know derives nothing
+                None => BTreeSet::new(),
+            };
+            first = concat_k(&first, &symbol_first, self.k);
+            if first.iter().all(|string| string.len() == self.k) {
+                break;
+            }
+        }
+        first
+    }
+
+    /// Returns the lookaheads of k terminals, or fewer ending with `$`, that can follow a sequence of symbols
+    /// at the end of a rule for a variable.
+    pub fn follow_of_sequence<S: AsRef<str>>(&self, symbols: &[S], variable: &str) -> BTreeSet<Vec<&'g str>> {
+        concat_k(&self.first_of_sequence(symbols), &self.follow[variable], self.k)
+    }
+}
+
+/// Concatenates every string of one set with every string of another, cutting them to at most k symbols.
+/// Strings ending with `$` are already complete.
+fn concat_k<'g>(prefixes: &BTreeSet<Vec<&'g str>>, suffixes: &BTreeSet<Vec<&'g str>>, k: usize) -> BTreeSet<Vec<&'g str>> {
+    let mut strings = BTreeSet::new();
+    for prefix in prefixes {
+        if prefix.len() >= k || prefix.last() == Some(&END_MARKER) {
+            strings.insert(prefix.clone());
+            continue;
+        }
+        for suffix in suffixes {
+            let mut string = prefix.clone();
+            string.extend(suffix.iter().take(k - prefix.len()));
+            strings.insert(string);
+        }
+    }
+    strings
+}
+
+/// Returns the bit standing for a terminal in FIRST and FOLLOW bit sets.
+fn terminal_bit(grammar: &ContextFreeGrammar, terminal: SymbolId) -> usize {
+    1 + terminal.index() - grammar.variables().len()
+}
+
+/// Returns the terminal, or `$`, a bit of a FIRST or FOLLOW bit set stands for.
+fn terminal_name(grammar: &ContextFreeGrammar, bit: usize) -> &str {
+    if bit == 0 {
+        END_MARKER
+    } else {
+        &grammar.terminals()[bit - 1]
+    }
+}
+
+/// Computes the FIRST set of every variable. Rules are revisited only when the FIRST set of a
+/// variable on their right hand side grows.
+fn first_sets(grammar: &ContextFreeGrammar, nullable: &[bool]) -> Vec<BitSet> {
+    let set_len = grammar.terminals().len() + 1;
+    let mut first = vec![BitSet::new(set_len); grammar.variables().len()];
+
+    let mut users: Vec<Vec<usize>> = vec![Vec::new(); grammar.variables().len()];
+    for (index, rule) in grammar.indexed_rules().iter().enumerate() {
+        for &symbol in &rule.rhs {
+            if grammar.is_variable_id(symbol) {
+                users[symbol.index()].push(index);
+            }
+        }
+    }
+
+    let mut worklist: Vec<usize> = (0..grammar.indexed_rules().len()).rev().collect();
+    let mut queued = vec![true; grammar.indexed_rules().len()];
+    while let Some(index) = worklist.pop() {
+        queued[index] = false;
+        let rule = &grammar.indexed_rules()[index];
+
+        let mut additions = BitSet::new(set_len);
+        for &symbol in &rule.rhs {
+            if grammar.is_variable_id(symbol) {
+                additions.union_with(&first[symbol.index()]);
+                if !nullable[symbol.index()] {
+                    break;
+                }
+            } else {
+                additions.insert(terminal_bit(grammar, symbol));
+                break;
+            }
+        }
+
+        if first[rule.lhs.index()].union_with(&additions) {
+            for &user in &users[rule.lhs.index()] {
+                if !queued[user] {
+                    queued[user] = true;
+                    worklist.push(user);
+                }
+            }
+        }
+    }
+
+    first
+}
+
+/// Computes the FOLLOW set of every variable, with `$` following the start symbol.
+/// Each `<A> ::= α <B> β` adds FIRST(β) to FOLLOW(<B>) once, and if β is nullable makes FOLLOW(<A>)
+/// flow into FOLLOW(<B>), which is then propagated along those edges until nothing changes.
+fn follow_sets(grammar: &ContextFreeGrammar, nullable: &[bool], first: &[BitSet]) -> Vec<BitSet> {
+    let set_len = grammar.terminals().len() + 1;
+    let mut follow = vec![BitSet::new(set_len); grammar.variables().len()];
+    let mut flows_into: Vec<Vec<usize>> = vec![Vec::new(); grammar.variables().len()];
+
+    if let Some(start) = grammar.symbol_id(grammar.start_symbol()) {
+        follow[start.index()].insert(0);
+    }
+
+    for rule in grammar.indexed_rules() {
+        // FIRST of the symbols after each position, built from the right
+        let mut rest_first = BitSet::new(set_len);
+        let mut rest_nullable = true;
+        for &symbol in rule.rhs.iter().rev() {
+            if grammar.is_variable_id(symbol) {
+                follow[symbol.index()].union_with(&rest_first);
+                if rest_nullable && symbol != rule.lhs {
+                    flows_into[rule.lhs.index()].push(symbol.index());
+                }
+
+                if nullable[symbol.index()] {
+                    rest_first.union_with(&first[symbol.index()]);
+                } else {
+                    rest_first = first[symbol.index()].clone();
+                    rest_nullable = false;
+                }
+            } else {
+                rest_first = BitSet::new(set_len);
+                rest_first.insert(terminal_bit(grammar, symbol));
+                rest_nullable = false;
+            }
+        }
+    }
+
+    let mut worklist: Vec<usize> = (0..grammar.variables().len()).collect();
+    while let Some(variable) = worklist.pop() {
+        for &target in &flows_into[variable] {
+            let source = follow[variable].clone();
+            if follow[target].union_with(&source) {
+                worklist.push(target);
+            }
+        }
+    }
+
+    follow
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_free_grammar::build_grammar;
+
+    fn set<'a>(symbols: &[&'a str]) -> IndexSet<&'a str> {
+        symbols.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_first_and_follow() {
+        let grammar = build_grammar(
+            "<E> ::= <T> <E'>
+             <E'> ::= + <T> <E'> | ε
+             <T> ::= <F> <T'>
+             <T'> ::= * <F> <T'> | ε
+             <F> ::= ( <E> ) | id",
+        )
+        .unwrap();
+        let sets = GrammarSets::new(&grammar);
+
+        assert_eq!(sets.nullable, ["<E'>", "<T'>"].into_iter().collect());
+        assert_eq!(sets.first["<E>"], set(&["(", "id"]));
+        assert_eq!(sets.first["<E'>"], set(&["+"]));
+        assert_eq!(sets.follow["<E>"], set(&["$", ")"]));
+        assert_eq!(sets.follow["<T>"], set(&["+", "$", ")"]));
+        assert_eq!(sets.follow["<F>"], set(&["*", "+", "$", ")"]));
+    }
+
+    #[test]
+    fn test_first_of_sequence() {
+        let grammar = build_grammar("<S> ::= <A> b\n<A> ::= a | ε").unwrap();
+        let sets = GrammarSets::new(&grammar);
+
+        assert_eq!(sets.first_of_sequence(&["<A>", "b"]), (set(&["a", "b"]), false));
+        assert_eq!(sets.first_of_sequence(&["<A>"]), (set(&["a"]), true));
+        assert_eq!(sets.first_of_sequence::<&str>(&[]), (set(&[]), true));
+    }
+
+    fn strings<'a>(strings: &[&'a str]) -> BTreeSet<Vec<&'a str>> {
+        strings.iter().map(|string| string.split_whitespace().collect()).collect()
+    }
+
+    #[test]
+    fn test_lookahead_sets() {
+        let grammar = build_grammar("<S> ::= <A> a b | <A> a c\n<A> ::= a <A> | ε").unwrap();
+        let sets = LookaheadSets::new(&grammar, 2);
+
+        assert_eq!(sets.first["<A>"], strings(&["", "a", "a a"]));
+        assert_eq!(sets.first["<S>"], strings(&["a a", "a b", "a c"]));
+        assert_eq!(sets.follow["<S>"], strings(&["$"]));
+        assert_eq!(sets.follow["<A>"], strings(&["a b", "a c"]));
+        assert_eq!(sets.follow_of_sequence(&["b"], "<S>"), strings(&["b $"]));
+        assert_eq!(LookaheadSets::new(&grammar, 3).first["<S>"], strings(&["a a a", "a a b", "a a c", "a b", "a c"]));
+    }
+
+    #[test]
+    fn test_nullable_through_chains() {
+        let grammar = build_grammar("<S> ::= <A> <B>\n<A> ::= <B> <B>\n<B> ::= <C>\n<C> ::= ε | c\n<D> ::= <D> d").unwrap();
+
+        assert_eq!(nullable_variables(&grammar), ["<S>", "<A>", "<B>", "<C>"].into_iter().collect());
+    }
+}
@@ -0,0 +1,307 @@
+//! The `grammar!` macro, which checks a BNF grammar while the crate using it compiles and expands to a
+//! recursive-descent parser for it, as generated by `bnf_parser codegen`.
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+use bnf_parser::context_free_grammar::{build_grammar, ContextFreeGrammar};
+use bnf_parser::{codegen, ll, lint, LintCode};
+
+/// Checks an LL(1) grammar at compile time and expands to a module with a recursive-descent parser for it.
+///
+/// The macro starts with the module to declare, as in `mod calc;` or `pub mod calc;`, followed by the
+/// grammar, one rule per line. Rust comments are ignored, but the grammar has to be valid Rust tokens:
+/// quotes and brackets must match. A grammar that isn't can be given as a string literal instead.
+/// An empty alternative can be written as `ε`, but rustc warns that it is a confusable character, so
+/// it is better left empty.
+/// The module has the items described in `bnf_parser::codegen::generate_parser`, including `parse`.
+///
+/// ```
+/// bnf_parser_macros::grammar! {
+///     mod list;
+///     @token <num> = /[0-9]+/
+///     <L> ::= <num> <M>
+///     <M> ::= , <num> <M> |  // a comma separated list of numbers
+/// }
+///
+/// assert!(list::parse("1, 22 ,3").is_ok());
+/// assert_eq!(list::parse("1,").unwrap_err().to_string(), "expected <num> but found the end of the input at bytes 2..2");
+///
+/// bnf_parser_macros::grammar!(mod quoted; r#"<S> ::= " <S> " | a"#);
+/// assert!(quoted::parse("\" \" a \" \"").is_ok());
+/// ```
+///
+/// A grammar that can't be built, uses a variable without rules or isn't LL(1) is a compile error,
+/// pointing at the rule it is about:
+///
+/// ```compile_fail
+/// bnf_parser_macros::grammar! {
+///     mod sum;
+///     <E> ::= <E> + <T> | <T>
+///     <T> ::= id
+/// }
+/// ```
+///
+/// ```compile_fail
+/// bnf_parser_macros::grammar! {
+///     mod undefined;
+///     <S> ::= a <X>
+/// }
+/// ```
+#[proc_macro]
+pub fn grammar(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(output) => output,
+        Err(error) => error.to_compile_error(),
+    }
+}
+
+/// A compile error, which the compiler shows for the source from `start` to `end`.
+struct Error {
+    message: String,
+    start: Span,
+    end: Span,
+}
+impl Error {
+    fn new(message: &str, span: Span) -> Error {
+        Error { message: message.to_string(), start: span, end: span }
+    }
+
+    /// Returns `::core::compile_error! { "message" }`. The compiler reports an error in a macro's output
+    /// at the spans of its first and last tokens, so they are set to `start` and `end`.
+    fn to_compile_error(&self) -> TokenStream {
+        let punct = |c: char, spacing: Spacing| {
+            let mut punct = Punct::new(c, spacing);
+            punct.set_span(self.start);
+            TokenTree::Punct(punct)
+        };
+        let mut message = Literal::string(&self.message);
+        message.set_span(self.end);
+        let mut group = Group::new(Delimiter::Brace, TokenStream::from(TokenTree::Literal(message)));
+        group.set_span(self.end);
+
+        TokenStream::from_iter([
+            punct(':', Spacing::Joint),
+            punct(':', Spacing::Alone),
+            TokenTree::Ident(Ident::new("core", self.start)),
+            punct(':', Spacing::Joint),
+            punct(':', Spacing::Alone),
+            TokenTree::Ident(Ident::new("compile_error", self.start)),
+            punct('!', Spacing::Alone),
+            TokenTree::Group(group),
+        ])
+    }
+}
+
+fn expand(input: TokenStream) -> Result<TokenStream, Error> {
+    let mut tokens: Vec<TokenTree> = input.into_iter().collect();
+    let header = module_header(&mut tokens)?;
+
+    let source = match tokens.as_slice() {
+        [TokenTree::Literal(literal)] => Source::from_literal(literal)?,
+        _ => Source::from_tokens(tokens),
+    };
+    let grammar = check_grammar(&source)?;
+
+    let parser = codegen::generate_parser(&grammar).map_err(|e| Error::new(&e.to_string(), Span::call_site()))?;
+    let parser = parser.parse::<TokenStream>().map_err(|e| Error::new(&e.to_string(), Span::call_site()))?;
+
+    let mut output = TokenStream::from_iter(header);
+    output.extend([TokenTree::Group(Group::new(Delimiter::Brace, parser))]);
+    Ok(output)
+}
+
+/// Removes the leading `mod name;`, with any visibility, from the tokens, returning it without the `;`.
+fn module_header(tokens: &mut Vec<TokenTree>) -> Result<Vec<TokenTree>, Error> {
+    let is_ident = |token: Option<&TokenTree>, name: &str| matches!(token, Some(TokenTree::Ident(ident)) if ident.to_string() == name);
+    let mut length = 0;
+    if is_ident(tokens.first(), "pub") {
+        length += 1;
+        if matches!(tokens.get(1), Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis) {
+            length += 1;
+        }
+    }
+
+    let name = matches!(tokens.get(length + 1), Some(TokenTree::Ident(_)));
+    let semicolon = matches!(tokens.get(length + 2), Some(TokenTree::Punct(punct)) if punct.as_char() == ';');
+    if !is_ident(tokens.get(length), "mod") || !name || !semicolon {
+        let span = tokens.first().map_or_else(Span::call_site, |token| token.span());
+        return Err(Error::new("expected the module to declare, as in `mod calc;`, before the grammar", span));
+    }
+
+    let header = tokens.drain(..length + 3).take(length + 2).collect();
+    Ok(header)
+}
+
+/// The text of a grammar, with where each part of it is in the macro's input.
+struct Source {
+    text: String,
+    /// Whether the text is a string literal, which the compiler can only point at as a whole.
+    literal: bool,
+    /// Spans of the tokens of the text with their byte ranges and lines in the text.
+    spans: Vec<(Span, usize, usize, usize)>,
+}
+impl Source {
+    /// Writes out tokens with the lines and columns they have in the Rust source, so the grammar keeps
+    /// its lines, and so `<E>` and `::=`, which are several tokens to Rust, are written without spaces.
+    fn from_tokens(tokens: Vec<TokenTree>) -> Source {
+        let mut flattened = Vec::new();
+        flatten(tokens, &mut flattened);
+
+        let mut source = Source { text: String::new(), literal: false, spans: Vec::new() };
+        let mut position = flattened.first().map(|(_, span)| (span.line(), 1));
+        let mut text_line = 1;
+        for (text, span) in flattened {
+            let (line, column) = position.expect("set above for a non-empty list");
+            if span.line() > line {
+                source.text.push_str(&"\n".repeat(span.line() - line));
+                text_line += span.line() - line;
+                source.text.push_str(&" ".repeat(span.column().saturating_sub(1)));
+            } else if span.column() >= column {
+                source.text.push_str(&" ".repeat(span.column() - column));
+            } else {
+                // tokens from another macro's expansion, whose positions say nothing about spacing
+                source.text.push(' ');
+            }
+
+            let start = source.text.len();
+            source.text.push_str(&text);
+            source.spans.push((span, start, source.text.len(), text_line));
+            text_line += text.matches('\n').count();
+            position = Some((span.end().line(), span.end().column()));
+        }
+        source
+    }
+
+    /// Reads the grammar from a string literal, whose span is the best the compiler can point at.
+    fn from_literal(literal: &Literal) -> Result<Source, Error> {
+        let text = unquote(&literal.to_string())
+            .ok_or_else(|| Error::new("expected the grammar as rules or as a string literal", literal.span()))?;
+        let spans = vec![(literal.span(), 0, text.len(), 1)];
+        Ok(Source { text, literal: true, spans })
+    }
+
+    /// Returns an error covering the tokens that overlap a byte range of the text, or the whole input if
+    /// there are none.
+    fn error(&self, message: &str, overlaps: impl Fn(usize, usize, usize) -> bool) -> Error {
+        let spans: Vec<Span> = self
+            .spans
+            .iter()
+            .filter(|(_, start, end, line)| overlaps(*start, *end, *line))
+            .map(|(span, _, _, _)| *span)
+            .collect();
+        match (spans.first(), spans.last()) {
+            (Some(start), Some(end)) => Error { message: message.to_string(), start: *start, end: *end },
+            _ => Error::new(message, Span::call_site()),
+        }
+    }
+
+    fn line_error(&self, message: &str, line: usize) -> Error {
+        self.error(message, |_, _, token_line| token_line == line)
+    }
+}
+
+fn flatten(tokens: impl IntoIterator<Item = TokenTree>, flattened: &mut Vec<(String, Span)>) {
+    for token in tokens {
+        match token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+                if !open.is_empty() {
+                    flattened.push((open.to_string(), group.span_open()));
+                }
+                flatten(group.stream(), flattened);
+                if !close.is_empty() {
+                    flattened.push((close.to_string(), group.span_close()));
+                }
+            }
+            token => flattened.push((token.to_string(), token.span())),
+        }
+    }
+}
+
+/// Returns the contents of a Rust string literal, raw or not, as it is written in the source.
+fn unquote(literal: &str) -> Option<String> {
+    if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let contents = raw[hashes..].strip_prefix('"')?.strip_suffix(&raw[..hashes])?.strip_suffix('"')?;
+        return Some(contents.to_string());
+    }
+
+    let mut contents = String::new();
+    let mut chars = literal.strip_prefix('"')?.strip_suffix('"')?.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            contents.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => contents.push('\n'),
+            'r' => contents.push('\r'),
+            't' => contents.push('\t'),
+            '0' => contents.push('\0'),
+            c @ ('\\' | '\'' | '"') => contents.push(c),
+            'x' => {
+                let code: String = chars.by_ref().take(2).collect();
+                contents.push(char::from(u8::from_str_radix(&code, 16).ok()?));
+            }
+            'u' => {
+                let code: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                contents.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            }
+            '\n' => {
+                // a line continuation skips the newline and the next line's indentation
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            }
+            _ => return None,
+        }
+    }
+    Some(contents)
+}
+
+/// Builds the grammar and checks what `bnf_parser check` reports as errors and that it is LL(1).
+fn check_grammar(source: &Source) -> Result<ContextFreeGrammar, Error> {
+    let grammar = build_grammar(&source.text).map_err(|error| {
+        // a string literal is one token, so the message has to say where in it the error is
+        let message = if source.literal { error.to_string() } else { error.message.clone() };
+        match error.span {
+            Some(span) => source.error(&message, |start, end, _| start < span.end.max(span.start + 1) && span.start < end),
+            None => Error::new(&message, Span::call_site()),
+        }
+    })?;
+
+    let undefined = lint::lint(&grammar, &source.text).into_iter().find(|lint| lint.code == LintCode::UndefinedVariable);
+    if let Some(lint) = undefined {
+        let message = if source.literal { lint.to_string() } else { lint.message };
+        return Err(match lint.line {
+            Some(line) => source.line_error(&message, line),
+            None => Error::new(&message, Span::call_site()),
+        });
+    }
+
+    if let Some(conflict) = ll::ll1_table(&grammar).conflicts().first() {
+        let message = format!("the grammar is not LL(1): {}", conflict);
+        let lines: Vec<usize> = conflict.rules.iter().filter_map(|rule| rule.span.map(|span| span.line)).collect();
+        return Err(source.error(&message, |_, _, line| lines.contains(&line)));
+    }
+
+    Ok(grammar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote(r#""<S> ::= \"a\" | b\n""#).unwrap(), "<S> ::= \"a\" | b\n");
+        assert_eq!(unquote(r###"r#"<S> ::= " <S> ""#"###).unwrap(), "<S> ::= \" <S> \"");
+        assert_eq!(unquote(r#""\u{3b5} \x41\
+                              B""#).unwrap(), "ε AB");
+        assert_eq!(unquote("42"), None);
+        assert_eq!(unquote(r#"b"bytes""#), None);
+    }
+}